@@ -18,26 +18,463 @@ export interface ScreenshotPayload {
   y: number
   buffer: Buffer
   mimeType: string
+  /** Display scale of the captured monitor; `width`/`height` are physical pixels. */
+  scaleFactor: number
+  /**
+   * Monitor the capture came from, in `move_window_to_monitor` order;
+   * 4294967295 (`u32::MAX`) for `capture_all_monitors`.
+   */
+  monitorIndex: number
+  /** Display device name such as `\\.\DISPLAY1`, or `"virtual"`. */
+  deviceName: string
+}
+/** Unencoded top-down RGBA pixels, 4 bytes per pixel. */
+export interface RawImage {
+  width: number
+  height: number
+  originX: number
+  originY: number
+  buffer: Buffer
+  scaleFactor: number
+  monitorIndex: number
+  deviceName: string
+}
+export const enum ImageFormat {
+  Png = 'Png',
+  Jpeg = 'Jpeg',
+  WebP = 'WebP'
+}
+export interface ScreenshotOptions {
+  includeCursor?: boolean
+  /** Defaults to PNG. */
+  format?: ImageFormat
+  /** JPEG quality, 1-100 (default 85). WebP is encoded losslessly. */
+  quality?: number
+  /** For `capture_monitor_screenshot`: leave out the taskbar and docked app bars. */
+  workAreaOnly?: boolean
+  /** Wait this long before capturing, e.g. to let the user arrange windows. */
+  delayMs?: number
+}
+export interface WindowInfo {
+  handle: string
+  title?: string
+  className?: string
+  processId?: number
+  processPath?: string
+  isMinimized: boolean
+  isMaximized: boolean
+  x: number
+  y: number
+  width: number
+  height: number
+}
+export const enum WindowState {
+  Minimize = 'Minimize',
+  Maximize = 'Maximize',
+  Restore = 'Restore',
+  Close = 'Close'
 }
 export interface ClipboardItem {
   sequence: number
   timestamp: number
+  /** Legacy comma-joined form of `formats`; `unknown` when nothing was read. */
   format: string
+  /** Payloads present, from `text`, `html`, `rtf`, `files` and `image`. */
+  formats: Array<string>
   text?: string
+  /**
+   * Text as it was on the clipboard, set when `normalizeNewlines`
+   * changed it. Restoring the item writes this back.
+   */
+  rawText?: string
   html?: string
+  rtf?: string
+  files?: Array<string>
   image?: Buffer
+  /** MIME type of `image`, per the watcher's `imageFormat`. */
+  imageMimeType?: string
+  imageOriginalWidth?: number
+  imageOriginalHeight?: number
+  sourceApp?: string
+  sourceTitle?: string
+  sourceUrl?: string
+  truncated: boolean
+  kind: string
+}
+export interface ClipboardWatcherOptions {
+  maxImageDimension?: number
+  maxItemBytes?: number
+  dedupWindow?: number
+  historyCapacity?: number
+  /** Retries when another process holds the clipboard open (default 5). */
+  openRetries?: number
+  /** First retry delay, doubled after each retry (default 20). */
+  openRetryDelayMs?: number
+  /** Upper bound for a single retry delay (default 200). */
+  openRetryMaxDelayMs?: number
+  /**
+   * Encoding for captured images (default PNG). JPEG flattens
+   * transparency onto white.
+   */
+  imageFormat?: ImageFormat
+  /** JPEG quality, 1-100 (default 85). */
+  imageQuality?: number
+  /** Convert text line endings to `\n` (default false). */
+  normalizeNewlines?: boolean
+}
+export interface ClipboardStats {
+  text: number
+  html: number
+  image: number
+  files: number
+  total: number
+  /** Times the clipboard stayed locked by another process through every retry. */
+  openFailures: number
+}
+export interface IconData {
+  png: Buffer
+  width: number
+  height: number
+  wasUpscaled: boolean
+}
+export interface IconRequest {
+  iconPath: string
+  iconIndex?: number
+  size?: number
+  /**
+   * Crop transparent padding around the glyph so it fills more of `size`.
+   * Icons without padding are left as they are.
+   */
+  autoTrim?: boolean
+  /** Pixels of the output kept around a trimmed glyph (default 0). */
+  trimMargin?: number
+}
+export interface IconResult {
+  ok: boolean
+  png?: Buffer
+  error?: string
 }
 export declare function scanApps(startMenuPaths: Array<string>, registryPaths: Array<string>): Promise<Array<AppInfo>>
-export declare function captureMonitorScreenshot(): Promise<ScreenshotPayload>
-export declare function subscribeClipboard(callback: (err: Error | null, arg: ClipboardItem) => any): void
+/**
+ * Apps pinned to the taskbar (`source` = `pinned_taskbar`) or, on Windows 10,
+ * the Start menu (`pinned_start`), e.g. to rank them higher.
+ */
+export declare function scanPinnedApps(): Promise<Array<AppInfo>>
+export interface RecentItem {
+  path: string
+  name: string
+  /** Unix milliseconds when the item was last opened. */
+  lastAccessed: number
+}
+/**
+ * Up to `limit` recently opened files and folders from the shell's Recent
+ * list, newest first; items whose target is gone are left out.
+ */
+export declare function scanRecentDocuments(limit: number): Promise<Array<RecentItem>>
+export const enum AppChangeKind {
+  Added = 'Added',
+  Changed = 'Changed',
+  Removed = 'Removed'
+}
+export interface AppChangeEvent {
+  kind: AppChangeKind
+  id: string
+  /** The re-resolved app; absent for removals. */
+  app?: AppInfo
+}
+/**
+ * Calls `callback` as apps under `paths` are added, changed or removed,
+ * compared against a scan taken when subscribing. Calling it again replaces
+ * the previous subscription.
+ */
+export declare function subscribeAppChanges(paths: ScanPaths, callback: (err: Error | null, arg: AppChangeEvent) => any): void
+export declare function unsubscribeAppChanges(): void
+export interface Rect {
+  x: number
+  y: number
+  width: number
+  height: number
+}
+/** Rectangles are in physical pixels of the virtual screen. */
+export interface MonitorInfo {
+  /** The index `move_window_to_monitor` and `DxgiCapturer` expect. */
+  index: number
+  bounds: Rect
+  /** `bounds` minus the taskbar and docked app bars. */
+  workArea: Rect
+  primary: boolean
+  scaleFactor: number
+  /** Display device name such as `\\.\DISPLAY1`. */
+  deviceName: string
+}
+export declare function getMonitorLayout(): Array<MonitorInfo>
+export declare function captureMonitorScreenshot(options?: ScreenshotOptions | undefined | null): Promise<ScreenshotPayload>
+/**
+ * Captures the monitor under the cursor without encoding it, for pipelines
+ * that want pixels rather than an image file.
+ */
+export declare function captureMonitorRaw(): Promise<RawImage>
+/** Captures the monitor under the cursor straight onto the clipboard as a bitmap. */
+export declare function captureMonitorToClipboard(): Promise<void>
+/**
+ * Captures every monitor as one image; `x`/`y` are negative when a monitor
+ * sits left of or above the primary one.
+ */
+export declare function captureAllMonitors(options?: ScreenshotOptions | undefined | null): Promise<ScreenshotPayload>
+/**
+ * Captures a window by handle (as returned by `capture_foreground_window`),
+ * even when it is covered by other windows.
+ */
+export declare function captureWindow(handle: string, options?: ScreenshotOptions | undefined | null): Promise<ScreenshotPayload>
+/** Like `capture_window`, but only the client area: no title bar or borders. */
+export declare function captureWindowClient(handle: string, options?: ScreenshotOptions | undefined | null): Promise<ScreenshotPayload>
+/** Captures a rectangle of the virtual screen, clamped to its bounds. */
+export declare function captureRegion(x: number, y: number, width: number, height: number, options?: ScreenshotOptions | undefined | null): Promise<ScreenshotPayload>
+export declare function subscribeClipboard(callback: (err: Error | null, arg: ClipboardItem) => any, options?: ClipboardWatcherOptions | undefined | null): void
 export declare function unsubscribeClipboard(): void
+export declare function pauseClipboard(): void
+export declare function resumeClipboard(): void
+export declare function getClipboardStats(): ClipboardStats
+export declare function resetClipboardStats(): void
+export declare function getClipboardHistory(limit: number): Array<ClipboardItem>
+export declare function searchClipboardHistory(query: string, limit: number): Array<ClipboardItem>
+export declare function getCurrentClipboard(): ClipboardItem | null
+export declare function setClipboardText(text: string): void
+export declare function setClipboardImage(png: Buffer): void
+export declare function restoreClipboardItem(item: ClipboardItem): void
+export declare function clearClipboard(): void
 export declare function captureForegroundWindow(): string | null
+export declare function getForegroundProcessPath(): string | null
+export interface Theme {
+  /** Apps are set to use the dark theme. */
+  dark: boolean
+  highContrast: boolean
+}
+export declare function getSystemTheme(): Theme
+/** The window accent color as `0xAARRGGBB`, or `null` if it can't be read. */
+export declare function getAccentColor(): number | null
+/**
+ * Calls `callback` with the new theme when dark mode, high contrast or the
+ * accent color changes. Calling it again replaces the previous callback.
+ */
+export declare function watchThemeChanges(callback: (err: Error | null, arg: Theme) => any): void
+export declare function unwatchThemeChanges(): void
+/** Opens the containing folder in Explorer with the file selected. */
+export declare function revealInExplorer(path: string): void
+/**
+ * Opens an `http`, `https`, `mailto`, `ms-settings` or `ms-windows-store` URL
+ * with its default handler; other schemes are rejected.
+ */
+export declare function openUrl(url: string): void
+export interface ProcessInfo {
+  pid: number
+  name: string
+  /** Full image path where permissions allow, otherwise the executable name. */
+  exePath: string
+  parentPid: number
+}
+export declare function listProcesses(): Array<ProcessInfo>
+/**
+ * Ends a process. Unless `force` is set, its windows are asked to close first
+ * and it is only terminated if it hasn't exited after two seconds. Fails with
+ * `E_ACCESS_DENIED` for elevated processes when this one isn't.
+ */
+export declare function killProcess(pid: number, force: boolean): Promise<void>
+export interface CommandResult {
+  /** `null` when the process was killed after timing out. */
+  exitCode?: number
+  stdout: string
+  stderr: string
+  timedOut: boolean
+}
+/**
+ * Runs a program without a console window and resolves with its output once
+ * it exits. With `timeout_ms`, the process and everything it started are
+ * killed when the time is up; background processes it leaves behind are ended
+ * once it exits.
+ */
+export declare function runCommand(exe: string, args: Array<string>, cwd?: string | undefined | null, timeoutMs?: number | undefined | null): Promise<CommandResult>
+/** Whether this process runs elevated, e.g. to skip a redundant "run as admin". */
+export declare function isElevated(): boolean
 export declare function focusWindow(handle: string): void
+/**
+ * Flashes the window's taskbar button `count` times, then keeps it highlighted
+ * until the user switches to the window.
+ */
+export declare function flashWindow(handle: string, count: number): void
+/**
+ * Keeps the window above all non-topmost windows (or releases it) without
+ * activating it.
+ */
+export declare function setWindowTopmost(handle: string, topmost: boolean): void
+export declare function getWindowInfo(handle: string): WindowInfo | null
+/**
+ * The top-level window under a screen point, e.g. the cursor position from
+ * `get_cursor_pos`.
+ */
+export declare function windowFromPoint(x: number, y: number): WindowInfo | null
+export declare function watchForegroundChanges(callback: (err: Error | null, arg: WindowInfo) => any): void
+export declare function unwatchForegroundChanges(): void
+export declare function setWindowState(handle: string, state: WindowState): void
+/** Omitted values fall back to the defaults (12ms focus settle, 35ms key delay). */
+export declare function setInputTiming(focusSettleMs?: number | undefined | null, keyDelayMs?: number | undefined | null): void
+export declare function getIdleTimeMs(): number
+/**
+ * Virtual-key code (e.g. 0x10 for Shift) currently held down, whichever
+ * window has focus.
+ */
+export declare function isKeyPressed(vk: number): boolean
+export interface Modifiers {
+  ctrl: boolean
+  alt: boolean
+  shift: boolean
+  win: boolean
+}
+/** Modifier keys currently held down; left and right are not distinguished. */
+export declare function getModifierState(): Modifiers
+export interface Point {
+  x: number
+  y: number
+}
+/** The pointer position in physical virtual-screen pixels. */
+export declare function getCursorPos(): Point
+/** Moves the pointer; fails with `E_INVALID_ARGUMENT` outside the virtual screen. */
+export declare function setCursorPos(x: number, y: number): void
+export declare function moveWindowToMonitor(handle: string, monitorIndex: number): void
 export declare function pasteClipboard(): void
+export declare function sendHotkey(spec: string): void
+/**
+ * Types `text` regardless of keyboard layout; line breaks are sent as Enter.
+ * Resolves once the last character has been sent.
+ */
+export declare function typeText(text: string, delayMs?: number | undefined | null): Promise<void>
+/**
+ * Holds the virtual keys down for `duration_ms` (at most 10s), then releases
+ * them in reverse order. Resolves once the keys are released.
+ */
+export declare function holdKeys(vks: Array<number>, durationMs: number): Promise<void>
+export declare function registerGlobalHotkey(id: number, modifiers: number, vk: number, callback: (err: Error | null, arg: number) => any): void
+export declare function unregisterGlobalHotkey(id: number): void
+export declare function copyClipboard(): void
+export declare function copySelection(restore: boolean): string | null
+export declare function pasteAsPlainText(restore: boolean): void
+export interface LogRecord {
+  /** `error`, `warn`, `info`, `debug` or `trace`. */
+  level: string
+  /** Module that logged the record, e.g. `wolong_core::app_index`. */
+  target: string
+  message: string
+}
+/**
+ * Forwards native log records to `callback`, starting at `info` level. The
+ * callback doesn't keep the process alive.
+ */
+export declare function initLogging(callback: (err: Error | null, arg: LogRecord) => any): void
+/** One of `off`, `error`, `warn`, `info`, `debug` or `trace`. */
+export declare function setLogLevel(level: string): void
 export declare function version(): string
+export interface BuildInfo {
+  version: string
+  targetTriple: string
+  rustcVersion: string
+  /** Short commit hash, or `"unknown"` when built outside a git checkout. */
+  gitSha: string
+  /** Unix seconds; honours `SOURCE_DATE_EPOCH`. */
+  buildTimestamp: number
+}
+/** Identifies the exact native build, for bug reports. */
+export declare function buildInfo(): BuildInfo
 export interface ScanPaths {
   startMenuPaths: Array<string>
   registryPaths: Array<string>
 }
+export const enum KnownFolder {
+  /** Start Menu programs shared by all users. */
+  CommonPrograms = 'CommonPrograms',
+  /** The current user's Start Menu programs. */
+  Programs = 'Programs',
+  Desktop = 'Desktop',
+  PublicDesktop = 'PublicDesktop',
+  Startup = 'Startup',
+  CommonStartup = 'CommonStartup',
+  Documents = 'Documents',
+  Downloads = 'Downloads',
+  LocalAppData = 'LocalAppData',
+  RoamingAppData = 'RoamingAppData',
+  ProgramData = 'ProgramData',
+  ProgramFiles = 'ProgramFiles',
+  /** Shortcuts to recently opened files, as read by `scan_recent_documents`. */
+  Recent = 'Recent'
+}
+/** Resolves a shell folder, following localized names and folder redirection. */
+export declare function getKnownFolder(folder: KnownFolder): string | null
+/**
+ * The desktop app that opens `protocol` URLs, e.g. `http` for the default
+ * browser or `mailto` for the mail client. `null` when nothing is registered
+ * or the handler is a packaged (Store) app.
+ */
+export declare function getDefaultAppFor(protocol: string): AppInfo | null
 export declare function getDefaultScanPaths(): ScanPaths
+/**
+ * Re-reads environment variables used to expand `%VAR%` paths, e.g. after
+ * changing `process.env`. `scan_apps` refreshes them itself.
+ */
+export declare function refreshEnvCache(): void
 export declare function extractIcon(iconPath: string): Buffer | null
+/**
+ * Like `extract_icon`, but reports the source resolution; without `size` the
+ * icon is returned at that resolution.
+ */
+export declare function extractIconInfo(iconPath: string, size?: number | undefined | null): IconData | null
+export interface FallbackIcon {
+  png: Buffer
+  /** `primary`, `fallback` or `generic`: which candidate the icon came from. */
+  source: string
+}
+/**
+ * Tries `icon_path`, then the icon of `fallback_exe`, then the generic
+ * application icon.
+ */
+export declare function extractIconWithFallback(iconPath: string, fallbackExe: string | undefined | null, size: number): FallbackIcon | null
+export declare function extractIconDataUrl(iconPath: string, size: number): string | null
+/**
+ * The icon's dominant color as `0xAARRGGBB`, ignoring transparent and
+ * near-white/near-black pixels; `null` when no color is left.
+ */
+export declare function iconDominantColor(iconPath: string): number | null
+export declare function extractThumbnail(path: string, size: number): Buffer | null
+export declare function extractUwpLogo(packageFamilyName: string, size: number): Buffer | null
+/** Results are in the same order as `requests`. */
+export declare function extractIcons(requests: Array<IconRequest>): Promise<Array<IconResult>>
+/**
+ * Scans like `scan_apps`, then extracts every app's icon at `icon_size` into
+ * the icon cache with at most `concurrency` extractions in flight, so later
+ * `extract_icon_*` calls at that size are cache hits. Resolves once all icons
+ * are cached; icons that fail to extract are skipped.
+ */
+export declare function warmIndex(paths: ScanPaths, iconSize: number, concurrency: number): Promise<Array<AppInfo>>
+export declare function clearIconCache(): void
+export declare function setIconCacheCapacity(capacity: number): void
+export declare function extractExtensionIcon(extension: string, size: number): Buffer | null
+/**
+ * Streams frames of one monitor, using DXGI Desktop Duplication where
+ * available and GDI otherwise (e.g. over RDP).
+ */
+export declare class DxgiCapturer {
+  /** `monitor_index` follows the monitor order used by `move_window_to_monitor`. */
+  constructor(monitorIndex?: number | undefined | null)
+  /** Whether frames come from Desktop Duplication rather than the GDI fallback. */
+  get usesDxgi(): boolean
+  /**
+   * The next changed frame, or `null` if the screen didn't change within
+   * `timeout_ms` (default 0).
+   */
+  nextFrame(timeoutMs?: number | undefined | null): RawImage | null
+}
+/**
+ * Calls `callback` with each changed frame of a monitor, at most `fps` (capped
+ * at 60) times a second. Frames are dropped while the previous one is still
+ * queued for JS. Starting a stream replaces the running one.
+ */
+export declare function startScreenStream(monitorIndex: number, fps: number, callback: (err: Error | null, arg: RawImage) => any): void
+export declare function stopScreenStream(): void
@@ -1,35 +1,5 @@
-struct ComGuard {
-    initialized: bool,
-}
-
-impl ComGuard {
-    fn new() -> CoreResult<Self> {
-        unsafe {
-            let hr = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
-            if hr.is_ok() {
-                Ok(Self { initialized: true })
-            } else if hr == RPC_E_CHANGED_MODE {
-                Ok(Self { initialized: false })
-            } else {
-                Err(CoreError::Other(anyhow::anyhow!(
-                    "CoInitializeEx failed: {hr}"
-                )))
-            }
-        }
-    }
-}
-
-impl Drop for ComGuard {
-    fn drop(&mut self) {
-        if self.initialized {
-            unsafe {
-                CoUninitialize();
-            }
-        }
-    }
-}
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env,
     path::{Path, PathBuf},
     ptr,
@@ -40,21 +10,20 @@ use walkdir::WalkDir;
 use winreg::{enums::*, RegKey, HKEY};
 
 use crate::{
+    com::{self, Apartment},
     error::{CoreError, CoreResult},
-    utils::{expand_env_vars, hash_id, normalize_path, string_from_wide, wide_string},
+    utils::{
+        expand_env_vars, hash_id, known_folder_path, normalize_path, parse_command_line,
+        refresh_env_cache, string_from_wide, wide_string,
+    },
+    AppInfo, KnownFolder, RecentItem,
 };
-use anyhow::anyhow;
 use windows::{
     core::{Interface, PCWSTR},
     Win32::{
-        Foundation::{MAX_PATH, RPC_E_CHANGED_MODE},
-        System::{
-            Com::{
-                CoCreateInstance, CoInitializeEx, CoUninitialize, IPersistFile,
-                CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, STGM_READ,
-            },
-        },
-        UI::Shell::{IShellLinkW, ShellLink, SLGP_RAWPATH},
+        Foundation::MAX_PATH,
+        System::Com::{CoCreateInstance, IPersistFile, CLSCTX_INPROC_SERVER, STGM_READ},
+        UI::Shell::{IShellLinkW, SHLoadIndirectString, ShellLink, SLGP_RAWPATH},
     },
 };
 
@@ -70,6 +39,19 @@ pub struct AppRecord {
     pub last_modified: u64,
 }
 
+impl From<AppRecord> for AppInfo {
+    fn from(record: AppRecord) -> Self {
+        AppInfo {
+            id: record.id,
+            name: record.name,
+            launch_path: record.launch_path,
+            working_directory: record.working_directory,
+            icon_path: record.icon_path,
+            source: record.source,
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 struct ShortcutInfo {
     target: Option<String>,
@@ -82,16 +64,16 @@ fn parse_shell_shortcut(path: &Path) -> CoreResult<ShortcutInfo> {
     unsafe {
         let shell_link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)
             .map_err(|err| {
-                CoreError::Other(anyhow!("CoCreateInstance(IShellLinkW) failed: {err}"))
+                CoreError::from_hresult("CoCreateInstance(IShellLinkW) failed", err.code())
             })?;
         let persist: IPersistFile = shell_link.cast().map_err(|err| {
-            CoreError::Other(anyhow!("QueryInterface(IPersistFile) failed: {err}"))
+            CoreError::from_hresult("QueryInterface(IPersistFile) failed", err.code())
         })?;
 
         let wide_path = wide_string(&path.to_string_lossy());
         persist
             .Load(PCWSTR(wide_path.as_ptr()), STGM_READ)
-            .map_err(|err| CoreError::Other(anyhow!("persist.Load failed: {err}")))?;
+            .map_err(|err| CoreError::from_hresult("persist.Load failed", err.code()))?;
 
         let mut buffer = [0u16; MAX_PATH as usize];
 
@@ -181,8 +163,14 @@ fn is_uninstaller_target(target: &str, arguments: Option<&str>) -> bool {
     false
 }
 
-pub fn scan_app_records(start_menu_paths: &[String], registry_paths: &[String]) -> CoreResult<Vec<AppRecord>> {
-    let _com_guard = ComGuard::new()?;
+pub fn scan_app_records(
+    start_menu_paths: &[String],
+    registry_paths: &[String],
+) -> CoreResult<Vec<AppRecord>> {
+    com::ensure_initialized(Apartment::Sta)?;
+    // Once per scan rather than per path, so a long-lived process still sees
+    // environment changes between scans.
+    refresh_env_cache();
     let mut map: HashMap<String, AppRecord> = HashMap::new();
 
     for path_str in start_menu_paths {
@@ -204,92 +192,262 @@ pub fn scan_app_records(start_menu_paths: &[String], registry_paths: &[String])
     Ok(values)
 }
 
-fn ingest_start_menu(map: &mut HashMap<String, AppRecord>, root: &Path, source_path: &str) -> CoreResult<()> {
+/// Real Start Menu trees are a few folders deep; this only stops runaway walks.
+const START_MENU_MAX_DEPTH: usize = 16;
+
+pub(crate) fn ingest_start_menu(
+    map: &mut HashMap<String, AppRecord>,
+    root: &Path,
+    source_path: &str,
+) -> CoreResult<()> {
     if !root.exists() {
         return Ok(());
     }
 
+    // A junction pointing back at an ancestor would otherwise be walked over
+    // and over, so each real directory is entered once.
+    let mut visited = HashSet::new();
     for entry in WalkDir::new(root)
         .follow_links(true)
+        .max_depth(START_MENU_MAX_DEPTH)
         .into_iter()
-        .filter_map(Result::ok)
+        .filter_entry(|entry| {
+            !entry.file_type().is_dir()
+                || visited.insert(
+                    std::fs::canonicalize(entry.path())
+                        .unwrap_or_else(|_| entry.path().to_path_buf()),
+                )
+        })
+        .filter_map(|entry| {
+            entry
+                .map_err(|err| log::trace!("skip start menu entry: {err}"))
+                .ok()
+        })
         .filter(|entry| entry.file_type().is_file())
     {
         let path = entry.into_path();
-        if !matches!(
-            path.extension()
-                .and_then(|ext| ext.to_str())
-                .map(|s| s.eq_ignore_ascii_case("lnk")),
-            Some(true)
-        ) {
+        if !is_shortcut_path(&path) {
             continue;
         }
+        if let Some(record) = shortcut_record(&path, source_path) {
+            map.insert(record.id.clone(), record);
+        }
+    }
 
-        let name = path
-            .file_stem()
-            .and_then(|stem| stem.to_str())
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .unwrap_or_else(|| "Unknown Shortcut".to_string());
+    Ok(())
+}
 
-        let shortcut = match parse_shell_shortcut(&path) {
-            Ok(info) => info,
-            Err(err) => {
-                log::trace!("skip shortcut {:?}: {err}", path);
+/// Pin folders below `%APPDATA%\Microsoft\Internet Explorer\Quick Launch\User Pinned`
+/// and the `source` their apps are tagged with.
+const PINNED_FOLDERS: [(&str, &str); 2] =
+    [("TaskBar", "pinned_taskbar"), ("StartMenu", "pinned_start")];
+
+/// Apps pinned to the taskbar or (on Windows 10) the Start menu. Windows 11
+/// keeps Start pins in an undocumented binary file, so only taskbar pins show
+/// up there.
+pub fn scan_pinned_apps() -> CoreResult<Vec<AppRecord>> {
+    com::ensure_initialized(Apartment::Sta)?;
+    refresh_env_cache();
+    let Some(roaming) = known_folder_path(KnownFolder::RoamingAppData)
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+    else {
+        return Ok(Vec::new());
+    };
+    let pinned_root = roaming.join(r"Microsoft\Internet Explorer\Quick Launch\User Pinned");
+
+    let mut map = HashMap::new();
+    for (folder, source) in PINNED_FOLDERS {
+        // Only the top level: the taskbar's `ImplicitAppShortcuts` subfolder
+        // holds jump-list helpers, not pins.
+        let Ok(entries) = std::fs::read_dir(pinned_root.join(folder)) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !path.is_file() || !is_shortcut_path(&path) {
                 continue;
             }
-        };
+            if let Some(record) = shortcut_record(&path, source) {
+                map.insert(record.id.clone(), record);
+            }
+        }
+    }
 
-        let Some(target_path) = shortcut.target.clone() else {
-            log::trace!("skip shortcut without target {:?}", path);
+    let mut values: Vec<AppRecord> = map.into_values().collect();
+    values.sort_by_key(|record| record.name.to_lowercase());
+    Ok(values)
+}
+
+/// A file or folder from the shell's recent items list.
+pub struct RecentDocument {
+    /// Target path, with forward slashes like the app index.
+    pub path: String,
+    pub name: String,
+    /// Unix milliseconds when the shell last recorded the item being opened.
+    pub last_accessed: i64,
+}
+
+impl From<RecentDocument> for RecentItem {
+    fn from(document: RecentDocument) -> Self {
+        RecentItem {
+            path: document.path,
+            name: document.name,
+            last_accessed: document.last_accessed,
+        }
+    }
+}
+
+/// Up to `limit` recently opened files and folders, newest first. Items whose
+/// target no longer exists are skipped.
+pub fn scan_recent_documents(limit: usize) -> CoreResult<Vec<RecentDocument>> {
+    com::ensure_initialized(Apartment::Sta)?;
+    let Some(recent_dir) = known_folder_path(KnownFolder::Recent) else {
+        return Ok(Vec::new());
+    };
+    let Ok(entries) = std::fs::read_dir(&recent_dir) else {
+        return Ok(Vec::new());
+    };
+
+    // The folder can hold thousands of shortcuts; the shell rewrites one each
+    // time its target is opened, so sort on that and only resolve what's needed.
+    let mut shortcuts: Vec<(PathBuf, SystemTime)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            is_shortcut_path(&path).then_some((path, modified))
+        })
+        .collect();
+    shortcuts.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+    let mut documents = Vec::new();
+    for (shortcut, modified) in shortcuts {
+        if documents.len() >= limit {
+            break;
+        }
+        let Some(target) = parse_shell_shortcut(&shortcut)
+            .ok()
+            .and_then(|info| info.target)
+        else {
             continue;
         };
+        let target = expand_env_vars(&target);
+        let target_path = Path::new(&target);
+        if !target_path.exists() {
+            continue;
+        }
 
-        let shortcut_path = normalize_path(&path);
-        // For shortcuts, use the target exe as launch_path instead of the shortcut itself
-        let launch_path = normalize_path(Path::new(&target_path));
-        let working_directory = shortcut.working_directory.or_else(|| {
-            Path::new(&target_path)
-                .parent()
-                .map(|dir| normalize_path(dir))
+        documents.push(RecentDocument {
+            name: target_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| target.clone()),
+            path: normalize_path(target_path),
+            last_accessed: modified
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as i64)
+                .unwrap_or(0),
         });
-        let icon_path = shortcut.icon_path.or_else(|| Some(target_path.clone()));
+    }
+    Ok(documents)
+}
 
-        let metadata = std::fs::metadata(&path).ok();
-        let modified = metadata
-            .and_then(|meta| meta.modified().ok())
-            .and_then(|stamp| stamp.duration_since(UNIX_EPOCH).ok())
-            .map(|duration| duration.as_secs())
-            .unwrap_or_else(|| {
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0)
-            });
+pub(crate) fn is_shortcut_path(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|s| s.eq_ignore_ascii_case("lnk")),
+        Some(true)
+    )
+}
 
-        // Use shortcut path for ID to maintain consistency for the same shortcut
-        let id = hash_id(&["start_menu", &shortcut_path]);
-        let record = AppRecord {
-            id: id.clone(),
-            name,
-            launch_path,
-            working_directory,
-            icon_path,
-            source: source_path.to_string(),
-            last_modified: modified,
-        };
+fn shortcut_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "Unknown Shortcut".to_string())
+}
+
+/// The id `shortcut_record` gives `path`, computable after the file is gone.
+pub(crate) fn shortcut_id(path: &Path) -> String {
+    // Keyed by the shortcut itself rather than its target, so shortcuts
+    // sharing a target stay separate entries.
+    hash_id(&["start_menu", &normalize_path(path)])
+}
+
+/// Resolves one Start Menu shortcut, or `None` if it can't be launched.
+pub(crate) fn shortcut_record(path: &Path, source_path: &str) -> Option<AppRecord> {
+    let name = shortcut_name(path);
+
+    let shortcut = match parse_shell_shortcut(path) {
+        Ok(info) => info,
+        Err(err) => {
+            log::trace!("skip shortcut {:?}: {err}", path);
+            return None;
+        }
+    };
+
+    let Some(target_path) = shortcut.target.clone() else {
+        log::trace!("skip shortcut without target {:?}", path);
+        return None;
+    };
+
+    // For shortcuts, use the target exe as launch_path instead of the shortcut itself
+    let launch_path = normalize_path(Path::new(&target_path));
+    let working_directory = shortcut.working_directory.or_else(|| {
+        Path::new(&target_path)
+            .parent()
+            .map(|dir| normalize_path(dir))
+    });
+    let icon_path = shortcut.icon_path.or_else(|| Some(target_path.clone()));
+
+    let metadata = std::fs::metadata(path).ok();
+    let modified = metadata
+        .and_then(|meta| meta.modified().ok())
+        .and_then(|stamp| stamp.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    Some(AppRecord {
+        id: shortcut_id(path),
+        name,
+        launch_path,
+        working_directory,
+        icon_path,
+        source: source_path.to_string(),
+        last_modified: modified,
+    })
+}
+
+pub(crate) fn ingest_registry_path(
+    map: &mut HashMap<String, AppRecord>,
+    registry_path: &str,
+) -> CoreResult<()> {
+    let (hive, key) = open_registry_path(registry_path)?;
 
-        map.insert(id, record);
+    for entry in key.enum_keys().flatten() {
+        if let Err(err) = ingest_uninstall_entry(map, &key, &entry, hive, registry_path) {
+            log::trace!("skip registry app {entry}: {err}");
+        }
     }
 
     Ok(())
 }
 
-fn ingest_registry_path(map: &mut HashMap<String, AppRecord>, registry_path: &str) -> CoreResult<()> {
+/// Opens a `HIVE\\subkey` path such as an Uninstall key for reading.
+pub(crate) fn open_registry_path(registry_path: &str) -> CoreResult<(HKEY, RegKey)> {
     // Parse registry path format: "HKEY_LOCAL_MACHINE\\SOFTWARE\\..."
     let parts: Vec<&str> = registry_path.splitn(2, '\\').collect();
     if parts.len() != 2 {
-        return Err(CoreError::Other(anyhow::anyhow!("invalid registry path format")));
+        return Err(CoreError::InvalidArgument(format!(
+            "invalid registry path '{registry_path}', expected HIVE\\subkey"
+        )));
     }
 
     let hive_str = parts[0];
@@ -298,20 +456,28 @@ fn ingest_registry_path(map: &mut HashMap<String, AppRecord>, registry_path: &st
     let hive = match hive_str {
         "HKEY_LOCAL_MACHINE" => HKEY_LOCAL_MACHINE,
         "HKEY_CURRENT_USER" => HKEY_CURRENT_USER,
-        _ => return Err(CoreError::Other(anyhow::anyhow!("unsupported registry hive: {}", hive_str))),
+        _ => {
+            return Err(CoreError::InvalidArgument(format!(
+                "unsupported registry hive: {hive_str}"
+            )))
+        }
     };
 
     let root = RegKey::predef(hive);
-    let key = root.open_subkey_with_flags(subkey, KEY_READ)
-        .map_err(|e| CoreError::Other(anyhow::anyhow!("failed to open registry key {}: {}", registry_path, e)))?;
-
-    for entry in key.enum_keys().flatten() {
-        if let Err(err) = ingest_uninstall_entry(map, &key, &entry, hive, registry_path) {
-            log::trace!("skip registry app {entry}: {err}");
-        }
-    }
-
-    Ok(())
+    let key = root
+        .open_subkey_with_flags(subkey, KEY_READ)
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => {
+                CoreError::NotFound(format!("registry key {registry_path} not found"))
+            }
+            std::io::ErrorKind::PermissionDenied => {
+                CoreError::AccessDenied(format!("cannot read registry key {registry_path}: {err}"))
+            }
+            _ => CoreError::Other(anyhow::anyhow!(
+                "failed to open registry key {registry_path}: {err}"
+            )),
+        })?;
+    Ok((hive, key))
 }
 
 fn ingest_uninstall_entry(
@@ -416,17 +582,112 @@ fn ingest_uninstall_entry(
     Ok(())
 }
 
-fn start_menu_roots() -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-    if let Ok(program_data) = env::var("PROGRAMDATA") {
-        let path = Path::new(&program_data).join("Microsoft\\Windows\\Start Menu\\Programs");
-        paths.push(path);
+const URL_ASSOCIATIONS_KEY: &str = r"Software\Microsoft\Windows\Shell\Associations\UrlAssociations";
+
+/// The desktop app that opens `protocol` URLs (`http`, `https`, `mailto`, ...),
+/// honouring the user's choice in Settings over the machine-wide handler.
+/// `None` when nothing is registered or the handler is a packaged (UWP) app,
+/// which has no `shell\open\command` to launch.
+pub fn default_app_for(protocol: &str) -> CoreResult<Option<AppRecord>> {
+    let protocol = protocol.trim().trim_end_matches(':').to_ascii_lowercase();
+    if protocol.is_empty() || protocol.contains(['\\', '/']) {
+        return Err(CoreError::InvalidArgument(format!(
+            "'{protocol}' is not a protocol name"
+        )));
     }
-    if let Ok(app_data) = env::var("APPDATA") {
-        let path = Path::new(&app_data).join("Microsoft\\Windows\\Start Menu\\Programs");
-        paths.push(path);
+
+    let choice_path = format!(r"{URL_ASSOCIATIONS_KEY}\{protocol}\UserChoice");
+    let user_choice = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(&choice_path)
+        .and_then(|key| key.get_value::<String, _>("ProgId"))
+        .ok()
+        .filter(|prog_id| !prog_id.trim().is_empty());
+    // Without a user choice, the protocol's own class key names the handler.
+    let (prog_id, source) = match user_choice {
+        Some(prog_id) => (prog_id, format!(r"HKEY_CURRENT_USER\{choice_path}")),
+        None => (protocol.clone(), format!(r"HKEY_CLASSES_ROOT\{protocol}")),
+    };
+
+    let Ok(handler) = RegKey::predef(HKEY_CLASSES_ROOT).open_subkey(&prog_id) else {
+        return Ok(None);
+    };
+    let Ok(command) = handler
+        .open_subkey(r"shell\open\command")
+        .and_then(|key| key.get_value::<String, _>(""))
+    else {
+        return Ok(None);
+    };
+    let (exe, _) = parse_command_line(&expand_env_vars(&command));
+    if exe.is_empty() {
+        return Ok(None);
     }
-    paths
+    let exe_path = Path::new(&exe);
+    let launch_path = normalize_path(exe_path);
+
+    let name = handler
+        .open_subkey("Application")
+        .and_then(|key| key.get_value::<String, _>("ApplicationName"))
+        .ok()
+        .and_then(|name| resolve_indirect_string(&name))
+        .or_else(|| {
+            exe_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| prog_id.clone());
+    let icon_path = handler
+        .open_subkey("DefaultIcon")
+        .and_then(|key| key.get_value::<String, _>(""))
+        .ok()
+        // Often `"C:\...\app.exe",0`; quotes can't occur inside a path.
+        .map(|icon| expand_env_vars(icon.replace('"', "").trim()))
+        .filter(|icon| !icon.is_empty())
+        .or_else(|| Some(launch_path.clone()));
+
+    Ok(Some(AppRecord {
+        id: hash_id(&["protocol", &protocol, &prog_id, &launch_path]),
+        name,
+        working_directory: exe_path.parent().map(normalize_path),
+        launch_path,
+        icon_path,
+        source,
+        last_modified: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+    }))
+}
+
+/// Resolves `@file,-id` and `@{package?ms-resource://...}` references as used
+/// by `ApplicationName`; plain strings pass through.
+fn resolve_indirect_string(value: &str) -> Option<String> {
+    let value = value.trim();
+    if !value.starts_with('@') {
+        return (!value.is_empty()).then(|| value.to_string());
+    }
+    let wide = wide_string(value);
+    let mut buffer = [0u16; 512];
+    unsafe { SHLoadIndirectString(PCWSTR(wide.as_ptr()), &mut buffer, None) }.ok()?;
+    string_from_wide(&buffer)
+}
+
+fn start_menu_roots() -> Vec<PathBuf> {
+    // The environment variables only cover the default layout; the known folder
+    // lookup follows redirection.
+    let roots = [
+        (KnownFolder::CommonPrograms, "PROGRAMDATA"),
+        (KnownFolder::Programs, "APPDATA"),
+    ];
+    roots
+        .into_iter()
+        .filter_map(|(folder, fallback_var)| {
+            known_folder_path(folder).map(PathBuf::from).or_else(|| {
+                env::var(fallback_var)
+                    .ok()
+                    .map(|base| Path::new(&base).join("Microsoft\\Windows\\Start Menu\\Programs"))
+            })
+        })
+        .collect()
 }
 
 pub fn get_default_scan_paths() -> (Vec<String>, Vec<String>) {
@@ -448,17 +709,25 @@ pub fn get_default_scan_paths() -> (Vec<String>, Vec<String>) {
     (start_menu_paths, registry_paths)
 }
 
+/// Takes the path out of `path[,index]` values such as `DisplayIcon`, which some
+/// installers write as a quoted command line or with arguments after the exe.
 fn clean_path_candidate(input: String) -> Option<String> {
-    let trimmed = input.trim().trim_matches('"');
-    if trimmed.is_empty() {
-        return None;
-    }
-    let parts: Vec<&str> = trimmed.split(',').collect();
-    let primary = parts.first().copied()?.trim();
+    let trimmed = input.trim();
+    let (exe, _) = parse_command_line(trimmed);
+    let primary = if trimmed.starts_with('"') || is_executable_candidate(&exe) {
+        exe
+    } else {
+        trimmed
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
     if primary.is_empty() {
         None
     } else {
-        Some(expand_env_vars(primary))
+        Some(expand_env_vars(&primary))
     }
 }
 
@@ -473,3 +742,61 @@ fn is_executable_candidate(path: &str) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use windows::core::HSTRING;
+
+    use super::*;
+
+    fn create_shortcut(path: &Path, target: &str) {
+        unsafe {
+            let shell_link: IShellLinkW =
+                CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).unwrap();
+            shell_link.SetPath(&HSTRING::from(target)).unwrap();
+            let persist: IPersistFile = shell_link.cast().unwrap();
+            persist
+                .Save(&HSTRING::from(path.as_os_str()), true)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn ingest_start_menu_enters_junction_loops_once() {
+        com::ensure_initialized(Apartment::Sta).unwrap();
+        let root = std::env::temp_dir().join(format!("wolong-start-menu-{}", std::process::id()));
+        let nested = root.join("Tools");
+        fs::create_dir_all(&nested).unwrap();
+        create_shortcut(
+            &root.join("Notepad.lnk"),
+            r"C:\Windows\System32\notepad.exe",
+        );
+        create_shortcut(
+            &nested.join("Calculator.lnk"),
+            r"C:\Windows\System32\calc.exe",
+        );
+
+        // `Tools\Loop` points back at the root, like a misconfigured Start Menu.
+        let status = Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(nested.join("Loop"))
+            .arg(&root)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "mklink /J failed");
+
+        let mut map = HashMap::new();
+        let result = ingest_start_menu(&mut map, &root, "test");
+        fs::remove_dir_all(&root).ok();
+        result.unwrap();
+
+        let mut names: Vec<_> = map.values().map(|record| record.name.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["Calculator", "Notepad"]);
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::c_void,
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+};
+
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{
+            CloseHandle, ERROR_SUCCESS, HANDLE, HWND, LPARAM, WAIT_OBJECT_0, WAIT_TIMEOUT, WPARAM,
+        },
+        Storage::FileSystem::{
+            CreateFileW, ReadDirectoryChangesW, FILE_ACTION_MODIFIED, FILE_FLAG_BACKUP_SEMANTICS,
+            FILE_FLAG_OVERLAPPED, FILE_LIST_DIRECTORY, FILE_NOTIFY_CHANGE_DIR_NAME,
+            FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_INFORMATION,
+            FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+        },
+        System::{
+            Registry::{
+                RegNotifyChangeKeyValue, HKEY, REG_NOTIFY_CHANGE_LAST_SET, REG_NOTIFY_CHANGE_NAME,
+            },
+            Threading::{CreateEventW, GetCurrentThreadId, ResetEvent, INFINITE},
+            IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED},
+        },
+        UI::WindowsAndMessaging::{
+            MsgWaitForMultipleObjects, PeekMessageW, PostThreadMessageW, MSG, PM_REMOVE,
+            QS_ALLINPUT, WM_QUIT,
+        },
+    },
+};
+use winreg::RegKey;
+
+use crate::{
+    app_index::{
+        ingest_registry_path, ingest_start_menu, is_shortcut_path, open_registry_path, shortcut_id,
+        shortcut_record, AppRecord,
+    },
+    com::{self, Apartment},
+    error::{CoreError, CoreResult},
+    utils::{normalize_path, refresh_env_cache, wide_string},
+    AppChangeEvent, AppChangeKind,
+};
+
+/// Installers write shortcuts and Uninstall values in bursts, so changes are
+/// re-resolved only once a source has been quiet for this long.
+const SETTLE_DELAY_MS: u32 = 300;
+
+const NOTIFY_BUFFER_BYTES: usize = 64 * 1024;
+
+static APP_WATCHER: Lazy<Mutex<Option<AppWatcher>>> = Lazy::new(|| Mutex::new(None));
+
+struct AppWatcher {
+    thread_id: u32,
+    handle: thread::JoinHandle<()>,
+}
+
+impl AppWatcher {
+    fn stop(self) {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        self.handle.join().ok();
+    }
+}
+
+/// Watches Start Menu folders and Uninstall keys, emitting an event for every
+/// app added, changed or removed after an initial (silent) scan. Calling it
+/// again replaces the previous subscription.
+pub fn subscribe_app_changes(
+    start_menu_paths: Vec<String>,
+    registry_paths: Vec<String>,
+    callback: ThreadsafeFunction<AppChangeEvent>,
+) -> CoreResult<()> {
+    let mut guard = APP_WATCHER.lock();
+    if let Some(watcher) = guard.take() {
+        watcher.stop();
+    }
+
+    let (ready, started) = mpsc::channel();
+    let handle = thread::Builder::new()
+        .name("wolong-app-watcher".to_string())
+        .spawn(move || run_app_watcher(start_menu_paths, registry_paths, callback, ready))
+        .map_err(|err| CoreError::Other(anyhow::anyhow!("spawn app watcher failed: {err}")))?;
+    let thread_id = started
+        .recv()
+        .map_err(|_| CoreError::Other(anyhow::anyhow!("app watcher exited during startup")))??;
+
+    *guard = Some(AppWatcher { thread_id, handle });
+    Ok(())
+}
+
+pub fn unsubscribe_app_changes() {
+    if let Some(watcher) = APP_WATCHER.lock().take() {
+        watcher.stop();
+    }
+}
+
+fn run_app_watcher(
+    start_menu_paths: Vec<String>,
+    registry_paths: Vec<String>,
+    callback: ThreadsafeFunction<AppChangeEvent>,
+    ready: mpsc::Sender<CoreResult<u32>>,
+) {
+    // Resolving shortcuts needs COM.
+    if let Err(err) = com::ensure_initialized(Apartment::Sta) {
+        let _ = ready.send(Err(err));
+        return;
+    }
+    // The baseline scan can take seconds, so the caller isn't kept waiting for it.
+    let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+
+    refresh_env_cache();
+    let mut sources = Vec::new();
+    for path in &start_menu_paths {
+        match Source::start_menu(path) {
+            Ok(source) => sources.push(source),
+            Err(err) => log::warn!("not watching start menu path {path:?}: {err}"),
+        }
+    }
+    for path in &registry_paths {
+        match Source::registry(path) {
+            Ok(source) => sources.push(source),
+            Err(err) => log::warn!("not watching registry path {path:?}: {err}"),
+        }
+    }
+
+    let mut pending = false;
+    'watch: loop {
+        let events: Vec<HANDLE> = sources.iter().map(|source| source.event.0).collect();
+        let timeout = if pending { SETTLE_DELAY_MS } else { INFINITE };
+        let result =
+            unsafe { MsgWaitForMultipleObjects(Some(&events), false, timeout, QS_ALLINPUT) };
+
+        if result == WAIT_TIMEOUT {
+            pending = false;
+            for source in &mut sources {
+                source.flush(&callback);
+            }
+            continue;
+        }
+
+        let signaled = result.0.wrapping_sub(WAIT_OBJECT_0.0) as usize;
+        if signaled < events.len() {
+            pending = true;
+            if let Err(err) = sources[signaled].collect() {
+                // The folder or key is most likely gone; report its apps as removed.
+                log::warn!("stopped watching {}: {err}", sources[signaled].name());
+                let mut source = sources.remove(signaled);
+                source.rescan = true;
+                source.flush(&callback);
+            }
+        } else if signaled == events.len() {
+            let mut msg = MSG::default();
+            while unsafe { PeekMessageW(&mut msg, HWND(0), 0, 0, PM_REMOVE) }.as_bool() {
+                if msg.message == WM_QUIT {
+                    break 'watch;
+                }
+            }
+        } else {
+            log::warn!(
+                "{}",
+                CoreError::from_win32("MsgWaitForMultipleObjects failed")
+            );
+            break;
+        }
+    }
+}
+
+/// One watched Start Menu folder or Uninstall key and the apps last seen in it.
+struct Source {
+    kind: SourceKind,
+    records: HashMap<String, AppRecord>,
+    /// Shortcuts touched since the last flush.
+    changed: HashSet<PathBuf>,
+    /// Set when changes can't be narrowed down to individual shortcuts.
+    rescan: bool,
+    /// Declared last so it outlives the pending notification it is tied to.
+    event: NotifyEvent,
+}
+
+enum SourceKind {
+    StartMenu(DirectoryWatch),
+    Registry { path: String, key: RegKey },
+}
+
+impl Source {
+    fn start_menu(root: &str) -> CoreResult<Self> {
+        let event = NotifyEvent::new()?;
+        let mut watch = DirectoryWatch::open(root, event.0)?;
+        watch.arm(event.0)?;
+
+        let mut records = HashMap::new();
+        ingest_start_menu(&mut records, &watch.root, &watch.source_path)?;
+        Ok(Self {
+            kind: SourceKind::StartMenu(watch),
+            records,
+            changed: HashSet::new(),
+            rescan: false,
+            event,
+        })
+    }
+
+    fn registry(path: &str) -> CoreResult<Self> {
+        let event = NotifyEvent::new()?;
+        let (_, key) = open_registry_path(path)?;
+        arm_registry(&key, event.0)?;
+
+        let mut records = HashMap::new();
+        ingest_registry_path(&mut records, path)?;
+        Ok(Self {
+            kind: SourceKind::Registry {
+                path: path.to_string(),
+                key,
+            },
+            records,
+            changed: HashSet::new(),
+            rescan: false,
+            event,
+        })
+    }
+
+    fn name(&self) -> &str {
+        match &self.kind {
+            SourceKind::StartMenu(watch) => &watch.source_path,
+            SourceKind::Registry { path, .. } => path,
+        }
+    }
+
+    /// Records what a signaled notification touched and re-arms it.
+    fn collect(&mut self) -> CoreResult<()> {
+        match &mut self.kind {
+            SourceKind::StartMenu(watch) => {
+                if watch.read_changes(&mut self.changed)? {
+                    self.rescan = true;
+                }
+                watch.arm(self.event.0)
+            }
+            // Registry notifications don't say which subkey changed; the
+            // Uninstall keys are cheap enough to enumerate again.
+            SourceKind::Registry { key, .. } => {
+                self.rescan = true;
+                arm_registry(key, self.event.0)
+            }
+        }
+    }
+
+    /// Re-resolves everything collected since the last flush and emits the
+    /// differences.
+    fn flush(&mut self, callback: &ThreadsafeFunction<AppChangeEvent>) {
+        let mut events = Vec::new();
+        if self.rescan {
+            let mut current = HashMap::new();
+            let scanned = match &self.kind {
+                SourceKind::StartMenu(watch) => {
+                    ingest_start_menu(&mut current, &watch.root, &watch.source_path)
+                }
+                SourceKind::Registry { path, .. } => ingest_registry_path(&mut current, path),
+            };
+            if let Err(err) = scanned {
+                log::debug!("rescan of {} failed: {err}", self.name());
+            }
+
+            let stale: Vec<String> = self
+                .records
+                .keys()
+                .filter(|id| !current.contains_key(*id))
+                .cloned()
+                .collect();
+            for id in stale {
+                apply_change(&mut self.records, id, None, &mut events);
+            }
+            for (id, record) in current {
+                apply_change(&mut self.records, id, Some(record), &mut events);
+            }
+        } else if let SourceKind::StartMenu(watch) = &self.kind {
+            for path in self.changed.iter() {
+                let record = if path.is_file() {
+                    shortcut_record(path, &watch.source_path)
+                } else {
+                    None
+                };
+                apply_change(&mut self.records, shortcut_id(path), record, &mut events);
+            }
+        }
+        self.rescan = false;
+        self.changed.clear();
+
+        for event in events {
+            let _ = callback.call(Ok(event), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+}
+
+/// Updates `records` with the latest resolution of `id` and queues the event
+/// describing the difference, if any.
+fn apply_change(
+    records: &mut HashMap<String, AppRecord>,
+    id: String,
+    record: Option<AppRecord>,
+    events: &mut Vec<AppChangeEvent>,
+) {
+    let Some(record) = record else {
+        if records.remove(&id).is_some() {
+            events.push(AppChangeEvent {
+                kind: AppChangeKind::Removed,
+                id,
+                app: None,
+            });
+        }
+        return;
+    };
+
+    let kind = match records.get(&id) {
+        None => AppChangeKind::Added,
+        Some(previous) if same_entry(previous, &record) => return,
+        Some(_) => AppChangeKind::Changed,
+    };
+    events.push(AppChangeEvent {
+        kind,
+        id: id.clone(),
+        app: Some(record.clone().into()),
+    });
+    records.insert(id, record);
+}
+
+/// Compares what callers see; registry entries get a fresh `last_modified` on
+/// every scan.
+fn same_entry(a: &AppRecord, b: &AppRecord) -> bool {
+    a.name == b.name
+        && a.launch_path == b.launch_path
+        && a.working_directory == b.working_directory
+        && a.icon_path == b.icon_path
+        && a.source == b.source
+}
+
+fn arm_registry(key: &RegKey, event: HANDLE) -> CoreResult<()> {
+    let status = unsafe {
+        let _ = ResetEvent(event);
+        RegNotifyChangeKeyValue(
+            HKEY(key.raw_handle()),
+            true,
+            REG_NOTIFY_CHANGE_NAME | REG_NOTIFY_CHANGE_LAST_SET,
+            event,
+            true,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return Err(CoreError::from_hresult(
+            "RegNotifyChangeKeyValue failed",
+            status.to_hresult(),
+        ));
+    }
+    Ok(())
+}
+
+/// An overlapped `ReadDirectoryChangesW` on a Start Menu folder tree.
+struct DirectoryWatch {
+    root: PathBuf,
+    source_path: String,
+    directory: HANDLE,
+    /// Boxed so the kernel's pointer stays valid while the struct moves.
+    overlapped: Box<OVERLAPPED>,
+    /// `u32`s for the DWORD alignment the notification records need.
+    buffer: Vec<u32>,
+}
+
+impl DirectoryWatch {
+    fn open(root: &str, event: HANDLE) -> CoreResult<Self> {
+        let wide = wide_string(root);
+        let directory = unsafe {
+            CreateFileW(
+                PCWSTR(wide.as_ptr()),
+                FILE_LIST_DIRECTORY.0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED,
+                None,
+            )
+        }
+        .map_err(|err| CoreError::from_hresult("CreateFileW failed", err.code()))?;
+
+        let root = PathBuf::from(root);
+        Ok(Self {
+            source_path: normalize_path(&root),
+            root,
+            directory,
+            overlapped: Box::new(OVERLAPPED {
+                hEvent: event,
+                ..Default::default()
+            }),
+            buffer: vec![0; NOTIFY_BUFFER_BYTES / 4],
+        })
+    }
+
+    fn arm(&mut self, event: HANDLE) -> CoreResult<()> {
+        unsafe {
+            let _ = ResetEvent(event);
+            ReadDirectoryChangesW(
+                self.directory,
+                self.buffer.as_mut_ptr() as *mut c_void,
+                (self.buffer.len() * 4) as u32,
+                true,
+                FILE_NOTIFY_CHANGE_FILE_NAME
+                    | FILE_NOTIFY_CHANGE_DIR_NAME
+                    | FILE_NOTIFY_CHANGE_LAST_WRITE,
+                None,
+                Some(&mut *self.overlapped),
+                None,
+            )
+        }
+        .map_err(|err| CoreError::from_hresult("ReadDirectoryChangesW failed", err.code()))
+    }
+
+    /// Adds changed shortcuts to `changed`; returns true when the whole tree
+    /// needs rescanning instead.
+    fn read_changes(&mut self, changed: &mut HashSet<PathBuf>) -> CoreResult<bool> {
+        let mut bytes = 0u32;
+        unsafe { GetOverlappedResult(self.directory, &*self.overlapped, &mut bytes, false) }
+            .map_err(|err| CoreError::from_hresult("GetOverlappedResult failed", err.code()))?;
+        // Zero bytes means the buffer overflowed and the details were dropped.
+        if bytes == 0 {
+            return Ok(true);
+        }
+
+        let mut rescan = false;
+        let mut offset = 0usize;
+        let base = self.buffer.as_ptr() as *const u8;
+        while offset < bytes as usize {
+            let (action, path, next) = unsafe {
+                let info = &*(base.add(offset) as *const FILE_NOTIFY_INFORMATION);
+                let name = std::slice::from_raw_parts(
+                    info.FileName.as_ptr(),
+                    info.FileNameLength as usize / 2,
+                );
+                (
+                    info.Action,
+                    self.root.join(String::from_utf16_lossy(name)),
+                    info.NextEntryOffset,
+                )
+            };
+            if is_shortcut_path(&path) {
+                changed.insert(path);
+            } else if action != FILE_ACTION_MODIFIED {
+                // A folder was added, removed or renamed, taking any number of
+                // shortcuts with it. Folder timestamps change with every file
+                // inside and are already covered by the file's own record.
+                rescan = true;
+            }
+            if next == 0 {
+                break;
+            }
+            offset += next as usize;
+        }
+        Ok(rescan)
+    }
+}
+
+impl Drop for DirectoryWatch {
+    fn drop(&mut self) {
+        // The kernel writes into `buffer` until the read is cancelled and has
+        // completed, so wait for that before freeing it.
+        unsafe {
+            if CancelIoEx(self.directory, Some(&*self.overlapped)).is_ok() {
+                let mut bytes = 0u32;
+                let _ = GetOverlappedResult(self.directory, &*self.overlapped, &mut bytes, true);
+            }
+            let _ = CloseHandle(self.directory);
+        }
+    }
+}
+
+/// Manual-reset event signaled by a directory or registry notification.
+struct NotifyEvent(HANDLE);
+
+impl NotifyEvent {
+    fn new() -> CoreResult<Self> {
+        unsafe { CreateEventW(None, true, false, PCWSTR::null()) }
+            .map(Self)
+            .map_err(|err| CoreError::from_hresult("CreateEventW failed", err.code()))
+    }
+}
+
+impl Drop for NotifyEvent {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
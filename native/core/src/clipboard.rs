@@ -1,84 +1,574 @@
 use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, VecDeque},
     ffi::c_void,
-    slice,
+    hash::{Hash, Hasher},
+    mem::size_of,
+    path::Path,
+    ptr, slice,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
     thread,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use image::{imageops::FilterType, RgbaImage};
 use napi::{
     bindgen_prelude::Buffer,
     threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
 };
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
-use windows::core::{PCSTR, PCWSTR};
+use windows::core::{w, PCSTR, PCWSTR};
 use windows::Win32::{
-    Foundation::{HGLOBAL, HWND},
-    Graphics::Gdi::{BITMAPINFOHEADER, BI_RGB},
+    Foundation::{GlobalFree, HANDLE, HGLOBAL, HWND, LPARAM, LRESULT, WPARAM},
+    Globalization::{MultiByteToWideChar, CP_ACP, CP_OEMCP, MULTI_BYTE_TO_WIDE_CHAR_FLAGS},
+    Graphics::Gdi::{BITMAPINFOHEADER, BITMAPV5HEADER, BI_BITFIELDS, BI_RGB},
     System::{
         DataExchange::{
-            CloseClipboard, GetClipboardData, GetClipboardSequenceNumber, OpenClipboard,
-            RegisterClipboardFormatA,
+            AddClipboardFormatListener, CloseClipboard, EmptyClipboard, GetClipboardData,
+            GetClipboardOwner, GetClipboardSequenceNumber, IsClipboardFormatAvailable,
+            OpenClipboard, RegisterClipboardFormatA, RemoveClipboardFormatListener,
+            SetClipboardData,
         },
-        Memory::{GlobalLock, GlobalSize, GlobalUnlock},
+        LibraryLoader::GetModuleHandleW,
+        Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+        Threading::GetCurrentThreadId,
+    },
+    UI::Shell::{DragQueryFileW, DROPFILES, HDROP},
+    UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+        PostThreadMessageW, RegisterClassW, TranslateMessage, HWND_MESSAGE, MSG, WINDOW_EX_STYLE,
+        WINDOW_STYLE, WM_CLIPBOARDUPDATE, WM_QUIT, WNDCLASSW,
     },
 };
 
+const CF_TEXT: u32 = 1;
+const CF_OEMTEXT: u32 = 7;
 const CF_UNICODETEXT: u32 = 13;
 const CF_DIB: u32 = 8;
+const CF_HDROP: u32 = 15;
+const CF_DIBV5: u32 = 17;
+const LCS_SRGB: u32 = 0x7352_4742; // 'sRGB'
+const HTML_FORMAT_NAME: &[u8] = b"HTML Format\0";
+const RTF_FORMAT_NAME: &[u8] = b"Rich Text Format\0";
+const EXCLUDE_MONITOR_FORMAT_NAME: &[u8] = b"ExcludeClipboardContentFromMonitorProcessing\0";
+const CAN_INCLUDE_HISTORY_FORMAT_NAME: &[u8] = b"CanIncludeInClipboardHistory\0";
+const VIEWER_IGNORE_FORMAT_NAME: &[u8] = b"Clipboard Viewer Ignore\0";
 
 use crate::{
-    error::{CoreError, CoreResult},
-    ClipboardItem,
+    error::{catch_callback_panic, CoreError, CoreResult},
+    input::{simulate_copy, simulate_paste},
+    screenshot::encode_rgba,
+    utils::{process_image_path, string_from_wide, window_process_id, window_title},
+    ClipboardItem, ClipboardStats, ClipboardWatcherOptions, ImageFormat,
 };
 
+#[derive(Clone, Default)]
 pub struct ClipboardSnapshot {
     pub sequence: u32,
     pub timestamp: i64,
+    /// `formats` joined with commas, or `unknown`/`excluded` when empty.
     pub format: String,
+    /// Payloads present (or skipped for size), in `text`, `html`, `rtf`,
+    /// `files`, `image` order.
+    pub formats: Vec<String>,
     pub text: Option<String>,
+    /// Text exactly as it was on the clipboard; only set when
+    /// `normalize_newlines` changed it.
+    pub raw_text: Option<String>,
     pub html: Option<String>,
+    pub rtf: Option<String>,
+    pub files: Option<Vec<String>>,
+    /// Encoded as `image_mime_type`.
     pub image: Option<Vec<u8>>,
+    pub image_mime_type: Option<String>,
+    /// Dimensions before downscaling; only set when the image was scaled.
+    pub image_original_size: Option<(u32, u32)>,
+    pub source_app: Option<String>,
+    pub source_title: Option<String>,
+    /// Page the HTML was copied from, taken from the CF_HTML `SourceURL` header.
+    pub source_url: Option<String>,
+    /// Set when at least one format was skipped for exceeding `max_item_bytes`.
+    pub truncated: bool,
+    /// Set when the owner asked clipboard monitors to ignore this content.
+    pub excluded: bool,
+}
+
+impl ClipboardSnapshot {
+    fn has_content(&self) -> bool {
+        self.text.is_some()
+            || self.html.is_some()
+            || self.rtf.is_some()
+            || self.files.is_some()
+            || self.image.is_some()
+            || self.truncated
+    }
+
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.text.hash(&mut hasher);
+        self.html.hash(&mut hasher);
+        self.rtf.hash(&mut hasher);
+        self.files.hash(&mut hasher);
+        self.image.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Case-insensitive match against text, HTML and dropped file names.
+    /// `needle` must already be lowercase.
+    fn matches_query(&self, needle: &str) -> bool {
+        let contains = |value: &Option<String>| {
+            value
+                .as_deref()
+                .is_some_and(|value| value.to_lowercase().contains(needle))
+        };
+        if contains(&self.text) || contains(&self.html) {
+            return true;
+        }
+
+        self.files.iter().flatten().any(|file| {
+            Path::new(file)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().to_lowercase().contains(needle))
+        })
+    }
+}
+
+/// Tunables applied when the watcher (or an on-demand read) captures the clipboard.
+#[derive(Clone)]
+pub struct WatcherConfig {
+    pub max_image_dimension: Option<u32>,
+    pub max_item_bytes: Option<usize>,
+    /// How many recently emitted snapshots to compare against; 0 disables dedup.
+    pub dedup_window: usize,
+    /// Maximum number of snapshots kept in the in-process history; 0 disables it.
+    pub history_capacity: usize,
+    /// How hard to try when another process holds the clipboard open.
+    pub open_retry: OpenRetryPolicy,
+    /// Encoding for captured images; PNG keeps them lossless.
+    pub image_format: ImageFormat,
+    /// JPEG quality, 1-100.
+    pub image_quality: u8,
+    /// Convert `\r\n` and lone `\r` in text to `\n`.
+    pub normalize_newlines: bool,
+}
+
+const DEFAULT_IMAGE_QUALITY: u8 = 85;
+
+const DEFAULT_HISTORY_CAPACITY: usize = 50;
+
+/// Exponential backoff for `OpenClipboard`, which fails while any other process
+/// has the clipboard open.
+#[derive(Clone, Copy)]
+pub struct OpenRetryPolicy {
+    /// Retries after the first attempt; 0 gives up immediately.
+    pub retries: u32,
+    /// Delay before the first retry, doubled after each one.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for OpenRetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            initial_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            max_image_dimension: None,
+            max_item_bytes: None,
+            dedup_window: 1,
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            open_retry: OpenRetryPolicy::default(),
+            image_format: ImageFormat::Png,
+            image_quality: DEFAULT_IMAGE_QUALITY,
+            normalize_newlines: false,
+        }
+    }
 }
 
 static CLIPBOARD_MANAGER: Lazy<ClipboardManager> = Lazy::new(ClipboardManager::new);
 
-pub fn start_clipboard_watcher(callback: ThreadsafeFunction<ClipboardItem>) -> CoreResult<()> {
-    CLIPBOARD_MANAGER.start(callback)
+pub fn start_clipboard_watcher(
+    callback: ThreadsafeFunction<ClipboardItem>,
+    config: WatcherConfig,
+) -> CoreResult<()> {
+    CLIPBOARD_MANAGER.start(callback, config)
 }
 
 pub fn stop_clipboard_watcher() {
     CLIPBOARD_MANAGER.stop();
 }
 
+/// Stops emitting changes without tearing down the watcher thread.
+pub fn pause_clipboard_watcher() {
+    CLIPBOARD_MANAGER.paused.store(true, Ordering::Relaxed);
+}
+
+/// Resumes emitting changes; anything copied while paused is not replayed.
+pub fn resume_clipboard_watcher() {
+    CLIPBOARD_MANAGER.paused.store(false, Ordering::Relaxed);
+}
+
+pub fn clipboard_stats() -> ClipboardStats {
+    CLIPBOARD_MANAGER.stats.snapshot()
+}
+
+pub fn reset_clipboard_stats() {
+    CLIPBOARD_MANAGER.stats.reset();
+}
+
+/// Returns up to `limit` captured snapshots, newest first.
+pub fn clipboard_history(limit: usize) -> Vec<ClipboardSnapshot> {
+    CLIPBOARD_MANAGER
+        .history
+        .lock()
+        .iter()
+        .rev()
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+/// Returns up to `limit` history entries matching `query`, newest first.
+pub fn search_clipboard_history(query: &str, limit: usize) -> Vec<ClipboardSnapshot> {
+    let needle = query.trim().to_lowercase();
+    CLIPBOARD_MANAGER
+        .history
+        .lock()
+        .iter()
+        .rev()
+        .filter(|snapshot| snapshot.matches_query(&needle))
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+pub fn read_current_clipboard() -> CoreResult<Option<ClipboardSnapshot>> {
+    let sequence = unsafe { GetClipboardSequenceNumber() };
+    let config = CLIPBOARD_MANAGER.config.lock().clone();
+    let snapshot = capture_clipboard_snapshot(sequence, &config)?;
+    Ok(snapshot.has_content().then_some(snapshot))
+}
+
+pub fn clear_clipboard() -> CoreResult<()> {
+    let _guard = open_clipboard()?;
+    unsafe {
+        EmptyClipboard().map_err(|_| CoreError::from_win32("EmptyClipboard failed"))?;
+    }
+
+    // Closing the clipboard posts WM_CLIPBOARDUPDATE, so mark before the guard drops.
+    CLIPBOARD_MANAGER.mark_own_write();
+    Ok(())
+}
+
+/// Payloads to place on the clipboard in a single open/empty session.
+#[derive(Default)]
+pub struct ClipboardContent {
+    pub text: Option<String>,
+    pub html: Option<String>,
+    pub rtf: Option<String>,
+    pub files: Option<Vec<String>>,
+    /// PNG-encoded image.
+    pub image: Option<Vec<u8>>,
+}
+
+/// Time to let the target app read the clipboard before restoring the original.
+const PASTE_RESTORE_DELAY: Duration = Duration::from_millis(200);
+
+/// Pastes the current clipboard text without formatting.
+///
+/// The clipboard is replaced with just its `CF_UNICODETEXT`, Ctrl+V is sent, and
+/// the full original contents are put back when `restore` is set. Clipboards
+/// without text are left untouched.
+pub fn paste_plain_text(restore: bool) -> CoreResult<()> {
+    let sequence = unsafe { GetClipboardSequenceNumber() };
+    let previous = capture_clipboard_snapshot(sequence, &restore_config())?;
+    let Some(text) = previous.text.clone() else {
+        return Ok(());
+    };
+
+    write_clipboard_text(&text)?;
+    let pasted = simulate_paste();
+
+    if restore {
+        thread::sleep(PASTE_RESTORE_DELAY);
+        restore_clipboard(previous)?;
+    }
+    pasted
+}
+
+/// How long to wait for the foreground app to answer a simulated Ctrl+C.
+const COPY_TIMEOUT: Duration = Duration::from_millis(500);
+const COPY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Copies the foreground selection and returns its text.
+///
+/// Ctrl+C is sent and the clipboard sequence number is watched until the target
+/// app writes to it. `None` means nothing was copied in time or the copy held no
+/// text. With `restore`, the previous clipboard contents are put back afterwards.
+pub fn copy_selection(restore: bool) -> CoreResult<Option<String>> {
+    let before = unsafe { GetClipboardSequenceNumber() };
+    if !restore {
+        return copy_selection_text(before);
+    }
+
+    let previous = capture_clipboard_snapshot(before, &restore_config())?;
+    // The copy is transient, so keep it away from subscribers, history and stats.
+    let _suppressed = CLIPBOARD_MANAGER.suppress();
+    let text = copy_selection_text(before);
+    let restored = restore_clipboard(previous);
+    let text = text?;
+    restored?;
+    Ok(text)
+}
+
+fn copy_selection_text(before: u32) -> CoreResult<Option<String>> {
+    simulate_copy()?;
+
+    let deadline = Instant::now() + COPY_TIMEOUT;
+    while unsafe { GetClipboardSequenceNumber() } == before {
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(COPY_POLL_INTERVAL);
+    }
+
+    // Opening waits out the source app, which holds the clipboard while writing.
+    let _guard = open_clipboard()?;
+    read_clipboard_text()
+}
+
+/// Watcher config for snapshots that are only taken to be put back later: the
+/// user's retry policy applies, but nothing is dropped, downscaled or re-encoded
+/// lossily.
+fn restore_config() -> WatcherConfig {
+    WatcherConfig {
+        max_image_dimension: None,
+        max_item_bytes: None,
+        image_format: ImageFormat::Png,
+        ..CLIPBOARD_MANAGER.config.lock().clone()
+    }
+}
+
+/// Puts a snapshot taken with `restore_config` back on the clipboard.
+///
+/// Excluded snapshots hold nothing to restore, and an empty one may stand for
+/// formats we can't read, so both leave the clipboard as it is.
+fn restore_clipboard(previous: ClipboardSnapshot) -> CoreResult<()> {
+    if previous.excluded || !previous.has_content() {
+        return Ok(());
+    }
+    write_clipboard_content(&previous.into())
+}
+
+pub fn write_clipboard_text(text: &str) -> CoreResult<()> {
+    write_clipboard_content(&ClipboardContent {
+        text: Some(text.to_string()),
+        ..Default::default()
+    })
+}
+
+pub fn write_clipboard_image(png: &[u8]) -> CoreResult<()> {
+    write_clipboard_content(&ClipboardContent {
+        image: Some(png.to_vec()),
+        ..Default::default()
+    })
+}
+
+/// Puts top-down RGBA pixels on the clipboard as `CF_DIBV5`/`CF_DIB`, skipping
+/// the PNG round-trip of `write_clipboard_image`.
+pub fn write_clipboard_rgba(rgba: &[u8], width: u32, height: u32) -> CoreResult<()> {
+    if rgba.len() != (width as usize) * (height as usize) * 4 {
+        return Err(CoreError::InvalidArgument(
+            "clipboard image buffer size mismatch".to_string(),
+        ));
+    }
+    let entries = image_entries(rgba, width, height);
+    set_clipboard_entries(&entries)
+}
+
+/// Replaces the clipboard with every format present in `content`.
+///
+/// All buffers are encoded before the clipboard is opened so a bad or empty
+/// payload never leaves the clipboard emptied.
+pub fn write_clipboard_content(content: &ClipboardContent) -> CoreResult<()> {
+    let mut entries: Vec<(u32, Vec<u8>)> = Vec::new();
+
+    if let Some(text) = content.text.as_deref() {
+        entries.push((CF_UNICODETEXT, utf16_bytes(text)));
+    }
+    if let Some(html) = content.html.as_deref() {
+        entries.push((registered_format(HTML_FORMAT_NAME), build_cf_html(html)));
+    }
+    if let Some(rtf) = content.rtf.as_deref() {
+        let mut bytes = rtf.as_bytes().to_vec();
+        bytes.push(0);
+        entries.push((registered_format(RTF_FORMAT_NAME), bytes));
+    }
+    if let Some(files) = content.files.as_deref().filter(|files| !files.is_empty()) {
+        entries.push((CF_HDROP, build_drop_files(files)));
+    }
+    if let Some(png) = content.image.as_deref() {
+        // Restored history items may hold JPEG or WebP, depending on the watcher config.
+        let decoded = image::load_from_memory(png)
+            .map_err(|err| {
+                CoreError::InvalidArgument(format!("decode clipboard image failed: {err}"))
+            })?
+            .to_rgba8();
+        let (width, height) = decoded.dimensions();
+        entries.extend(image_entries(decoded.as_raw(), width, height));
+    }
+
+    if entries.is_empty() {
+        return Err(CoreError::InvalidArgument(
+            "clipboard content has no formats to write".to_string(),
+        ));
+    }
+    set_clipboard_entries(&entries)
+}
+
+fn image_entries(rgba: &[u8], width: u32, height: u32) -> [(u32, Vec<u8>); 2] {
+    let pixels = rgba_to_bottom_up_bgra(rgba, width, height);
+
+    // CF_DIBV5 keeps the alpha channel; the plain CF_DIB copy is for readers that
+    // only understand BITMAPINFOHEADER.
+    [
+        (CF_DIBV5, build_dibv5(&pixels, width, height)),
+        (CF_DIB, build_dib(&pixels, width, height)),
+    ]
+}
+
+fn set_clipboard_entries(entries: &[(u32, Vec<u8>)]) -> CoreResult<()> {
+    let _guard = open_clipboard()?;
+    unsafe {
+        EmptyClipboard().map_err(|_| CoreError::from_win32("EmptyClipboard failed"))?;
+        for (format, bytes) in entries {
+            set_clipboard_bytes(*format, bytes)?;
+        }
+    }
+
+    // Closing the clipboard posts WM_CLIPBOARDUPDATE, so mark before the guard drops.
+    CLIPBOARD_MANAGER.mark_own_write();
+    Ok(())
+}
+
 struct ClipboardManager {
     callback: Arc<Mutex<Option<ThreadsafeFunction<ClipboardItem>>>>,
     worker: Mutex<Option<ClipboardWorker>>,
+    last_sequence: Arc<AtomicU32>,
+    paused: Arc<AtomicBool>,
+    /// Active `suppress` guards; changes made while any is held are not emitted.
+    suppressed: Arc<AtomicU32>,
+    config: Arc<Mutex<WatcherConfig>>,
+    history: Arc<Mutex<VecDeque<ClipboardSnapshot>>>,
+    stats: Arc<ClipboardCounters>,
 }
 
 struct ClipboardWorker {
     shutdown: Arc<AtomicBool>,
+    thread_id: Arc<AtomicU32>,
     handle: thread::JoinHandle<()>,
 }
 
+/// State shared between the manager and the watcher thread.
+#[derive(Clone)]
+struct WatcherContext {
+    callback: Arc<Mutex<Option<ThreadsafeFunction<ClipboardItem>>>>,
+    last_sequence: Arc<AtomicU32>,
+    paused: Arc<AtomicBool>,
+    suppressed: Arc<AtomicU32>,
+    config: Arc<Mutex<WatcherConfig>>,
+    recent_hashes: Arc<Mutex<VecDeque<u64>>>,
+    history: Arc<Mutex<VecDeque<ClipboardSnapshot>>>,
+    stats: Arc<ClipboardCounters>,
+}
+
+/// Per-format counts of snapshots emitted by the watcher.
+#[derive(Default)]
+struct ClipboardCounters {
+    text: AtomicU32,
+    html: AtomicU32,
+    image: AtomicU32,
+    files: AtomicU32,
+    total: AtomicU32,
+    /// Times `OpenClipboard` was given up on after exhausting the retries.
+    open_failures: AtomicU32,
+}
+
+impl ClipboardCounters {
+    fn record(&self, snapshot: &ClipboardSnapshot) {
+        for (counter, present) in [
+            (&self.text, snapshot.text.is_some()),
+            (&self.html, snapshot.html.is_some()),
+            (&self.image, snapshot.image.is_some()),
+            (&self.files, snapshot.files.is_some()),
+            (&self.total, true),
+        ] {
+            if present {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> ClipboardStats {
+        ClipboardStats {
+            text: self.text.load(Ordering::Relaxed),
+            html: self.html.load(Ordering::Relaxed),
+            image: self.image.load(Ordering::Relaxed),
+            files: self.files.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            open_failures: self.open_failures.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.text,
+            &self.html,
+            &self.image,
+            &self.files,
+            &self.total,
+            &self.open_failures,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
 impl ClipboardManager {
     fn new() -> Self {
         Self {
             callback: Arc::new(Mutex::new(None)),
             worker: Mutex::new(None),
+            last_sequence: Arc::new(AtomicU32::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
+            suppressed: Arc::new(AtomicU32::new(0)),
+            config: Arc::new(Mutex::new(WatcherConfig::default())),
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            stats: Arc::new(ClipboardCounters::default()),
         }
     }
 
-    fn start(&self, callback: ThreadsafeFunction<ClipboardItem>) -> CoreResult<()> {
+    fn start(
+        &self,
+        callback: ThreadsafeFunction<ClipboardItem>,
+        config: WatcherConfig,
+    ) -> CoreResult<()> {
         {
             let mut guard = self.callback.lock();
             *guard = Some(callback);
         }
+        *self.config.lock() = config;
 
         let mut worker_guard = self.worker.lock();
         if worker_guard.is_some() {
@@ -86,100 +576,475 @@ impl ClipboardManager {
         }
 
         let shutdown = Arc::new(AtomicBool::new(false));
-        let cb_holder = Arc::clone(&self.callback);
+        let thread_id = Arc::new(AtomicU32::new(0));
         let shutdown_flag = Arc::clone(&shutdown);
+        let worker_thread_id = Arc::clone(&thread_id);
+        self.last_sequence.store(0, Ordering::Relaxed);
+        let context = WatcherContext {
+            callback: Arc::clone(&self.callback),
+            last_sequence: Arc::clone(&self.last_sequence),
+            paused: Arc::clone(&self.paused),
+            suppressed: Arc::clone(&self.suppressed),
+            config: Arc::clone(&self.config),
+            recent_hashes: Arc::new(Mutex::new(VecDeque::new())),
+            history: Arc::clone(&self.history),
+            stats: Arc::clone(&self.stats),
+        };
 
         let handle = thread::Builder::new()
             .name("wolong-clipboard".to_string())
-            .spawn(move || poll_clipboard(cb_holder, shutdown_flag))
+            .spawn(move || run_clipboard_watcher(context, shutdown_flag, worker_thread_id))
             .map_err(|err| {
                 CoreError::Other(anyhow::anyhow!("spawn clipboard thread failed: {err}"))
             })?;
 
-        *worker_guard = Some(ClipboardWorker { shutdown, handle });
+        *worker_guard = Some(ClipboardWorker {
+            shutdown,
+            thread_id,
+            handle,
+        });
         Ok(())
     }
 
     fn stop(&self) {
         let mut worker_guard = self.worker.lock();
         if let Some(worker) = worker_guard.take() {
-            worker.shutdown.store(true, Ordering::Relaxed);
+            worker.shutdown.store(true, Ordering::SeqCst);
+            // A listener thread is parked in GetMessageW; wake it so it sees the flag.
+            let thread_id = worker.thread_id.load(Ordering::SeqCst);
+            if thread_id != 0 {
+                unsafe {
+                    let _ = PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+                }
+            }
             worker.handle.join().ok();
         }
 
         let mut cb_guard = self.callback.lock();
         *cb_guard = None;
     }
+
+    /// Records the current sequence number so the watcher skips a change we made ourselves.
+    ///
+    /// Must run while the clipboard is still open: the sequence number is final
+    /// after the last `SetClipboardData`, but the watcher is only notified on close.
+    fn mark_own_write(&self) {
+        let current = unsafe { GetClipboardSequenceNumber() };
+        self.last_sequence.store(current, Ordering::Relaxed);
+    }
+
+    /// Hides clipboard changes from the watcher until the guard is dropped.
+    ///
+    /// Unlike `mark_own_write` this also covers writes by other processes, whose
+    /// notifications may arrive before we know their sequence number.
+    fn suppress(&self) -> SuppressGuard<'_> {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        SuppressGuard(&self.suppressed)
+    }
 }
 
-fn poll_clipboard(
-    callback_holder: Arc<Mutex<Option<ThreadsafeFunction<ClipboardItem>>>>,
-    shutdown: Arc<AtomicBool>,
-) {
-    let mut last_sequence: u32 = 0;
-    while !shutdown.load(Ordering::Relaxed) {
+struct SuppressGuard<'a>(&'a AtomicU32);
+
+impl Drop for SuppressGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl WatcherContext {
+    fn check_for_change(&self) {
         let current = unsafe { GetClipboardSequenceNumber() };
-        if current != 0 && current != last_sequence {
-            last_sequence = current;
-            if let Ok(snapshot) = capture_clipboard_snapshot(current) {
-                if let Some(callback) = callback_holder.lock().as_ref() {
-                    let item: ClipboardItem = snapshot.into();
-                    let _ = callback.call(Ok(item), ThreadsafeFunctionCallMode::NonBlocking);
-                }
+        if current == 0 || current == self.last_sequence.swap(current, Ordering::Relaxed) {
+            return;
+        }
+        // The sequence still advances while paused so resuming doesn't emit a backlog.
+        if self.paused.load(Ordering::Relaxed) || self.suppressed.load(Ordering::Relaxed) > 0 {
+            return;
+        }
+
+        let config = self.config.lock().clone();
+        if let Ok(snapshot) = capture_clipboard_snapshot(current, &config) {
+            if snapshot.excluded {
+                return;
+            }
+            if self.is_duplicate(&snapshot, config.dedup_window) {
+                return;
+            }
+            self.record_history(&snapshot, config.history_capacity);
+            self.stats.record(&snapshot);
+            if let Some(callback) = self.callback.lock().as_ref() {
+                let item: ClipboardItem = snapshot.into();
+                let _ = callback.call(Ok(item), ThreadsafeFunctionCallMode::NonBlocking);
             }
         }
+    }
+
+    fn record_history(&self, snapshot: &ClipboardSnapshot, capacity: usize) {
+        let mut history = self.history.lock();
+        if capacity > 0 {
+            history.push_back(snapshot.clone());
+        }
+        while history.len() > capacity {
+            history.pop_front();
+        }
+    }
+
+    /// Some apps set the clipboard several times for one copy; skip content we
+    /// emitted within the last `window` snapshots.
+    fn is_duplicate(&self, snapshot: &ClipboardSnapshot, window: usize) -> bool {
+        if window == 0 || snapshot.truncated {
+            return false;
+        }
+
+        let digest = snapshot.content_hash();
+        let mut recent = self.recent_hashes.lock();
+        if recent.contains(&digest) {
+            return true;
+        }
+
+        recent.push_back(digest);
+        while recent.len() > window {
+            recent.pop_front();
+        }
+        false
+    }
+}
+
+fn run_clipboard_watcher(
+    context: WatcherContext,
+    shutdown: Arc<AtomicBool>,
+    thread_id: Arc<AtomicU32>,
+) {
+    match ClipboardListener::create() {
+        Ok(listener) => {
+            thread_id.store(unsafe { GetCurrentThreadId() }, Ordering::SeqCst);
+            listen_clipboard(context, &shutdown, listener);
+        }
+        Err(err) => {
+            log::warn!("clipboard listener unavailable, falling back to polling: {err}");
+            poll_clipboard(context, &shutdown);
+        }
+    }
+}
+
+fn poll_clipboard(context: WatcherContext, shutdown: &AtomicBool) {
+    while !shutdown.load(Ordering::Relaxed) {
+        context.check_for_change();
         thread::sleep(Duration::from_millis(200));
     }
 }
 
-fn capture_clipboard_snapshot(sequence: u32) -> CoreResult<ClipboardSnapshot> {
-    let mut attempts = 0;
-    loop {
-        match unsafe { OpenClipboard(HWND(0)) } {
-            Ok(_) => break,
-            Err(_) if attempts < 5 => {
-                attempts += 1;
-                thread::sleep(Duration::from_millis(20));
+fn listen_clipboard(context: WatcherContext, shutdown: &AtomicBool, _listener: ClipboardListener) {
+    // Pick up whatever is already on the clipboard, like the first poll would.
+    context.check_for_change();
+    LISTENER_CONTEXT.with(|slot| *slot.borrow_mut() = Some(context));
+
+    let mut msg = MSG::default();
+    while !shutdown.load(Ordering::SeqCst) {
+        let result = unsafe { GetMessageW(&mut msg, HWND(0), 0, 0) };
+        if result.0 <= 0 {
+            break;
+        }
+        unsafe {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+
+    LISTENER_CONTEXT.with(|slot| *slot.borrow_mut() = None);
+}
+
+thread_local! {
+    static LISTENER_CONTEXT: RefCell<Option<WatcherContext>> = const { RefCell::new(None) };
+}
+
+const LISTENER_CLASS_NAME: PCWSTR = w!("WolongClipboardListener");
+
+static LISTENER_CLASS: Lazy<u16> = Lazy::new(|| unsafe {
+    let instance = GetModuleHandleW(None).unwrap_or_default();
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(clipboard_listener_proc),
+        hInstance: instance.into(),
+        lpszClassName: LISTENER_CLASS_NAME,
+        ..Default::default()
+    };
+    RegisterClassW(&class)
+});
+
+/// Hidden message-only window registered for `WM_CLIPBOARDUPDATE`.
+struct ClipboardListener {
+    hwnd: HWND,
+}
+
+impl ClipboardListener {
+    fn create() -> CoreResult<Self> {
+        if *LISTENER_CLASS == 0 {
+            return Err(CoreError::Windows(
+                "RegisterClassW clipboard listener failed".to_string(),
+            ));
+        }
+
+        unsafe {
+            let instance = GetModuleHandleW(None).unwrap_or_default();
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                LISTENER_CLASS_NAME,
+                PCWSTR::null(),
+                WINDOW_STYLE(0),
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                None,
+                instance,
+                None,
+            );
+            if hwnd.0 == 0 {
+                return Err(CoreError::from_win32(
+                    "CreateWindowExW clipboard listener failed",
+                ));
+            }
+
+            if AddClipboardFormatListener(hwnd).is_err() {
+                let err = CoreError::from_win32("AddClipboardFormatListener failed");
+                let _ = DestroyWindow(hwnd);
+                return Err(err);
             }
-            Err(_) => return Err(CoreError::from_win32("OpenClipboard failed")),
+
+            Ok(Self { hwnd })
         }
     }
+}
 
-    let _guard = ClipboardGuard;
-    let text = read_clipboard_text()?;
-    let html = read_clipboard_html()?;
-    let image = read_clipboard_image().transpose()?;
+impl Drop for ClipboardListener {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = RemoveClipboardFormatListener(self.hwnd);
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
 
-    let mut formats = Vec::new();
-    if text.is_some() {
-        formats.push("text".to_string());
+unsafe extern "system" fn clipboard_listener_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_CLIPBOARDUPDATE {
+        catch_callback_panic("clipboard listener", || {
+            LISTENER_CONTEXT.with(|slot| {
+                if let Some(context) = slot.borrow().as_ref() {
+                    context.check_for_change();
+                }
+            });
+        });
+        return LRESULT(0);
     }
-    if html.is_some() {
-        formats.push("html".to_string());
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Opens the clipboard with the watcher's retry policy.
+fn open_clipboard() -> CoreResult<ClipboardGuard> {
+    let policy = CLIPBOARD_MANAGER.config.lock().open_retry;
+    open_clipboard_with(&policy)
+}
+
+fn open_clipboard_with(policy: &OpenRetryPolicy) -> CoreResult<ClipboardGuard> {
+    let mut delay = policy.initial_delay;
+    for attempt in 0..=policy.retries {
+        if unsafe { OpenClipboard(HWND(0)) }.is_ok() {
+            return Ok(ClipboardGuard);
+        }
+        if attempt < policy.retries {
+            thread::sleep(delay.min(policy.max_delay));
+            delay = delay.saturating_mul(2);
+        }
     }
-    if image.is_some() {
-        formats.push("image".to_string());
+    CLIPBOARD_MANAGER
+        .stats
+        .open_failures
+        .fetch_add(1, Ordering::Relaxed);
+    Err(CoreError::ClipboardBusy)
+}
+
+fn capture_clipboard_snapshot(
+    sequence: u32,
+    config: &WatcherConfig,
+) -> CoreResult<ClipboardSnapshot> {
+    let _guard = open_clipboard_with(&config.open_retry)?;
+    // Password managers mark secrets so clipboard managers leave them alone.
+    if is_excluded_from_monitoring() {
+        return Ok(ClipboardSnapshot {
+            sequence,
+            timestamp: now_millis(),
+            format: "excluded".to_string(),
+            excluded: true,
+            ..Default::default()
+        });
     }
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|duration| duration.as_millis() as i64)
-        .unwrap_or(0);
+    // The owner may already be destroyed (e.g. a short-lived process); every lookup
+    // below simply yields `None` in that case.
+    let owner = unsafe { GetClipboardOwner() };
+    let source_app = window_process_id(owner).and_then(process_image_path);
+    let source_title = window_title(owner);
+
+    // Check sizes up front so a pathological copy never gets materialized.
+    let mut skipped = Vec::new();
+    let mut within_limit = |name: &'static str, format: u32| {
+        let fits = !exceeds_item_limit(format, config.max_item_bytes);
+        if !fits {
+            skipped.push(name);
+        }
+        fits
+    };
+    let read_text = within_limit("text", available_text_format());
+    let read_html = within_limit("html", registered_format(HTML_FORMAT_NAME));
+    let read_rtf = within_limit("rtf", registered_format(RTF_FORMAT_NAME));
+    let read_files = within_limit("files", CF_HDROP);
+    let read_image = within_limit("image", preferred_image_format());
+
+    let text = if read_text {
+        read_clipboard_text()?
+    } else {
+        None
+    };
+    let (text, raw_text) = match text {
+        Some(raw) if config.normalize_newlines => match normalize_newlines(&raw) {
+            Some(normalized) => (Some(normalized), Some(raw)),
+            None => (Some(raw), None),
+        },
+        text => (text, None),
+    };
+    let html = if read_html {
+        read_clipboard_html()?
+    } else {
+        None
+    };
+    let source_url = html.as_ref().and_then(|html| html.source_url.clone());
+    let html = html.map(|html| html.html);
+    let rtf = if read_rtf {
+        read_clipboard_rtf()?
+    } else {
+        None
+    };
+    let files = if read_files {
+        read_clipboard_files()
+    } else {
+        None
+    };
+    let image = if read_image {
+        read_clipboard_image(config).transpose()?
+    } else {
+        None
+    };
+    let image_original_size = image.as_ref().and_then(|image| image.original_size);
+    let image_mime_type = image.as_ref().map(|image| image.mime_type.to_string());
+    let image = image.map(|image| image.bytes);
+
+    let mut formats = Vec::new();
+    for (name, present) in [
+        ("text", text.is_some()),
+        ("html", html.is_some()),
+        ("rtf", rtf.is_some()),
+        ("files", files.is_some()),
+        ("image", image.is_some()),
+    ] {
+        if present || skipped.contains(&name) {
+            formats.push(name.to_string());
+        }
+    }
 
     Ok(ClipboardSnapshot {
         sequence,
-        timestamp,
+        timestamp: now_millis(),
         format: if formats.is_empty() {
             "unknown".to_string()
         } else {
             formats.join(",")
         },
+        formats,
         text,
+        raw_text,
         html,
+        rtf,
+        files,
         image,
+        image_mime_type,
+        image_original_size,
+        source_app,
+        source_title,
+        source_url,
+        truncated: !skipped.is_empty(),
+        excluded: false,
     })
 }
 
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Honors the opt-out formats documented for clipboard history/monitoring apps.
+fn is_excluded_from_monitoring() -> bool {
+    let available = |name: &[u8]| {
+        let format = registered_format(name);
+        format != 0 && unsafe { IsClipboardFormatAvailable(format).is_ok() }
+    };
+
+    if available(EXCLUDE_MONITOR_FORMAT_NAME) || available(VIEWER_IGNORE_FORMAT_NAME) {
+        return true;
+    }
+
+    // `CanIncludeInClipboardHistory` is a DWORD; zero means "keep out of history".
+    unsafe {
+        let format = registered_format(CAN_INCLUDE_HISTORY_FORMAT_NAME);
+        if format == 0 {
+            return false;
+        }
+        let handle = match GetClipboardData(format).ok() {
+            Some(handle) if handle.0 != 0 => handle,
+            _ => return false,
+        };
+        let global = HGLOBAL(handle.0 as *mut c_void);
+        if GlobalSize(global) < size_of::<u32>() {
+            return false;
+        }
+        let locked = GlobalLock(global);
+        if locked.is_null() {
+            return false;
+        }
+        let value = ptr::read_unaligned(locked as *const u32);
+        let _ = GlobalUnlock(global);
+        value == 0
+    }
+}
+
+fn registered_format(name: &[u8]) -> u32 {
+    unsafe { RegisterClipboardFormatA(PCSTR::from_raw(name.as_ptr())) }
+}
+
+/// Returns true when `format` is present and its global is larger than `limit`.
+fn exceeds_item_limit(format: u32, limit: Option<usize>) -> bool {
+    let Some(limit) = limit else {
+        return false;
+    };
+    if format == 0 {
+        return false;
+    }
+
+    unsafe {
+        match GetClipboardData(format).ok() {
+            Some(handle) if handle.0 != 0 => GlobalSize(HGLOBAL(handle.0 as *mut c_void)) > limit,
+            _ => false,
+        }
+    }
+}
+
 struct ClipboardGuard;
 
 impl Drop for ClipboardGuard {
@@ -190,9 +1055,45 @@ impl Drop for ClipboardGuard {
     }
 }
 
+/// Copies `bytes` into a movable global and hands it to the clipboard.
+///
+/// The clipboard must already be open and emptied. On success the system owns the
+/// allocation; on failure it is freed here.
+unsafe fn set_clipboard_bytes(format: u32, bytes: &[u8]) -> CoreResult<()> {
+    let global = GlobalAlloc(GMEM_MOVEABLE, bytes.len())
+        .map_err(|_| CoreError::from_win32("GlobalAlloc clipboard buffer failed"))?;
+
+    let locked = GlobalLock(global);
+    if locked.is_null() {
+        let err = CoreError::from_win32("GlobalLock clipboard buffer failed");
+        let _ = GlobalFree(global);
+        return Err(err);
+    }
+
+    ptr::copy_nonoverlapping(bytes.as_ptr(), locked as *mut u8, bytes.len());
+    let _ = GlobalUnlock(global);
+
+    if SetClipboardData(format, HANDLE(global.0 as isize)).is_err() {
+        let err = CoreError::from_win32("SetClipboardData failed");
+        let _ = GlobalFree(global);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Legacy apps may only offer ANSI text, so fall back through the narrow formats.
+fn available_text_format() -> u32 {
+    [CF_UNICODETEXT, CF_TEXT, CF_OEMTEXT]
+        .into_iter()
+        .find(|format| unsafe { IsClipboardFormatAvailable(*format).is_ok() })
+        .unwrap_or(CF_UNICODETEXT)
+}
+
 fn read_clipboard_text() -> CoreResult<Option<String>> {
+    let format = available_text_format();
     unsafe {
-        let handle = match GetClipboardData(CF_UNICODETEXT).ok() {
+        let handle = match GetClipboardData(format).ok() {
             Some(handle) if handle.0 != 0 => handle,
             _ => return Ok(None),
         };
@@ -203,21 +1104,67 @@ fn read_clipboard_text() -> CoreResult<Option<String>> {
             return Err(CoreError::from_win32("GlobalLock clipboard text failed"));
         }
 
-        let text = PCWSTR::from_raw(locked as *const u16)
-            .to_string()
-            .map_err(|err| {
-                CoreError::Other(anyhow::anyhow!("convert clipboard text failed: {err}"))
-            })?;
+        let text = if format == CF_UNICODETEXT {
+            PCWSTR::from_raw(locked as *const u16)
+                .to_string()
+                .map_err(|err| {
+                    CoreError::Other(anyhow::anyhow!("convert clipboard text failed: {err}"))
+                })
+        } else {
+            let bytes = slice::from_raw_parts(locked as *const u8, GlobalSize(global));
+            let len = bytes
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(bytes.len());
+            let code_page = if format == CF_OEMTEXT {
+                CP_OEMCP
+            } else {
+                CP_ACP
+            };
+            decode_multibyte(code_page, &bytes[..len])
+        };
 
         let _ = GlobalUnlock(global);
-        Ok(Some(text))
+        text.map(Some)
     }
 }
 
-fn read_clipboard_html() -> CoreResult<Option<String>> {
+/// Converts `\r\n` and lone `\r` to `\n`; `None` when `text` has neither.
+fn normalize_newlines(text: &str) -> Option<String> {
+    if !text.contains('\r') {
+        return None;
+    }
+    Some(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+fn decode_multibyte(code_page: u32, bytes: &[u8]) -> CoreResult<String> {
+    if bytes.is_empty() {
+        return Ok(String::new());
+    }
+
+    let flags = MULTI_BYTE_TO_WIDE_CHAR_FLAGS(0);
+    let len = unsafe { MultiByteToWideChar(code_page, flags, bytes, None) };
+    if len <= 0 {
+        return Err(CoreError::from_win32("MultiByteToWideChar failed"));
+    }
+
+    let mut wide = vec![0u16; len as usize];
+    let written = unsafe { MultiByteToWideChar(code_page, flags, bytes, Some(&mut wide)) };
+    if written <= 0 {
+        return Err(CoreError::from_win32("MultiByteToWideChar failed"));
+    }
+    wide.truncate(written as usize);
+    Ok(String::from_utf16_lossy(&wide))
+}
+
+struct ClipboardHtml {
+    html: String,
+    source_url: Option<String>,
+}
+
+fn read_clipboard_html() -> CoreResult<Option<ClipboardHtml>> {
     unsafe {
-        let format_name = b"HTML Format\0";
-        let cf_html = RegisterClipboardFormatA(PCSTR::from_raw(format_name.as_ptr()));
+        let cf_html = registered_format(HTML_FORMAT_NAME);
         if cf_html == 0 {
             return Ok(None);
         }
@@ -240,62 +1187,136 @@ fn read_clipboard_html() -> CoreResult<Option<String>> {
         }
 
         let data = slice::from_raw_parts(locked as *const u8, size as usize);
-        let html_raw = String::from_utf8_lossy(data).to_string();
-        
-        // Windows clipboard HTML format has a special header:
-        // Version:0.9
-        // StartHTML:0000000000
-        // EndHTML:0000000000
-        // StartFragment:0000000000
-        // EndFragment:0000000000
-        // ...actual HTML content...
-        let html = if html_raw.starts_with("Version:") {
-            // Extract the actual HTML content after the header
-            if let Some(start_fragment_pos) = html_raw.find("StartFragment:") {
-                if let Some(end_fragment_pos) = html_raw.find("EndFragment:") {
-                    if let Some(start_html_pos) = html_raw.find("StartHTML:") {
-                        if let Some(end_html_pos) = html_raw.find("EndHTML:") {
-                            // Parse offsets
-                            let start_html_offset = html_raw[start_html_pos + 10..]
-                                .lines()
-                                .next()
-                                .and_then(|s| s.trim().parse::<usize>().ok())
-                                .unwrap_or(0);
-                            let end_html_offset = html_raw[end_html_pos + 8..]
-                                .lines()
-                                .next()
-                                .and_then(|s| s.trim().parse::<usize>().ok())
-                                .unwrap_or(html_raw.len());
-                            
-                            if start_html_offset < html_raw.len() && end_html_offset <= html_raw.len() && start_html_offset < end_html_offset {
-                                html_raw[start_html_offset..end_html_offset].to_string()
-                            } else {
-                                html_raw
-                            }
-                        } else {
-                            html_raw
-                        }
-                    } else {
-                        html_raw
-                    }
-                } else {
-                    html_raw
-                }
-            } else {
-                html_raw
-            }
-        } else {
-            html_raw
+        let html = parse_cf_html(data);
+
+        let _ = GlobalUnlock(global);
+        Ok((!html.html.trim().is_empty()).then_some(html))
+    }
+}
+
+/// Splits a CF_HTML block into its markup and header metadata.
+///
+/// The header looks like:
+/// Version:0.9
+/// StartHTML:0000000105
+/// EndHTML:0000000199
+/// StartFragment:0000000141
+/// EndFragment:0000000163
+/// SourceURL:https://example.com/
+/// ...followed by the HTML document.
+///
+/// Offsets are byte offsets into the raw block, so the buffer is sliced before
+/// decoding. The fragment (the actual selection) wins over the full document;
+/// anything malformed falls back to everything after the header.
+fn parse_cf_html(data: &[u8]) -> ClipboardHtml {
+    let data = &data[..data
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(data.len())];
+    let body_start = data
+        .iter()
+        .position(|byte| *byte == b'<')
+        .unwrap_or(data.len());
+    let header = String::from_utf8_lossy(&data[..body_start]);
+
+    let offset = |key: &str| {
+        cf_html_header_value(&header, key).and_then(|value| value.parse::<usize>().ok())
+    };
+    let range = |start: &str, end: &str| match (offset(start), offset(end)) {
+        (Some(start), Some(end)) if start < end && end <= data.len() => Some(start..end),
+        _ => None,
+    };
+
+    let markup = range("StartFragment", "EndFragment")
+        .or_else(|| range("StartHTML", "EndHTML"))
+        .map_or(&data[body_start..], |range| &data[range]);
+
+    ClipboardHtml {
+        html: String::from_utf8_lossy(markup).into_owned(),
+        source_url: cf_html_header_value(&header, "SourceURL")
+            .filter(|url| !url.is_empty())
+            .map(str::to_string),
+    }
+}
+
+/// Looks up `key` among the `Key:Value` lines of a CF_HTML header.
+fn cf_html_header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    header
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .map(str::trim)
+}
+
+fn read_clipboard_rtf() -> CoreResult<Option<String>> {
+    unsafe {
+        let cf_rtf = registered_format(RTF_FORMAT_NAME);
+        if cf_rtf == 0 {
+            return Ok(None);
+        }
+
+        let handle = match GetClipboardData(cf_rtf).ok() {
+            Some(handle) if handle.0 != 0 => handle,
+            _ => return Ok(None),
         };
 
+        let global = HGLOBAL(handle.0 as *mut c_void);
+        let locked = GlobalLock(global);
+        if locked.is_null() {
+            return Err(CoreError::from_win32("GlobalLock clipboard RTF failed"));
+        }
+
+        let size = GlobalSize(global);
+        let data = slice::from_raw_parts(locked as *const u8, size);
+        // The global is often larger than the document and padded with NULs.
+        let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        let rtf = String::from_utf8_lossy(&data[..len]).into_owned();
+
         let _ = GlobalUnlock(global);
-        Ok(Some(html))
+        Ok((!rtf.is_empty()).then_some(rtf))
+    }
+}
+
+struct ClipboardImage {
+    bytes: Vec<u8>,
+    mime_type: &'static str,
+    original_size: Option<(u32, u32)>,
+}
+
+fn read_clipboard_files() -> Option<Vec<String>> {
+    unsafe {
+        let handle = match GetClipboardData(CF_HDROP).ok() {
+            Some(handle) if handle.0 != 0 => handle,
+            _ => return None,
+        };
+
+        let hdrop = HDROP(handle.0);
+        let count = DragQueryFileW(hdrop, u32::MAX, None);
+        let mut files = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            let len = DragQueryFileW(hdrop, index, None);
+            let mut buffer = vec![0u16; len as usize + 1];
+            DragQueryFileW(hdrop, index, Some(&mut buffer));
+            if let Some(path) = string_from_wide(&buffer) {
+                files.push(path);
+            }
+        }
+
+        (!files.is_empty()).then_some(files)
+    }
+}
+
+/// `CF_DIBV5` carries alpha and channel masks, so prefer it over the plain DIB.
+fn preferred_image_format() -> u32 {
+    if unsafe { IsClipboardFormatAvailable(CF_DIBV5).is_ok() } {
+        CF_DIBV5
+    } else {
+        CF_DIB
     }
 }
 
-fn read_clipboard_image() -> Option<CoreResult<Vec<u8>>> {
+fn read_clipboard_image(config: &WatcherConfig) -> Option<CoreResult<ClipboardImage>> {
     unsafe {
-        let handle = match GetClipboardData(CF_DIB).ok() {
+        let handle = match GetClipboardData(preferred_image_format()).ok() {
             Some(handle) if handle.0 != 0 => handle,
             _ => return None,
         };
@@ -315,106 +1336,423 @@ fn read_clipboard_image() -> Option<CoreResult<Vec<u8>>> {
         }
 
         let data = slice::from_raw_parts(locked as *const u8, size as usize);
-        let header = match parse_bitmap_header(data) {
-            Ok(header) => header,
-            Err(err) => {
-                let _ = GlobalUnlock(global);
-                return Some(Err(err));
+        let decoded = decode_dib(data);
+        let _ = GlobalUnlock(global);
+        let (rgba, width_u32, height_u32) = match decoded {
+            Ok(decoded) => decoded,
+            Err(err) => return Some(Err(err)),
+        };
+
+        let (rgba, out_width, out_height, original_size) = match config
+            .max_image_dimension
+            .and_then(|max| scaled_dimensions(width_u32, height_u32, max))
+        {
+            Some((target_width, target_height)) => {
+                let Some(source) = RgbaImage::from_raw(width_u32, height_u32, rgba) else {
+                    return Some(Err(CoreError::Other(anyhow::anyhow!(
+                        "clipboard image buffer size mismatch"
+                    ))));
+                };
+                let scaled = image::imageops::resize(
+                    &source,
+                    target_width,
+                    target_height,
+                    FilterType::Triangle,
+                );
+                (
+                    scaled.into_raw(),
+                    target_width,
+                    target_height,
+                    Some((width_u32, height_u32)),
+                )
             }
+            None => (rgba, width_u32, height_u32, None),
         };
 
-        let pixels_offset = header.header_size as usize;
-        let width_u32 = abs_i32_to_u32(header.width);
-        let height_u32 = abs_i32_to_u32(header.height);
-        let stride = (((u32::from(header.bit_count) * width_u32) + 31) / 32 * 4) as usize;
-        let height = height_u32 as usize;
-        let width = width_u32 as usize;
+        Some(
+            encode_rgba(
+                &rgba,
+                out_width,
+                out_height,
+                config.image_format,
+                config.image_quality,
+            )
+            .map(|(bytes, mime_type)| ClipboardImage {
+                bytes,
+                mime_type,
+                original_size,
+            }),
+        )
+    }
+}
 
-        if pixels_offset + stride * height > data.len() {
-            let _ = GlobalUnlock(global);
-            return Some(Err(CoreError::Other(anyhow::anyhow!(
-                "clipboard DIB buffer too small"
-            ))));
-        }
+/// Decodes a packed DIB (`CF_DIB`/`CF_DIBV5` data) into top-down RGBA pixels.
+///
+/// The header comes from another process, so every size derived from it is
+/// checked against `data` before any pixel is read.
+fn decode_dib(data: &[u8]) -> CoreResult<(Vec<u8>, u32, u32)> {
+    let header = parse_bitmap_header(data)?;
+    if !matches!(
+        (header.masks, header.bit_count),
+        (None, 24 | 32) | (Some(_), 16 | 32)
+    ) {
+        return Err(CoreError::Other(anyhow::anyhow!(
+            "unsupported clipboard bit depth: {}",
+            header.bit_count
+        )));
+    }
+
+    let pixels_offset = header.pixels_offset;
+    let width_u32 = abs_i32_to_u32(header.width);
+    let height_u32 = abs_i32_to_u32(header.height);
+    let stride = dib_stride(header.bit_count, width_u32);
+    let height = height_u32 as usize;
+    let width = width_u32 as usize;
 
-        let mut rgba = Vec::with_capacity(width * height * 4);
-        let top_down = header.height < 0;
+    let pixels_end = stride
+        .checked_mul(height)
+        .and_then(|len| len.checked_add(pixels_offset));
+    if pixels_end.is_none_or(|end| end > data.len()) {
+        return Err(CoreError::Other(anyhow::anyhow!(
+            "clipboard DIB buffer too small"
+        )));
+    }
 
-        for row in 0..height {
-            let src_row = if top_down { row } else { height - 1 - row };
-            let row_start = pixels_offset + src_row * stride;
-            let row_data = &data[row_start..row_start + stride];
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    let top_down = header.height < 0;
 
-            match header.bit_count {
-                32 => {
-                    for chunk in row_data.chunks_exact(4).take(width) {
-                        rgba.extend_from_slice(&[chunk[2], chunk[1], chunk[0], chunk[3]]);
-                    }
+    for row in 0..height {
+        let src_row = if top_down { row } else { height - 1 - row };
+        let row_start = pixels_offset + src_row * stride;
+        let row_data = &data[row_start..row_start + stride];
+
+        match (header.masks, header.bit_count) {
+            (None, 32) => {
+                for chunk in row_data.chunks_exact(4).take(width) {
+                    rgba.extend_from_slice(&[chunk[2], chunk[1], chunk[0], chunk[3]]);
                 }
-                24 => {
-                    for chunk in row_data.chunks_exact(3).take(width) {
-                        rgba.extend_from_slice(&[chunk[2], chunk[1], chunk[0], 255]);
-                    }
+            }
+            (None, _) => {
+                for chunk in row_data.chunks_exact(3).take(width) {
+                    rgba.extend_from_slice(&[chunk[2], chunk[1], chunk[0], 255]);
                 }
-                _ => {
-                    let _ = GlobalUnlock(global);
-                    return Some(Err(CoreError::Other(anyhow::anyhow!(
-                        "unsupported clipboard bit depth: {}",
-                        header.bit_count
-                    ))));
+            }
+            (Some(masks), 32) => {
+                for chunk in row_data.chunks_exact(4).take(width) {
+                    let pixel = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                    rgba.extend_from_slice(&masks.to_rgba(pixel));
+                }
+            }
+            (Some(masks), _) => {
+                for chunk in row_data.chunks_exact(2).take(width) {
+                    let pixel = u32::from(u16::from_le_bytes([chunk[0], chunk[1]]));
+                    rgba.extend_from_slice(&masks.to_rgba(pixel));
                 }
             }
         }
+    }
 
-        let _ = GlobalUnlock(global);
-
-        let mut png = Vec::new();
-        if let Err(err) = PngEncoder::new(&mut png).write_image(
-            &rgba,
-            width as u32,
-            height as u32,
-            ColorType::Rgba8,
-        ) {
-            return Some(Err(CoreError::Other(anyhow::anyhow!(
-                "encode clipboard image failed: {err}"
-            ))));
-        }
+    Ok((rgba, width_u32, height_u32))
+}
 
-        Some(Ok(png))
+/// Returns the aspect-preserving size that fits within `max_dimension`, or `None`
+/// when the image already fits.
+fn scaled_dimensions(width: u32, height: u32, max_dimension: u32) -> Option<(u32, u32)> {
+    let longest = width.max(height);
+    if max_dimension == 0 || longest <= max_dimension {
+        return None;
     }
+
+    let scale = f64::from(max_dimension) / f64::from(longest);
+    let scaled_width = ((f64::from(width) * scale).round() as u32).max(1);
+    let scaled_height = ((f64::from(height) * scale).round() as u32).max(1);
+    Some((scaled_width, scaled_height))
 }
 
 struct DibHeader {
     width: i32,
     height: i32,
     bit_count: u16,
-    header_size: u32,
+    /// Byte offset of the first pixel row from the start of the DIB.
+    pixels_offset: usize,
+    /// Channel masks for `BI_BITFIELDS` (and 16bpp `BI_RGB`); `None` is the
+    /// plain 24/32bpp BGR(A) fast path.
+    masks: Option<ChannelMasks>,
+}
+
+#[derive(Clone, Copy)]
+struct ChannelMasks {
+    red: u32,
+    green: u32,
+    blue: u32,
+    alpha: u32,
+}
+
+impl ChannelMasks {
+    /// The implicit X1R5G5B5 layout of 16bpp `BI_RGB` bitmaps.
+    const RGB555: ChannelMasks = ChannelMasks {
+        red: 0x7C00,
+        green: 0x03E0,
+        blue: 0x001F,
+        alpha: 0,
+    };
+
+    fn to_rgba(self, pixel: u32) -> [u8; 4] {
+        let alpha = if self.alpha == 0 {
+            255
+        } else {
+            extract_channel(pixel, self.alpha)
+        };
+        [
+            extract_channel(pixel, self.red),
+            extract_channel(pixel, self.green),
+            extract_channel(pixel, self.blue),
+            alpha,
+        ]
+    }
+}
+
+/// Pulls the bits selected by `mask` out of `pixel` and scales them to 0..=255.
+fn extract_channel(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = mask.count_ones();
+    let value = (pixel & mask) >> mask.trailing_zeros();
+    if bits >= 8 {
+        (value >> (bits - 8)) as u8
+    } else {
+        let max = (1u32 << bits) - 1;
+        ((value * 255 + max / 2) / max) as u8
+    }
+}
+
+const BI_ALPHABITFIELDS: u32 = 6;
+const BITMAPINFOHEADER_SIZE: usize = 40;
+/// BITMAPV2INFOHEADER and later carry RGB masks inside the header.
+const HEADER_WITH_RGB_MASKS: usize = 52;
+/// BITMAPV3INFOHEADER and later also carry the alpha mask.
+const HEADER_WITH_ALPHA_MASK: usize = 56;
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
 }
 
 fn parse_bitmap_header(data: &[u8]) -> CoreResult<DibHeader> {
-    if data.len() < std::mem::size_of::<BITMAPINFOHEADER>() {
+    if data.len() < size_of::<BITMAPINFOHEADER>() {
         return Err(CoreError::Other(anyhow::anyhow!(
             "clipboard DIB header too small"
         )));
     }
 
-    let header = unsafe { *(data.as_ptr() as *const BITMAPINFOHEADER) };
+    let header = unsafe { ptr::read_unaligned(data.as_ptr() as *const BITMAPINFOHEADER) };
+    let header_size = header.biSize as usize;
+    if header_size < BITMAPINFOHEADER_SIZE || header_size > data.len() {
+        return Err(CoreError::Other(anyhow::anyhow!(
+            "invalid clipboard DIB header size {}",
+            header.biSize
+        )));
+    }
+
+    let compression = header.biCompression;
+    let mut pixels_offset = header_size;
+    let masks = if compression == BI_RGB.0 {
+        (header.biBitCount == 16).then_some(ChannelMasks::RGB555)
+    } else if compression == BI_BITFIELDS.0 || compression == BI_ALPHABITFIELDS {
+        // Old-style headers append the masks after the header; newer ones embed them.
+        let (mask_base, has_alpha) = if header_size >= HEADER_WITH_RGB_MASKS {
+            (BITMAPINFOHEADER_SIZE, header_size >= HEADER_WITH_ALPHA_MASK)
+        } else {
+            let has_alpha = compression == BI_ALPHABITFIELDS;
+            pixels_offset += if has_alpha { 16 } else { 12 };
+            (header_size, has_alpha)
+        };
 
-    if header.biCompression != BI_RGB.0 {
+        let mask_at = |index: usize| read_u32(data, mask_base + index * 4);
+        let (Some(red), Some(green), Some(blue)) = (mask_at(0), mask_at(1), mask_at(2)) else {
+            return Err(CoreError::Other(anyhow::anyhow!(
+                "clipboard DIB color masks truncated"
+            )));
+        };
+        let alpha = if has_alpha {
+            mask_at(3).unwrap_or(0)
+        } else {
+            0
+        };
+        Some(ChannelMasks {
+            red,
+            green,
+            blue,
+            alpha,
+        })
+    } else {
         return Err(CoreError::Other(anyhow::anyhow!(
             "unsupported compression {}",
-            header.biCompression
+            compression
         )));
+    };
+
+    // Anything above 8bpp may still carry an optional palette hint.
+    pixels_offset += header.biClrUsed as usize * 4;
+
+    // Some producers write a V5 header *and* repeat the three masks after it.
+    if let Some(masks) = masks.filter(|_| header_size >= HEADER_WITH_RGB_MASKS) {
+        let stride = dib_stride(header.biBitCount, abs_i32_to_u32(header.biWidth));
+        let pixels_end = stride
+            .checked_mul(abs_i32_to_u32(header.biHeight) as usize)
+            .and_then(|len| len.checked_add(pixels_offset + 12));
+        let repeated = read_u32(data, pixels_offset) == Some(masks.red)
+            && read_u32(data, pixels_offset + 4) == Some(masks.green)
+            && read_u32(data, pixels_offset + 8) == Some(masks.blue);
+        if repeated && pixels_end.is_some_and(|end| data.len() >= end) {
+            pixels_offset += 12;
+        }
     }
 
     Ok(DibHeader {
         width: header.biWidth,
         height: header.biHeight,
         bit_count: header.biBitCount,
-        header_size: header.biSize,
+        pixels_offset,
+        masks,
     })
 }
 
+/// NUL-terminated little-endian UTF-16, as `CF_UNICODETEXT` expects.
+fn utf16_bytes(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Wraps an HTML document or fragment in the CF_HTML header.
+///
+/// If `html` already carries `<!--StartFragment-->`/`<!--EndFragment-->` markers
+/// they are reused, otherwise the whole input becomes the fragment.
+fn build_cf_html(html: &str) -> Vec<u8> {
+    const START_MARKER: &str = "<!--StartFragment-->";
+    const END_MARKER: &str = "<!--EndFragment-->";
+
+    let document = if html.contains(START_MARKER) && html.contains(END_MARKER) {
+        html.to_string()
+    } else {
+        format!("<html><body>{START_MARKER}{html}{END_MARKER}</body></html>")
+    };
+
+    let header = |start_html: usize,
+                  end_html: usize,
+                  start_fragment: usize,
+                  end_fragment: usize| {
+        format!(
+            "Version:0.9\r\nStartHTML:{start_html:010}\r\nEndHTML:{end_html:010}\r\nStartFragment:{start_fragment:010}\r\nEndFragment:{end_fragment:010}\r\n"
+        )
+    };
+
+    // Every offset is padded to ten digits, so the header length is fixed.
+    let start_html = header(0, 0, 0, 0).len();
+    let end_html = start_html + document.len();
+    let start_fragment =
+        start_html + document.find(START_MARKER).unwrap_or_default() + START_MARKER.len();
+    let end_fragment = start_html + document.rfind(END_MARKER).unwrap_or(document.len());
+
+    let mut bytes = header(start_html, end_html, start_fragment, end_fragment).into_bytes();
+    bytes.extend_from_slice(document.as_bytes());
+    bytes.push(0);
+    bytes
+}
+
+/// Builds a `DROPFILES` block followed by a double-NUL-terminated wide path list.
+fn build_drop_files(files: &[String]) -> Vec<u8> {
+    let header = DROPFILES {
+        pFiles: size_of::<DROPFILES>() as u32,
+        fWide: true.into(),
+        ..Default::default()
+    };
+
+    let mut bytes = struct_bytes(&header).to_vec();
+    for file in files {
+        let native = file.replace('/', "\\");
+        bytes.extend(
+            native
+                .encode_utf16()
+                .chain(std::iter::once(0))
+                .flat_map(u16::to_le_bytes),
+        );
+    }
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+}
+
+/// Row size in bytes of an uncompressed DIB; rows are padded to a DWORD boundary.
+fn dib_stride(bit_count: u16, width: u32) -> usize {
+    // u64 so a bogus header width can't overflow the bit count.
+    ((u64::from(bit_count) * u64::from(width)).div_ceil(32) * 4) as usize
+}
+
+/// Converts top-down RGBA pixels into the bottom-up BGRA layout DIBs expect.
+fn rgba_to_bottom_up_bgra(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let stride = dib_stride(32, width);
+    let row_len = width as usize * 4;
+    let mut bgra = vec![0u8; stride * height as usize];
+
+    for (row, src_row) in rgba.chunks_exact(row_len).enumerate() {
+        let dst_start = (height as usize - 1 - row) * stride;
+        let dst_row = &mut bgra[dst_start..dst_start + row_len];
+        for (dst, src) in dst_row.chunks_exact_mut(4).zip(src_row.chunks_exact(4)) {
+            dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+        }
+    }
+
+    bgra
+}
+
+fn build_dibv5(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let header = BITMAPV5HEADER {
+        bV5Size: size_of::<BITMAPV5HEADER>() as u32,
+        bV5Width: width as i32,
+        bV5Height: height as i32,
+        bV5Planes: 1,
+        bV5BitCount: 32,
+        bV5Compression: BI_BITFIELDS,
+        bV5SizeImage: pixels.len() as u32,
+        bV5RedMask: 0x00FF_0000,
+        bV5GreenMask: 0x0000_FF00,
+        bV5BlueMask: 0x0000_00FF,
+        bV5AlphaMask: 0xFF00_0000,
+        bV5CSType: LCS_SRGB,
+        ..Default::default()
+    };
+
+    let mut data = Vec::with_capacity(size_of::<BITMAPV5HEADER>() + pixels.len());
+    data.extend_from_slice(struct_bytes(&header));
+    data.extend_from_slice(pixels);
+    data
+}
+
+fn build_dib(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let header = BITMAPINFOHEADER {
+        biSize: size_of::<BITMAPINFOHEADER>() as u32,
+        biWidth: width as i32,
+        biHeight: height as i32,
+        biPlanes: 1,
+        biBitCount: 32,
+        biCompression: BI_RGB.0,
+        biSizeImage: pixels.len() as u32,
+        ..Default::default()
+    };
+
+    let mut data = Vec::with_capacity(size_of::<BITMAPINFOHEADER>() + pixels.len());
+    data.extend_from_slice(struct_bytes(&header));
+    data.extend_from_slice(pixels);
+    data
+}
+
+fn struct_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
 fn abs_i32_to_u32(value: i32) -> u32 {
     if value < 0 {
         value.wrapping_neg() as u32
@@ -423,15 +1761,418 @@ fn abs_i32_to_u32(value: i32) -> u32 {
     }
 }
 
+impl From<ClipboardSnapshot> for ClipboardContent {
+    fn from(snapshot: ClipboardSnapshot) -> Self {
+        ClipboardContent {
+            // Windows apps expect the original `\r\n` line endings back.
+            text: snapshot.raw_text.or(snapshot.text),
+            html: snapshot.html,
+            rtf: snapshot.rtf,
+            files: snapshot.files,
+            image: snapshot.image,
+        }
+    }
+}
+
+impl From<ClipboardItem> for ClipboardContent {
+    fn from(item: ClipboardItem) -> Self {
+        ClipboardContent {
+            text: item.raw_text.or(item.text),
+            html: item.html,
+            rtf: item.rtf,
+            files: item.files,
+            image: item.image.map(|image| image.to_vec()),
+        }
+    }
+}
+
+impl From<ClipboardWatcherOptions> for WatcherConfig {
+    fn from(options: ClipboardWatcherOptions) -> Self {
+        let defaults = OpenRetryPolicy::default();
+        WatcherConfig {
+            max_image_dimension: options.max_image_dimension,
+            max_item_bytes: options.max_item_bytes.map(|bytes| bytes as usize),
+            dedup_window: options.dedup_window.map_or(1, |window| window as usize),
+            history_capacity: options
+                .history_capacity
+                .map_or(DEFAULT_HISTORY_CAPACITY, |capacity| capacity as usize),
+            image_format: options.image_format.unwrap_or(ImageFormat::Png),
+            image_quality: options
+                .image_quality
+                .unwrap_or(DEFAULT_IMAGE_QUALITY)
+                .clamp(1, 100),
+            normalize_newlines: options.normalize_newlines.unwrap_or(false),
+            open_retry: OpenRetryPolicy {
+                retries: options.open_retries.unwrap_or(defaults.retries),
+                initial_delay: options
+                    .open_retry_delay_ms
+                    .map_or(defaults.initial_delay, |ms| {
+                        Duration::from_millis(ms as u64)
+                    }),
+                max_delay: options
+                    .open_retry_max_delay_ms
+                    .map_or(defaults.max_delay, |ms| Duration::from_millis(ms as u64)),
+            },
+        }
+    }
+}
+
+/// Buckets clipboard text so the UI can render it appropriately.
+///
+/// Returns one of `url`, `email`, `color`, `number`, `file_path` or `text`.
+fn classify_text(text: &str) -> &'static str {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.contains('\n') {
+        return "text";
+    }
+
+    // Colors like `rgb(1, 2, 3)` and paths like `C:\Program Files` may contain spaces;
+    // everything else with whitespace is prose.
+    if is_color(trimmed) {
+        "color"
+    } else if is_file_path(trimmed) {
+        "file_path"
+    } else if trimmed.contains(char::is_whitespace) {
+        "text"
+    } else if is_url(trimmed) {
+        "url"
+    } else if is_email(trimmed) {
+        "email"
+    } else if is_number(trimmed) {
+        "number"
+    } else {
+        "text"
+    }
+}
+
+fn is_url(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    ["http://", "https://", "ftp://"]
+        .iter()
+        .any(|scheme| lower.len() > scheme.len() && lower.starts_with(scheme))
+}
+
+fn is_email(value: &str) -> bool {
+    let address = match value.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("mailto:") => &value[7..],
+        _ => value,
+    };
+    // Drop any `?subject=...` query that mailto links carry.
+    let address = address.split('?').next().unwrap_or_default();
+
+    let Some((local, domain)) = address.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-')
+}
+
+fn is_color(value: &str) -> bool {
+    if let Some(hex) = value.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+
+    let lower = value.to_ascii_lowercase();
+    let Some(args) = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    else {
+        return false;
+    };
+    let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+    matches!(parts.len(), 3 | 4)
+        && parts.iter().enumerate().all(|(index, part)| {
+            if index == 3 {
+                part.parse::<f32>()
+                    .is_ok_and(|alpha| (0.0..=1.0).contains(&alpha))
+            } else {
+                part.parse::<u16>().is_ok_and(|channel| channel <= 255)
+            }
+        })
+}
+
+fn is_number(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return false;
+    }
+    let cleaned: String = digits.chars().filter(|&c| c != ',' && c != '_').collect();
+    cleaned.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+fn is_file_path(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let drive_path = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    let unc_path = value.starts_with("\\\\") && value.len() > 2;
+    drive_path || unc_path
+}
+
 impl From<ClipboardSnapshot> for ClipboardItem {
     fn from(snapshot: ClipboardSnapshot) -> Self {
+        let kind = snapshot
+            .text
+            .as_deref()
+            .map(classify_text)
+            .unwrap_or(if snapshot.image.is_some() {
+                "image"
+            } else if snapshot.files.is_some() {
+                "files"
+            } else {
+                "text"
+            })
+            .to_string();
         ClipboardItem {
             sequence: snapshot.sequence,
             timestamp: snapshot.timestamp,
             format: snapshot.format,
+            formats: snapshot.formats,
             text: snapshot.text,
+            raw_text: snapshot.raw_text,
             html: snapshot.html,
+            rtf: snapshot.rtf,
+            files: snapshot.files,
             image: snapshot.image.map(Buffer::from),
+            image_mime_type: snapshot.image_mime_type,
+            image_original_width: snapshot.image_original_size.map(|(width, _)| width),
+            image_original_height: snapshot.image_original_size.map(|(_, height)| height),
+            source_app: snapshot.source_app,
+            source_title: snapshot.source_title,
+            source_url: snapshot.source_url,
+            truncated: snapshot.truncated,
+            kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_text_detects_mailto_links_as_email() {
+        assert_eq!(classify_text("mailto:someone@example.com"), "email");
+        assert_eq!(
+            classify_text("mailto:someone@example.com?subject=Hi"),
+            "email"
+        );
+        assert_eq!(classify_text("someone@example.com"), "email");
+    }
+
+    #[test]
+    fn classify_text_rejects_non_hex_colors() {
+        assert_eq!(classify_text("#GGG"), "text");
+        assert_eq!(classify_text("#12345"), "text");
+    }
+
+    #[test]
+    fn classify_text_accepts_hex_and_rgb_colors() {
+        assert_eq!(classify_text("#1a2B3c"), "color");
+        assert_eq!(classify_text("#fff"), "color");
+        assert_eq!(classify_text("rgb(255, 128, 0)"), "color");
+    }
+
+    #[test]
+    fn classify_text_detects_urls() {
+        assert_eq!(classify_text("https://example.com/path?q=1"), "url");
+        assert_eq!(classify_text("  http://example.com  "), "url");
+        assert_eq!(classify_text("https://"), "text");
+    }
+
+    /// As Chrome writes it: `Version:0.9`, a `SourceURL` after the offsets and
+    /// CRLF line breaks in the document.
+    const CHROME_CF_HTML: &str = concat!(
+        "Version:0.9\r\n",
+        "StartHTML:0000000146\r\n",
+        "EndHTML:0000000237\r\n",
+        "StartFragment:0000000182\r\n",
+        "EndFragment:0000000201\r\n",
+        "SourceURL:https://example.com/page?id=1\r\n",
+        "<html>\r\n<body>\r\n",
+        "<!--StartFragment--><b>Hello</b> w\u{f6}rld<!--EndFragment-->\r\n",
+        "</body>\r\n</html>",
+    );
+
+    /// As Word writes it: `Version:1.0`, extra `StartSelection`/`EndSelection`
+    /// fields and a `file:` source URL.
+    const WORD_CF_HTML: &str = concat!(
+        "Version:1.0\r\n",
+        "StartHTML:0000000210\r\n",
+        "EndHTML:0000000471\r\n",
+        "StartFragment:0000000375\r\n",
+        "EndFragment:0000000435\r\n",
+        "StartSelection:0000000375\r\n",
+        "EndSelection:0000000435\r\n",
+        "SourceURL:file:///C:/Users/me/Documents/Report.docx\r\n",
+        "<html xmlns:o=\"urn:schemas-microsoft-com:office:office\">\r\n",
+        "<head>\r\n<meta name=Generator content=\"Microsoft Word 15\">\r\n</head>\r\n",
+        "<body lang=EN-US>\r\n",
+        "<!--StartFragment--><p class=MsoNormal>Quarterly <b>results</b><o:p></o:p></p>\r\n",
+        "<!--EndFragment-->\r\n",
+        "</body>\r\n</html>",
+    );
+
+    fn header_offset(data: &str, key: &str) -> usize {
+        cf_html_header_value(data, key).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn cf_html_fixture_offsets_point_at_the_fragment() {
+        for data in [CHROME_CF_HTML, WORD_CF_HTML] {
+            let start = header_offset(data, "StartFragment");
+            let end = header_offset(data, "EndFragment");
+            assert!(data[..start].ends_with("<!--StartFragment-->"));
+            assert!(data[end..].starts_with("<!--EndFragment-->"));
+            assert_eq!(header_offset(data, "EndHTML"), data.len());
+        }
+    }
+
+    #[test]
+    fn parse_cf_html_extracts_chrome_fragment() {
+        let parsed = parse_cf_html(CHROME_CF_HTML.as_bytes());
+        // Offsets are byte offsets, so the multi-byte `ö` must not shift the slice.
+        assert_eq!(parsed.html, "<b>Hello</b> w\u{f6}rld");
+        assert_eq!(
+            parsed.source_url.as_deref(),
+            Some("https://example.com/page?id=1")
+        );
+    }
+
+    #[test]
+    fn parse_cf_html_extracts_word_fragment() {
+        let parsed = parse_cf_html(WORD_CF_HTML.as_bytes());
+        assert_eq!(
+            parsed.html,
+            "<p class=MsoNormal>Quarterly <b>results</b><o:p></o:p></p>\r\n"
+        );
+        assert_eq!(
+            parsed.source_url.as_deref(),
+            Some("file:///C:/Users/me/Documents/Report.docx")
+        );
+    }
+
+    #[test]
+    fn parse_cf_html_falls_back_to_body_on_bad_offsets() {
+        let data = "Version:0.9\r\nStartFragment:0000000999\r\nEndFragment:0000000005\r\n<b>x</b>";
+        let parsed = parse_cf_html(data.as_bytes());
+        assert_eq!(parsed.html, "<b>x</b>");
+        assert_eq!(parsed.source_url, None);
+    }
+
+    #[test]
+    fn build_cf_html_round_trips_through_parse() {
+        let parsed = parse_cf_html(&build_cf_html("<i>caf\u{e9}</i>"));
+        assert_eq!(parsed.html, "<i>caf\u{e9}</i>");
+    }
+
+    fn dib_header(width: i32, height: i32, bit_count: u16) -> Vec<u8> {
+        let mut header = Vec::with_capacity(40);
+        header.extend_from_slice(&40u32.to_le_bytes());
+        header.extend_from_slice(&width.to_le_bytes());
+        header.extend_from_slice(&height.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&bit_count.to_le_bytes());
+        header.extend_from_slice(&[0; 24]);
+        header
+    }
+
+    #[test]
+    fn decode_dib_rejects_sizes_that_overflow() {
+        let mut data = dib_header(i32::MAX, i32::MAX, 32);
+        data.extend_from_slice(&[0; 64]);
+        let err = decode_dib(&data).unwrap_err();
+        assert_eq!(err.to_string(), "clipboard DIB buffer too small");
+
+        let data = dib_header(i32::MAX, i32::MIN, 32);
+        assert!(decode_dib(&data).is_err());
+    }
+
+    #[test]
+    fn decode_dib_rejects_truncated_pixels() {
+        let mut data = dib_header(4, 4, 32);
+        data.extend_from_slice(&[0; 4 * 4 * 4 - 1]);
+        assert!(decode_dib(&data).is_err());
+    }
+
+    #[test]
+    fn image_entries_round_trip_through_decode_dib() {
+        // 3x2 keeps the rows unpadded but the image asymmetric, so a flipped or
+        // transposed decode would show.
+        let rgba: Vec<u8> = vec![
+            255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, // top row
+            10, 20, 30, 1, 200, 100, 50, 254, 255, 255, 255, 255, // bottom row
+        ];
+        for (format, dib) in image_entries(&rgba, 3, 2) {
+            let header = parse_bitmap_header(&dib).unwrap();
+            assert_eq!((header.width, header.bit_count), (3, 32), "format {format}");
+            assert_eq!(header.height.unsigned_abs(), 2, "format {format}");
+
+            let (decoded, width, height) = decode_dib(&dib).unwrap();
+            assert_eq!((width, height), (3, 2), "format {format}");
+            assert_eq!(decoded, rgba, "format {format}");
         }
     }
+
+    // Uses the real system clipboard, so it only runs on request:
+    // `cargo test -- --ignored --test-threads=1`.
+    #[test]
+    #[ignore]
+    fn own_writes_are_marked_and_read_back() {
+        write_clipboard_text("wolong round-trip \u{1f600}").unwrap();
+        let sequence = unsafe { GetClipboardSequenceNumber() };
+        assert_eq!(
+            CLIPBOARD_MANAGER.last_sequence.load(Ordering::Relaxed),
+            sequence
+        );
+        let snapshot = read_current_clipboard().unwrap().unwrap();
+        assert_eq!(
+            snapshot.text.as_deref(),
+            Some("wolong round-trip \u{1f600}")
+        );
+
+        clear_clipboard().unwrap();
+        let sequence = unsafe { GetClipboardSequenceNumber() };
+        assert_eq!(
+            CLIPBOARD_MANAGER.last_sequence.load(Ordering::Relaxed),
+            sequence
+        );
+        assert!(read_current_clipboard().unwrap().is_none());
+    }
+
+    #[test]
+    #[ignore]
+    fn restored_items_are_marked_and_read_back() {
+        write_clipboard_content(&ClipboardContent {
+            text: Some("restored".to_string()),
+            html: Some("<b>restored</b>".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let snapshot = read_current_clipboard().unwrap().unwrap();
+        let sequence = unsafe { GetClipboardSequenceNumber() };
+        assert_eq!(
+            CLIPBOARD_MANAGER.last_sequence.load(Ordering::Relaxed),
+            sequence
+        );
+
+        // Restoring a captured snapshot is what `restore_clipboard_item` does.
+        write_clipboard_content(&snapshot.into()).unwrap();
+        let restored = read_current_clipboard().unwrap().unwrap();
+        let sequence = unsafe { GetClipboardSequenceNumber() };
+        assert_eq!(
+            CLIPBOARD_MANAGER.last_sequence.load(Ordering::Relaxed),
+            sequence
+        );
+        assert_eq!(restored.text.as_deref(), Some("restored"));
+        assert_eq!(restored.html.as_deref(), Some("<b>restored</b>"));
+    }
 }
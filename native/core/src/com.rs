@@ -0,0 +1,107 @@
+//! Per-thread COM initialization.
+//!
+//! Threading contract: any function that touches COM calls [`ensure_initialized`]
+//! first, on the thread it runs on. The first call initializes COM for that
+//! thread and it stays initialized until the thread exits, so the tokio
+//! blocking pool and the N-API main thread pay for `CoInitializeEx` once rather
+//! than per call. COM objects must not leave the thread that created them.
+//!
+//! A thread's apartment is fixed by whoever initializes it first. If that was
+//! the host (or an earlier call asking for the other model), COM is still usable
+//! and the existing apartment is reported back; callers that strictly need one
+//! model can check the returned value.
+
+use std::cell::RefCell;
+
+use windows::Win32::{
+    Foundation::RPC_E_CHANGED_MODE,
+    System::Com::{
+        CoInitializeEx, CoUninitialize, COINIT, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED,
+    },
+};
+
+use crate::error::{CoreError, CoreResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Apartment {
+    /// Single-threaded. Needed by shell objects such as `IShellLinkW`,
+    /// `IShellItemImageFactory` and `ShellExecuteW`.
+    Sta,
+    /// Multi-threaded, for free-threaded objects.
+    Mta,
+}
+
+impl Apartment {
+    fn flag(self) -> COINIT {
+        match self {
+            Apartment::Sta => COINIT_APARTMENTTHREADED,
+            Apartment::Mta => COINIT_MULTITHREADED,
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Apartment::Sta => Apartment::Mta,
+            Apartment::Mta => Apartment::Sta,
+        }
+    }
+}
+
+struct ThreadCom {
+    apartment: Apartment,
+    /// Whether our `CoInitializeEx` succeeded and so must be balanced on exit.
+    owned: bool,
+}
+
+impl Drop for ThreadCom {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                CoUninitialize();
+            }
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_COM: RefCell<Option<ThreadCom>> = const { RefCell::new(None) };
+}
+
+/// Makes sure COM is initialized on the current thread and returns the
+/// apartment the thread ended up in.
+pub(crate) fn ensure_initialized(apartment: Apartment) -> CoreResult<Apartment> {
+    THREAD_COM.with(|state| {
+        let mut state = state.borrow_mut();
+        if let Some(com) = state.as_ref() {
+            if com.apartment != apartment {
+                log::debug!(
+                    "COM wanted {apartment:?} but the thread is already {:?}",
+                    com.apartment
+                );
+            }
+            return Ok(com.apartment);
+        }
+
+        let hr = unsafe { CoInitializeEx(None, apartment.flag()) };
+        let com = if hr.is_ok() {
+            ThreadCom {
+                apartment,
+                owned: true,
+            }
+        } else if hr == RPC_E_CHANGED_MODE {
+            log::debug!(
+                "COM wanted {apartment:?} but the host initialized the thread as {:?}",
+                apartment.other()
+            );
+            ThreadCom {
+                apartment: apartment.other(),
+                owned: false,
+            }
+        } else {
+            return Err(CoreError::from_hresult("CoInitializeEx failed", hr));
+        };
+        let current = com.apartment;
+        *state = Some(com);
+        Ok(current)
+    })
+}
@@ -0,0 +1,313 @@
+use windows::{
+    core::Interface,
+    Win32::{
+        Foundation::E_FAIL,
+        Graphics::{
+            Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+            Direct3D11::{
+                D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
+                D3D11_CPU_ACCESS_READ, D3D11_CREATE_DEVICE_FLAG, D3D11_MAPPED_SUBRESOURCE,
+                D3D11_MAP_READ, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+            },
+            Dxgi::{
+                CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory1, IDXGIOutput, IDXGIOutput1,
+                IDXGIOutputDuplication, DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_WAIT_TIMEOUT,
+                DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTPUT_DESC,
+            },
+            Gdi::HMONITOR,
+        },
+    },
+};
+
+use crate::error::{CoreError, CoreResult};
+use crate::monitor::{monitor_bounds, monitor_handles, monitor_scale_factor, DpiAwarenessScope};
+use crate::screenshot::{
+    capture_screen_rect, monitor_identity, recycle_buffer, take_buffer, CaptureOptions, RawCapture,
+};
+
+/// Repeatedly captures one monitor, handing out only frames that changed.
+///
+/// Uses DXGI Desktop Duplication, which copies straight from the compositor and
+/// is far cheaper than `BitBlt` per frame. Where duplication isn't available
+/// (RDP sessions, some VMs, basic display drivers) it falls back to GDI and
+/// compares each frame with the previous one instead.
+///
+/// The D3D objects are tied to the creating thread, so a capturer must stay on it.
+pub struct MonitorCapturer {
+    monitor: HMONITOR,
+    backend: Backend,
+}
+
+enum Backend {
+    /// `None` between losing the duplication and successfully reopening it.
+    Dxgi(Option<Duplication>),
+    Gdi {
+        last_frame: Option<Vec<u8>>,
+    },
+}
+
+impl MonitorCapturer {
+    /// `monitor_index` follows the order of `enumerate_monitors`.
+    pub fn new(monitor_index: usize) -> CoreResult<Self> {
+        let _dpi = DpiAwarenessScope::per_monitor();
+        let monitor = *monitor_handles()?
+            .get(monitor_index)
+            .ok_or_else(|| CoreError::NotFound(format!("monitor {monitor_index} not found")))?;
+
+        let backend = match Duplication::open(monitor) {
+            Ok(duplication) => Backend::Dxgi(Some(duplication)),
+            Err(err) => {
+                log::debug!("desktop duplication unavailable, using GDI: {err}");
+                Backend::Gdi { last_frame: None }
+            }
+        };
+        Ok(Self { monitor, backend })
+    }
+
+    pub fn uses_dxgi(&self) -> bool {
+        matches!(self.backend, Backend::Dxgi(_))
+    }
+
+    /// Waits up to `timeout_ms` for the screen to change and returns the new
+    /// frame, or `None` if nothing changed. The GDI fallback never waits.
+    pub fn next_frame(&mut self, timeout_ms: u32) -> CoreResult<Option<RawCapture>> {
+        let _dpi = DpiAwarenessScope::per_monitor();
+        match &mut self.backend {
+            Backend::Dxgi(slot) => {
+                // Mode changes, UAC prompts and the lock screen invalidate the
+                // duplication; it has to be recreated, possibly more than once
+                // while the secure desktop is up.
+                let Some(duplication) = slot else {
+                    *slot = Duplication::open(self.monitor).ok();
+                    return Ok(None);
+                };
+                match duplication.next_frame(timeout_ms) {
+                    Ok(frame) => Ok(frame),
+                    Err(err) if err.code() == DXGI_ERROR_ACCESS_LOST => {
+                        // An output allows one duplication per device, so the
+                        // old one must be released before opening another.
+                        *slot = None;
+                        *slot = Duplication::open(self.monitor).ok();
+                        Ok(None)
+                    }
+                    Err(err) => Err(CoreError::from_hresult(
+                        "AcquireNextFrame failed",
+                        err.code(),
+                    )),
+                }
+            }
+            Backend::Gdi { last_frame } => {
+                let bounds = monitor_bounds(self.monitor)?;
+                let capture = unsafe {
+                    capture_screen_rect(
+                        bounds.left,
+                        bounds.top,
+                        bounds.right - bounds.left,
+                        bounds.bottom - bounds.top,
+                        &CaptureOptions::default(),
+                    )?
+                };
+                if last_frame.as_deref() == Some(capture.rgba.as_slice()) {
+                    recycle_buffer(capture.rgba);
+                    return Ok(None);
+                }
+                let mut copy = take_buffer(capture.rgba.len());
+                copy.copy_from_slice(&capture.rgba);
+                if let Some(previous) = last_frame.replace(copy) {
+                    recycle_buffer(previous);
+                }
+                Ok(Some(capture))
+            }
+        }
+    }
+}
+
+struct Duplication {
+    monitor: HMONITOR,
+    origin: (i32, i32),
+    context: ID3D11DeviceContext,
+    device: ID3D11Device,
+    duplication: IDXGIOutputDuplication,
+    /// CPU-readable copy target, recreated when the frame size changes.
+    staging: Option<(ID3D11Texture2D, u32, u32)>,
+    frame_acquired: bool,
+}
+
+impl Duplication {
+    fn open(monitor: HMONITOR) -> CoreResult<Self> {
+        let (adapter, output, desc) = find_output(monitor)?;
+
+        let mut device = None;
+        let mut context = None;
+        unsafe {
+            D3D11CreateDevice(
+                &adapter,
+                D3D_DRIVER_TYPE_UNKNOWN,
+                None,
+                D3D11_CREATE_DEVICE_FLAG(0),
+                None,
+                D3D11_SDK_VERSION,
+                Some(&mut device),
+                None,
+                Some(&mut context),
+            )
+            .map_err(|err| CoreError::from_hresult("D3D11CreateDevice failed", err.code()))?;
+        }
+        let (Some(device), Some(context)) = (device, context) else {
+            return Err(CoreError::Other(anyhow::anyhow!(
+                "D3D11CreateDevice returned no device"
+            )));
+        };
+
+        let duplication = unsafe {
+            output
+                .cast::<IDXGIOutput1>()
+                .and_then(|output| output.DuplicateOutput(&device))
+                .map_err(|err| CoreError::from_hresult("DuplicateOutput failed", err.code()))?
+        };
+
+        Ok(Self {
+            monitor,
+            origin: (desc.DesktopCoordinates.left, desc.DesktopCoordinates.top),
+            context,
+            device,
+            duplication,
+            staging: None,
+            frame_acquired: false,
+        })
+    }
+
+    fn next_frame(&mut self, timeout_ms: u32) -> windows::core::Result<Option<RawCapture>> {
+        let mut info = DXGI_OUTDUPL_FRAME_INFO::default();
+        let mut resource = None;
+        match unsafe {
+            self.duplication
+                .AcquireNextFrame(timeout_ms, &mut info, &mut resource)
+        } {
+            Ok(()) => {}
+            Err(err) if err.code() == DXGI_ERROR_WAIT_TIMEOUT => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        self.frame_acquired = true;
+
+        // A zero present time means only the mouse pointer moved.
+        let frame = match resource {
+            Some(resource) if info.LastPresentTime != 0 => resource
+                .cast::<ID3D11Texture2D>()
+                .and_then(|texture| self.read_frame(&texture))
+                .map(Some),
+            _ => Ok(None),
+        };
+
+        unsafe {
+            self.duplication.ReleaseFrame()?;
+        }
+        self.frame_acquired = false;
+        frame
+    }
+
+    fn read_frame(&mut self, texture: &ID3D11Texture2D) -> windows::core::Result<RawCapture> {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut desc) };
+        let (width, height) = (desc.Width, desc.Height);
+        let staging = self.staging_texture(desc)?;
+
+        let mut rgba = take_buffer(width as usize * height as usize * 4);
+        unsafe {
+            self.context.CopyResource(&staging, texture);
+
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            self.context
+                .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+            let row_bytes = width as usize * 4;
+            for (y, row) in rgba.chunks_exact_mut(row_bytes).enumerate() {
+                let source = std::slice::from_raw_parts(
+                    (mapped.pData as *const u8).add(y * mapped.RowPitch as usize),
+                    row_bytes,
+                );
+                row.copy_from_slice(source);
+            }
+            self.context.Unmap(&staging, 0);
+        }
+
+        // Duplicated frames are BGRA with an undefined alpha byte.
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+            pixel[3] = 0xFF;
+        }
+
+        Ok(RawCapture {
+            width,
+            height,
+            origin_x: self.origin.0,
+            origin_y: self.origin.1,
+            rgba,
+            scale_factor: monitor_scale_factor(self.monitor),
+            monitor: monitor_identity(self.monitor),
+        })
+    }
+
+    fn staging_texture(
+        &mut self,
+        frame: D3D11_TEXTURE2D_DESC,
+    ) -> windows::core::Result<ID3D11Texture2D> {
+        if let Some((texture, width, height)) = &self.staging {
+            if (*width, *height) == (frame.Width, frame.Height) {
+                return Ok(texture.clone());
+            }
+        }
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+            MiscFlags: 0,
+            MipLevels: 1,
+            ArraySize: 1,
+            ..frame
+        };
+        let mut texture = None;
+        unsafe {
+            self.device
+                .CreateTexture2D(&desc, None, Some(&mut texture))?;
+        }
+        let texture = texture.ok_or_else(|| windows::core::Error::from(E_FAIL))?;
+        self.staging = Some((texture.clone(), frame.Width, frame.Height));
+        Ok(texture)
+    }
+}
+
+impl Drop for Duplication {
+    fn drop(&mut self) {
+        // A frame still held (after an error mid-copy) blocks other duplication
+        // clients until released; the interfaces themselves release on drop.
+        if self.frame_acquired {
+            unsafe {
+                let _ = self.duplication.ReleaseFrame();
+            }
+        }
+    }
+}
+
+/// Finds the adapter and output that drive `monitor`.
+fn find_output(monitor: HMONITOR) -> CoreResult<(IDXGIAdapter1, IDXGIOutput, DXGI_OUTPUT_DESC)> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }
+        .map_err(|err| CoreError::from_hresult("CreateDXGIFactory1 failed", err.code()))?;
+
+    let mut adapter_index = 0;
+    while let Ok(adapter) = unsafe { factory.EnumAdapters1(adapter_index) } {
+        let mut output_index = 0;
+        while let Ok(output) = unsafe { adapter.EnumOutputs(output_index) } {
+            let mut desc = DXGI_OUTPUT_DESC::default();
+            if unsafe { output.GetDesc(&mut desc) }.is_ok() && desc.Monitor == monitor {
+                return Ok((adapter, output, desc));
+            }
+            output_index += 1;
+        }
+        adapter_index += 1;
+    }
+
+    Err(CoreError::Other(anyhow::anyhow!(
+        "no DXGI output drives the monitor"
+    )))
+}
@@ -1,7 +1,24 @@
-use std::io;
+use std::{
+    any::Any,
+    io,
+    panic::{self, AssertUnwindSafe},
+};
 
 use napi::{Error as NapiError, Status};
 use thiserror::Error;
+use tokio::task::JoinError;
+use windows::{
+    core::{HRESULT, PWSTR},
+    Win32::{
+        Foundation::{
+            GetLastError, ERROR_ACCESS_DENIED, ERROR_CANCELLED, ERROR_FILE_NOT_FOUND,
+            ERROR_INVALID_WINDOW_HANDLE, ERROR_PATH_NOT_FOUND, WIN32_ERROR,
+        },
+        System::Diagnostics::Debug::{
+            FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+        },
+    },
+};
 
 #[derive(Debug, Error)]
 pub enum CoreError {
@@ -14,6 +31,28 @@ pub enum CoreError {
     #[error("n-api error: {0}")]
     Napi(String),
 
+    #[error("hotkey {0} is already registered by another application")]
+    HotkeyAlreadyRegistered(u32),
+
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    AccessDenied(String),
+
+    #[error("clipboard is held open by another application")]
+    ClipboardBusy,
+
+    /// The user dismissed a system prompt, e.g. declined a UAC elevation.
+    #[error("{0}")]
+    Cancelled(String),
+
+    #[error("native code panicked: {0}")]
+    Panic(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -21,20 +60,195 @@ pub enum CoreError {
 pub type CoreResult<T> = Result<T, CoreError>;
 
 impl CoreError {
+    /// Wraps `GetLastError`, e.g. `"OpenClipboard failed (5: Access is denied.)"`.
+    /// Codes JS can act on map to their own variant (see `classify`).
     pub fn from_win32(prefix: &str) -> Self {
-        use windows::Win32::Foundation::GetLastError;
-        let code = unsafe { GetLastError().0 };
-        CoreError::Windows(format!("{prefix} (code {code})"))
+        let error = unsafe { GetLastError() };
+        let code = error.0;
+        let message = match system_message(code) {
+            Some(message) => format!("{prefix} ({code}: {message})"),
+            None => format!("{prefix} (code {code})"),
+        };
+        classify(error.to_hresult(), message)
+    }
+
+    /// Like `from_win32`, for COM calls that report an `HRESULT` instead.
+    pub fn from_hresult(prefix: &str, hr: HRESULT) -> Self {
+        let code = hr.0 as u32;
+        let message = match system_message(code) {
+            Some(message) => format!("{prefix} ({code:#010x}: {message})"),
+            None => format!("{prefix} (hresult {code:#010x})"),
+        };
+        classify(hr, message)
+    }
+
+    /// Stable identifier for the failure, so JS can branch on it without
+    /// matching message text. These are part of the public API; don't rename them.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CoreError::Io(err) => match err.kind() {
+                io::ErrorKind::NotFound => "E_NOT_FOUND",
+                io::ErrorKind::PermissionDenied => "E_ACCESS_DENIED",
+                _ => "E_IO",
+            },
+            CoreError::Windows(_) => "E_WINDOWS",
+            CoreError::Napi(_) => "E_NAPI",
+            CoreError::HotkeyAlreadyRegistered(_) => "E_HOTKEY_IN_USE",
+            CoreError::InvalidArgument(_) => "E_INVALID_ARGUMENT",
+            CoreError::NotFound(_) => "E_NOT_FOUND",
+            CoreError::AccessDenied(_) => "E_ACCESS_DENIED",
+            CoreError::ClipboardBusy => "E_CLIPBOARD_BUSY",
+            CoreError::Cancelled(_) => "E_CANCELLED",
+            CoreError::Panic(_) => "E_PANIC",
+            CoreError::Other(_) => "E_INTERNAL",
+        }
     }
 }
 
+impl From<JoinError> for CoreError {
+    fn from(err: JoinError) -> Self {
+        if err.is_panic() {
+            panic_error(err.into_panic())
+        } else {
+            CoreError::Other(anyhow::anyhow!("background task failed: {err}"))
+        }
+    }
+}
+
+/// Picks the variant for a failed Windows call. Win32 codes arrive wrapped as
+/// `HRESULT_FROM_WIN32`, which also covers COM's `E_ACCESSDENIED`.
+fn classify(hr: HRESULT, message: String) -> CoreError {
+    let is = |code: WIN32_ERROR| hr == code.to_hresult();
+    if is(ERROR_ACCESS_DENIED) {
+        CoreError::AccessDenied(message)
+    } else if is(ERROR_FILE_NOT_FOUND)
+        || is(ERROR_PATH_NOT_FOUND)
+        || is(ERROR_INVALID_WINDOW_HANDLE)
+    {
+        CoreError::NotFound(message)
+    } else if is(ERROR_CANCELLED) {
+        CoreError::Cancelled(message)
+    } else {
+        CoreError::Windows(message)
+    }
+}
+
+/// Runs an N-API entry point, turning a panic into `CoreError::Panic`. A panic
+/// unwinding into N-API would otherwise abort the whole host process.
+pub fn catch_panic<T>(f: impl FnOnce() -> CoreResult<T>) -> CoreResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| Err(panic_error(payload)))
+}
+
+/// Runs the body of an `extern "system"` callback such as a window procedure.
+/// Unwinding out of one aborts the process, so a panic is logged and dropped.
+pub(crate) fn catch_callback_panic(callback: &str, f: impl FnOnce()) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+        log::error!("{callback}: {}", panic_error(payload));
+    }
+}
+
+fn panic_error(payload: Box<dyn Any + Send>) -> CoreError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    CoreError::Panic(message)
+}
+
+/// Logs panics (with their location) through `log` before the default hook
+/// runs, so they reach the JS log sink even when caught.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log::error!("native panic: {info}");
+        default_hook(info);
+    }));
+}
+
+/// The system's description of a Win32 error or `HRESULT`, in the user's language.
+fn system_message(code: u32) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            None,
+            code,
+            0,
+            PWSTR(buffer.as_mut_ptr()),
+            buffer.len() as u32,
+            None,
+        )
+    };
+    let message = String::from_utf16_lossy(&buffer[..len as usize]);
+    let message = message.trim_end();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+/// The reason is `"<code>: <message>"`, e.g. `"E_NOT_FOUND: window '0x1a2b' not found"`.
 impl From<CoreError> for NapiError {
     fn from(value: CoreError) -> Self {
-        match value {
-            CoreError::Io(err) => NapiError::new(Status::GenericFailure, err.to_string()),
-            CoreError::Windows(message) => NapiError::new(Status::GenericFailure, message),
-            CoreError::Napi(message) => NapiError::new(Status::GenericFailure, message),
-            CoreError::Other(err) => NapiError::new(Status::GenericFailure, err.to_string()),
-        }
+        NapiError::new(Status::GenericFailure, format!("{}: {value}", value.code()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catch_panic_converts_panics_to_e_panic() {
+        let err = catch_panic::<()>(|| panic!("boom")).unwrap_err();
+        assert_eq!(err.code(), "E_PANIC");
+        assert!(matches!(&err, CoreError::Panic(message) if message == "boom"));
+
+        let err = catch_panic::<()>(|| panic!("index {} out of range", 3)).unwrap_err();
+        assert!(matches!(&err, CoreError::Panic(message) if message == "index 3 out of range"));
+
+        let napi_err = NapiError::from(catch_panic::<()>(|| panic!("boom")).unwrap_err());
+        assert!(napi_err.reason.starts_with("E_PANIC: "));
+    }
+
+    #[test]
+    fn catch_callback_panic_swallows_panics() {
+        let mut ran = false;
+        catch_callback_panic("test callback", || {
+            ran = true;
+            panic!("boom");
+        });
+        assert!(ran);
+    }
+
+    #[test]
+    fn catch_panic_passes_results_through() {
+        assert_eq!(catch_panic(|| Ok(7)).unwrap(), 7);
+        let err = catch_panic::<()>(|| Err(CoreError::NotFound("x".to_string()))).unwrap_err();
+        assert_eq!(err.code(), "E_NOT_FOUND");
+    }
+
+    #[test]
+    fn windows_errors_map_to_actionable_codes() {
+        let code = |hr: HRESULT| CoreError::from_hresult("call failed", hr).code();
+        assert_eq!(code(ERROR_ACCESS_DENIED.to_hresult()), "E_ACCESS_DENIED");
+        assert_eq!(
+            code(windows::Win32::Foundation::E_ACCESSDENIED),
+            "E_ACCESS_DENIED"
+        );
+        assert_eq!(code(ERROR_FILE_NOT_FOUND.to_hresult()), "E_NOT_FOUND");
+        assert_eq!(code(ERROR_PATH_NOT_FOUND.to_hresult()), "E_NOT_FOUND");
+        assert_eq!(
+            code(ERROR_INVALID_WINDOW_HANDLE.to_hresult()),
+            "E_NOT_FOUND"
+        );
+        assert_eq!(code(ERROR_CANCELLED.to_hresult()), "E_CANCELLED");
+        assert_eq!(code(windows::Win32::Foundation::E_FAIL), "E_WINDOWS");
+    }
+
+    #[test]
+    fn mapped_errors_keep_the_system_message() {
+        let err = CoreError::from_hresult("OpenProcess failed", ERROR_ACCESS_DENIED.to_hresult());
+        assert!(err
+            .to_string()
+            .starts_with("OpenProcess failed (0x80070005"));
     }
 }
@@ -1,122 +1,948 @@
+use crate::com::{self, Apartment};
 use crate::error::{CoreError, CoreResult};
-use crate::utils::{expand_env_vars, wide_string};
-use std::path::Path;
+use crate::utils::{expand_env_vars, string_from_wide, wide_string};
+use base64::{engine::general_purpose, Engine as _};
+use image::{imageops::FilterType, RgbaImage};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 use windows::{
-    core::PCWSTR,
+    core::{PCWSTR, PWSTR},
     Win32::{
+        Foundation::{ERROR_INSUFFICIENT_BUFFER, ERROR_SUCCESS, MAX_PATH, SIZE},
         Graphics::Gdi::{
-            CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, GetDC, ReleaseDC,
-            SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+            CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, GetDIBits, GetObjectW,
+            SelectObject, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HBITMAP,
+            HDC, HGDIOBJ,
+        },
+        Storage::{
+            FileSystem::{FILE_ATTRIBUTE_NORMAL, FILE_FLAGS_AND_ATTRIBUTES},
+            Packaging::Appx::{GetPackagePathByFullName, GetPackagesByPackageFamily},
         },
-        Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES,
         UI::{
-            Shell::{SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_LARGEICON},
+            Controls::{IImageList, ILD_TRANSPARENT},
+            Shell::{
+                IShellItemImageFactory, SHCreateItemFromParsingName, SHGetFileInfoW,
+                SHGetImageList, SHFILEINFOW, SHGFI_FLAGS, SHGFI_ICON, SHGFI_LARGEICON,
+                SHGFI_SYSICONINDEX, SHGFI_USEFILEATTRIBUTES, SHIL_JUMBO, SIIGBF_BIGGERSIZEOK,
+                SIIGBF_THUMBNAILONLY,
+            },
             WindowsAndMessaging::{
-                DestroyIcon, DrawIconEx, GetSystemMetrics, DI_NORMAL, SM_CXICON, SM_CYICON,
+                DestroyIcon, DrawIconEx, GetSystemMetrics, PrivateExtractIconsW, DI_MASK,
+                DI_NORMAL, HICON, SM_CXICON, SM_CYICON,
             },
         },
     },
 };
 
-const ICON_SIZE: i32 = 48;
+pub const ICON_SIZE: i32 = 48;
+const JUMBO_ICON_SIZE: i32 = 256;
+const DEFAULT_ICON_CACHE_CAPACITY: usize = 256;
+
+static ICON_CACHE: Lazy<Mutex<IconCache>> =
+    Lazy::new(|| Mutex::new(IconCache::new(DEFAULT_ICON_CACHE_CAPACITY)));
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct IconCacheKey {
+    path: String,
+    index: Option<i32>,
+    /// Output size; `None` keeps the source resolution.
+    size: Option<u32>,
+    /// Margin kept by `trim_transparent_border`; `None` when not trimming.
+    trim: Option<u32>,
+}
+
+/// An encoded icon together with the resolution it was rendered from.
+#[derive(Clone)]
+pub struct IconData {
+    pub png: Vec<u8>,
+    /// Source image size, before any scaling (after trimming, if requested).
+    pub width: u32,
+    pub height: u32,
+    /// The source was smaller than the output and had to be scaled up.
+    pub was_upscaled: bool,
+}
+
+struct CachedIcon {
+    icon: IconData,
+    /// Source file mtime when the icon was extracted; a change invalidates the entry.
+    modified: Option<SystemTime>,
+    last_used: u64,
+}
+
+/// Least-recently-used cache of encoded icons. Capacities are small, so
+/// eviction just scans for the oldest entry.
+struct IconCache {
+    entries: HashMap<IconCacheKey, CachedIcon>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl IconCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: &IconCacheKey, modified: Option<SystemTime>) -> Option<IconData> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        if entry.modified != modified {
+            self.entries.remove(key);
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some(entry.icon.clone())
+    }
+
+    fn insert(&mut self, key: IconCacheKey, icon: IconData, modified: Option<SystemTime>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.clock += 1;
+        self.entries.insert(
+            key,
+            CachedIcon {
+                icon,
+                modified,
+                last_used: self.clock,
+            },
+        );
+        self.evict_to(self.capacity);
+    }
+
+    fn evict_to(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+}
+
+pub fn clear_icon_cache() {
+    ICON_CACHE.lock().entries.clear();
+}
+
+/// Caps how many encoded icons are kept; 0 disables caching.
+pub fn set_icon_cache_capacity(capacity: usize) {
+    let mut cache = ICON_CACHE.lock();
+    cache.capacity = capacity;
+    cache.evict_to(capacity);
+}
+
+/// Serves `key` from the cache, or runs `extract` and remembers a successful result.
+/// The lock is not held while extracting.
+fn cached_icon(
+    key: IconCacheKey,
+    modified: Option<SystemTime>,
+    extract: impl FnOnce() -> CoreResult<Option<IconData>>,
+) -> CoreResult<Option<IconData>> {
+    if let Some(icon) = ICON_CACHE.lock().get(&key, modified) {
+        return Ok(Some(icon));
+    }
+
+    let icon = extract()?;
+    if let Some(icon) = &icon {
+        ICON_CACHE.lock().insert(key, icon.clone(), modified);
+    }
+    Ok(icon)
+}
+
+/// Splits `"path.exe,3"` into the path and icon index, if one is given.
+fn parse_icon_location(icon_path: &str) -> (&str, Option<i32>) {
+    match icon_path.rsplit_once(',') {
+        Some((path, index)) => match index.trim().parse::<i32>() {
+            Ok(index) => (path.trim(), Some(index)),
+            Err(_) => (icon_path.trim(), None),
+        },
+        None => (icon_path.trim(), None),
+    }
+}
 
 pub fn extract_icon_data(icon_path: &str) -> CoreResult<Option<Vec<u8>>> {
+    extract_icon_sized(icon_path, None, ICON_SIZE as u32)
+}
+
+/// Like `extract_icon_data`, with an explicit icon index (overriding any `,N`
+/// suffix in `icon_path`) and output size.
+pub fn extract_icon_sized(
+    icon_path: &str,
+    icon_index: Option<i32>,
+    size: u32,
+) -> CoreResult<Option<Vec<u8>>> {
+    let icon = extract_icon(icon_path, icon_index, Some(size), None)?;
+    Ok(icon.map(|icon| icon.png))
+}
+
+/// Like `extract_icon_data`, but also reports the source resolution. Without a
+/// `size` the icon is encoded at that resolution instead of being scaled.
+pub fn extract_icon_info(icon_path: &str, size: Option<u32>) -> CoreResult<Option<IconData>> {
+    extract_icon(icon_path, None, size, None)
+}
+
+/// `trim` crops away transparent padding, keeping that many output pixels of
+/// margin around the glyph.
+fn extract_icon(
+    icon_path: &str,
+    icon_index: Option<i32>,
+    size: Option<u32>,
+    trim: Option<u32>,
+) -> CoreResult<Option<IconData>> {
     // Parse icon path (may contain index like "path.exe,0")
-    let path_str = if let Some(comma_pos) = icon_path.find(',') {
-        icon_path[..comma_pos].trim()
-    } else {
-        icon_path.trim()
-    };
+    let (path_str, parsed_index) = parse_icon_location(icon_path);
+    let index = icon_index.or(parsed_index);
+    let size = size.map(|size| size.clamp(1, JUMBO_ICON_SIZE as u32));
 
     let expanded = expand_env_vars(path_str);
     // Convert to PathBuf and normalize to Windows format (use \ instead of /)
     let path_buf = Path::new(&expanded).to_path_buf();
     let normalized = path_buf.to_string_lossy().replace('/', "\\");
 
-    if !Path::new(&normalized).exists() {
+    let Ok(metadata) = fs::metadata(&normalized) else {
         return Ok(None);
+    };
+
+    let key = IconCacheKey {
+        path: normalized.to_lowercase(),
+        index,
+        size,
+        trim,
+    };
+    cached_icon(key, metadata.modified().ok(), || {
+        // An explicit index names a specific resource (e.g. "shell32.dll,21"),
+        // which the shell's per-file icon lookup would ignore.
+        if let Some(index) = index {
+            let requested = size.unwrap_or(JUMBO_ICON_SIZE as u32);
+            if let Some((pixels, width, height)) =
+                resource_icon_pixels(&normalized, index, requested)
+            {
+                return finish_icon(pixels, width, height, size, trim).map(Some);
+            }
+        }
+        let wide_path = wide_string(&normalized);
+        shell_icon(&wide_path, false, size, trim)
+    })
+}
+
+/// Which step of `extract_icon_with_fallback` produced the icon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IconSource {
+    Primary,
+    Fallback,
+    Generic,
+}
+
+impl IconSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IconSource::Primary => "primary",
+            IconSource::Fallback => "fallback",
+            IconSource::Generic => "generic",
+        }
+    }
+}
+
+/// Tries `icon_path`, then the icon of `fallback_exe`, then the shell's generic
+/// application icon, so a shortcut whose icon resource is gone still gets a tile.
+pub fn extract_icon_with_fallback(
+    icon_path: &str,
+    fallback_exe: Option<&str>,
+    size: u32,
+) -> CoreResult<Option<(Vec<u8>, IconSource)>> {
+    let candidates = [Some(icon_path), fallback_exe]
+        .into_iter()
+        .zip([IconSource::Primary, IconSource::Fallback]);
+    for (path, source) in candidates {
+        let Some(path) = path.filter(|path| !path.trim().is_empty()) else {
+            continue;
+        };
+        match extract_icon_sized(path, None, size) {
+            Ok(Some(png)) => return Ok(Some((png, source))),
+            Ok(None) => {}
+            Err(err) => log::debug!("{} icon {path:?} failed: {err}", source.as_str()),
+        }
+    }
+
+    // Any `.exe` without an embedded icon is shown with this one.
+    let generic = extract_extension_icon("exe", size)?;
+    Ok(generic.map(|png| (png, IconSource::Generic)))
+}
+
+/// The icon as a `data:image/png;base64,...` URL, ready for an `<img src>`.
+pub fn extract_icon_data_url(icon_path: &str, size: u32) -> CoreResult<Option<String>> {
+    let png = extract_icon_sized(icon_path, None, size)?;
+    Ok(png.map(|png| {
+        format!(
+            "data:image/png;base64,{}",
+            general_purpose::STANDARD.encode(png)
+        )
+    }))
+}
+
+/// The icon's most prominent color as `0xAARRGGBB` (alpha always `0xFF`), e.g.
+/// for tinting a tile background. Transparent and near-white/near-black pixels
+/// are ignored; `None` when nothing else is left, as with monochrome glyphs.
+pub fn icon_dominant_color(icon_path: &str) -> CoreResult<Option<u32>> {
+    // The default size is usually cached already and plenty for a histogram.
+    let Some(png) = extract_icon_sized(icon_path, None, ICON_SIZE as u32)? else {
+        return Ok(None);
+    };
+    let image = image::load_from_memory(&png)
+        .map_err(|err| CoreError::Other(anyhow::anyhow!("decode icon failed: {err}")))?
+        .into_rgba8();
+    Ok(dominant_color(image.as_raw()))
+}
+
+/// Buckets pixels by their top 4 bits per channel, weighting vivid colors
+/// above greys, and averages the pixels of the heaviest bucket.
+fn dominant_color(rgba: &[u8]) -> Option<u32> {
+    #[derive(Clone, Copy, Default)]
+    struct Bucket {
+        weight: u32,
+        sums: [u32; 3],
+        count: u32,
+    }
+
+    let mut buckets = vec![Bucket::default(); 1 << 12];
+    for pixel in rgba.chunks_exact(4) {
+        let (r, g, b, a) = (pixel[0], pixel[1], pixel[2], pixel[3]);
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        if a < 128 || min > 230 || max < 25 {
+            continue;
+        }
+        let index = (usize::from(r >> 4) << 8) | (usize::from(g >> 4) << 4) | usize::from(b >> 4);
+        let bucket = &mut buckets[index];
+        // Chroma plus a floor, so a grey-only icon still yields its grey.
+        bucket.weight += u32::from(max - min) + 16;
+        bucket.count += 1;
+        for (sum, channel) in bucket.sums.iter_mut().zip([r, g, b]) {
+            *sum += u32::from(channel);
+        }
     }
 
+    let bucket = buckets
+        .iter()
+        .filter(|bucket| bucket.count > 0)
+        .max_by_key(|bucket| bucket.weight)?;
+    let [r, g, b] = bucket.sums.map(|sum| sum / bucket.count);
+    Some(0xFF00_0000 | (r << 16) | (g << 8) | b)
+}
+
+/// Loads the tile logo of an installed UWP/MSIX package, scaled to `size`.
+///
+/// The logo is whichever of `Square44x44Logo`/`Square150x150Logo` suits the
+/// size best, using the on-disk asset variant (`.scale-200`, `.targetsize-48`,
+/// ...) closest to the requested size. `None` when the package or a usable
+/// asset can't be found.
+pub fn extract_uwp_logo(package_family_name: &str, size: u32) -> CoreResult<Option<Vec<u8>>> {
+    let size = size.clamp(1, JUMBO_ICON_SIZE as u32);
+    let Some(install_path) = package_install_path(package_family_name.trim()) else {
+        return Ok(None);
+    };
+    let Ok(manifest) = fs::read_to_string(install_path.join("AppxManifest.xml")) else {
+        return Ok(None);
+    };
+
+    let preferred: &[(&str, u32)] = if size <= 96 {
+        &[("Square44x44Logo", 44), ("Square150x150Logo", 150)]
+    } else {
+        &[("Square150x150Logo", 150), ("Square44x44Logo", 44)]
+    };
+    let asset = preferred.iter().find_map(|&(attribute, base_size)| {
+        let logo = manifest_attribute(&manifest, attribute)?;
+        closest_logo_asset(&install_path.join(logo.replace('/', "\\")), base_size, size)
+    });
+    let Some(asset) = asset else {
+        return Ok(None);
+    };
+
+    let Ok(image) = image::open(&asset) else {
+        return Ok(None);
+    };
+    let image = image.into_rgba8();
+    let (width, height) = image.dimensions();
+    let icon = finish_icon(
+        image.into_raw(),
+        width as i32,
+        height as i32,
+        Some(size),
+        None,
+    )?;
+    Ok(Some(icon.png))
+}
+
+/// Install directory of the first installed package in a package family.
+fn package_install_path(family_name: &str) -> Option<PathBuf> {
+    let family = wide_string(family_name);
     unsafe {
-        let wide_path = wide_string(&normalized);
-        let mut file_info = std::mem::zeroed::<SHFILEINFOW>();
-
-        let result = SHGetFileInfoW(
-            PCWSTR(wide_path.as_ptr()),
-            FILE_FLAGS_AND_ATTRIBUTES(0),
-            Some(&mut file_info),
-            std::mem::size_of::<SHFILEINFOW>() as u32,
-            SHGFI_ICON | SHGFI_LARGEICON,
+        let mut count = 0u32;
+        let mut buffer_length = 0u32;
+        let probe = GetPackagesByPackageFamily(
+            PCWSTR(family.as_ptr()),
+            &mut count,
+            None,
+            &mut buffer_length,
+            PWSTR::null(),
         );
+        if probe != ERROR_INSUFFICIENT_BUFFER || count == 0 {
+            return None;
+        }
 
-        if result == 0 || file_info.hIcon.is_invalid() {
-            return Ok(None);
+        let mut names = vec![PWSTR::null(); count as usize];
+        let mut buffer = vec![0u16; buffer_length as usize];
+        let result = GetPackagesByPackageFamily(
+            PCWSTR(family.as_ptr()),
+            &mut count,
+            Some(names.as_mut_ptr()),
+            &mut buffer_length,
+            PWSTR(buffer.as_mut_ptr()),
+        );
+        if result != ERROR_SUCCESS || count == 0 {
+            return None;
         }
+        let full_name = PCWSTR(names[0].0);
 
-        let hicon = file_info.hIcon;
-        let hdc = GetDC(None);
-        if hdc.is_invalid() {
-            let _ = DestroyIcon(hicon);
-            return Ok(None);
+        let mut path_length = 0u32;
+        let probe = GetPackagePathByFullName(full_name, &mut path_length, PWSTR::null());
+        if probe != ERROR_INSUFFICIENT_BUFFER {
+            return None;
+        }
+        let mut path = vec![0u16; path_length as usize];
+        if GetPackagePathByFullName(full_name, &mut path_length, PWSTR(path.as_mut_ptr()))
+            != ERROR_SUCCESS
+        {
+            return None;
         }
+        string_from_wide(&path).map(PathBuf::from)
+    }
+}
 
-        let icon_width = GetSystemMetrics(SM_CXICON);
-        let icon_height = GetSystemMetrics(SM_CYICON);
+/// Reads `name="value"` from the manifest; good enough for the logo attributes,
+/// which only appear on `VisualElements`.
+fn manifest_attribute<'a>(manifest: &'a str, name: &str) -> Option<&'a str> {
+    let start = manifest.find(&format!("{name}=\""))? + name.len() + 2;
+    let len = manifest[start..].find('"')?;
+    Some(&manifest[start..start + len]).filter(|value| !value.is_empty())
+}
 
-        // Create bitmap
-        let bmi = BITMAPINFO {
-            bmiHeader: BITMAPINFOHEADER {
-                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
-                biWidth: icon_width,
-                biHeight: -icon_height, // Negative for top-down DIB
-                biPlanes: 1,
-                biBitCount: 32,
-                biCompression: BI_RGB.0,
-                biSizeImage: 0,
-                biXPelsPerMeter: 0,
-                biYPelsPerMeter: 0,
-                biClrUsed: 0,
-                biClrImportant: 0,
-            },
-            bmiColors: [std::mem::zeroed(); 1],
+/// Picks the variant of `logo` (e.g. `Assets\Logo.png`) whose pixel size is
+/// the smallest one >= `size`, or the largest available otherwise.
+fn closest_logo_asset(logo: &Path, base_size: u32, size: u32) -> Option<PathBuf> {
+    let stem = logo.file_stem()?.to_string_lossy().to_lowercase();
+    let extension = logo.extension()?.to_string_lossy().to_lowercase();
+    let prefix = format!("{stem}.");
+
+    let candidates = fs::read_dir(logo.parent()?).ok()?.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        let qualifiers = if name == format!("{stem}.{extension}") {
+            ""
+        } else {
+            name.strip_prefix(&prefix)?
+                .strip_suffix(&format!(".{extension}"))?
         };
+        // High-contrast variants look wrong in a normal launcher.
+        if qualifiers.contains("contrast-") {
+            return None;
+        }
+        let pixels = asset_pixel_size(qualifiers, base_size);
+        Some((pixels, path))
+    });
 
-        let mut bits_ptr: *mut std::ffi::c_void = std::ptr::null_mut();
-        let hbitmap = match CreateDIBSection(
-            hdc,
-            &bmi,
-            DIB_RGB_COLORS,
-            &mut bits_ptr,
-            None,
-            0,
-        ) {
-            Ok(bitmap) => bitmap,
-            Err(_) => {
-                ReleaseDC(None, hdc);
-                let _ = DestroyIcon(hicon);
-                return Ok(None);
+    candidates
+        .min_by_key(|(pixels, _)| {
+            if *pixels >= size {
+                (0, *pixels)
+            } else {
+                (1, u32::MAX - pixels)
             }
-        };
+        })
+        .map(|(_, path)| path)
+}
 
-        if hbitmap.is_invalid() || bits_ptr.is_null() {
-            ReleaseDC(None, hdc);
-            let _ = DestroyIcon(hicon);
-            return Ok(None);
+/// Effective pixel size of an asset from qualifiers like `scale-200` or
+/// `targetsize-48_altform-unplated`.
+fn asset_pixel_size(qualifiers: &str, base_size: u32) -> u32 {
+    for qualifier in qualifiers.split(['_', '.']) {
+        if let Some(target) = qualifier.strip_prefix("targetsize-") {
+            if let Ok(target) = target.parse::<u32>() {
+                return target;
+            }
         }
+        if let Some(scale) = qualifier.strip_prefix("scale-") {
+            if let Ok(scale) = scale.parse::<u32>() {
+                return base_size * scale / 100;
+            }
+        }
+    }
+    base_size
+}
 
-        let mem_dc = CreateCompatibleDC(hdc);
-        if mem_dc.is_invalid() {
-            let _ = DeleteObject(hbitmap);
-            ReleaseDC(None, hdc);
-            let _ = DestroyIcon(hicon);
+pub struct IconJob {
+    pub icon_path: String,
+    pub icon_index: Option<i32>,
+    pub size: u32,
+    /// Crop transparent padding, keeping this many pixels of margin.
+    pub trim: Option<u32>,
+}
+
+/// Upper bound on worker threads used by `extract_icon_batch`.
+const MAX_ICON_WORKERS: usize = 4;
+
+/// Extracts many icons at once, spreading the work over a few threads.
+/// Results line up with `jobs` by index.
+pub fn extract_icon_batch(jobs: &[IconJob]) -> Vec<CoreResult<Option<Vec<u8>>>> {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(MAX_ICON_WORKERS);
+    let chunk_size = jobs.len().div_ceil(workers).max(1);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let handle = scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|job| {
+                            let icon = extract_icon(
+                                &job.icon_path,
+                                job.icon_index,
+                                Some(job.size),
+                                job.trim,
+                            )?;
+                            Ok(icon.map(|icon| icon.png))
+                        })
+                        .collect::<Vec<_>>()
+                });
+                (chunk.len(), handle)
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|(len, handle)| {
+                // Keep the output aligned with `jobs` even if a worker dies.
+                handle.join().unwrap_or_else(|_| {
+                    (0..len)
+                        .map(|_| Err(CoreError::Panic("icon worker panicked".to_string())))
+                        .collect()
+                })
+            })
+            .collect()
+    })
+}
+
+/// Shell thumbnail of a file (photo preview, video frame, ...) as PNG, fitted
+/// within `size` x `size` with its aspect ratio kept. `None` when the shell has
+/// no thumbnail for the file, so callers can fall back to its icon.
+pub fn extract_thumbnail(path: &str, size: u32) -> CoreResult<Option<Vec<u8>>> {
+    let size = size.clamp(1, 1024);
+    let normalized = expand_env_vars(path.trim()).replace('/', "\\");
+    let Ok(metadata) = fs::metadata(&normalized) else {
+        return Ok(None);
+    };
+
+    let key = IconCacheKey {
+        path: format!("thumbnail:{}", normalized.to_lowercase()),
+        index: None,
+        size: Some(size),
+        trim: None,
+    };
+    let thumbnail = cached_icon(key, metadata.modified().ok(), || {
+        com::ensure_initialized(Apartment::Sta)?;
+        let Some((pixels, width, height)) = thumbnail_pixels(&normalized, size) else {
             return Ok(None);
+        };
+
+        // SIIGBF_BIGGERSIZEOK may hand back a larger cached thumbnail.
+        let scale = (size as f64 / width.max(height) as f64).min(1.0);
+        let target_width = ((width as f64 * scale).round() as u32).max(1);
+        let target_height = ((height as f64 * scale).round() as u32).max(1);
+        let pixels = if (target_width, target_height) != (width, height) {
+            resize_image(pixels, width, height, target_width, target_height)?
+        } else {
+            pixels
+        };
+        let png = encode_as_png(&pixels, target_width as usize, target_height as usize)?;
+        Ok(Some(IconData {
+            png,
+            width,
+            height,
+            was_upscaled: false,
+        }))
+    })?;
+    Ok(thumbnail.map(|thumbnail| thumbnail.png))
+}
+
+fn thumbnail_pixels(path: &str, size: u32) -> Option<(Vec<u8>, u32, u32)> {
+    let wide_path = wide_string(path);
+    unsafe {
+        let factory: IShellItemImageFactory =
+            SHCreateItemFromParsingName(PCWSTR(wide_path.as_ptr()), None).ok()?;
+        let requested = SIZE {
+            cx: size as i32,
+            cy: size as i32,
+        };
+        let bitmap = factory
+            .GetImage(requested, SIIGBF_THUMBNAILONLY | SIIGBF_BIGGERSIZEOK)
+            .map(OwnedBitmap)
+            .ok()?;
+        bitmap_pixels(bitmap.0)
+    }
+}
+
+/// Copies a 32-bit shell bitmap out as straight-alpha RGBA.
+unsafe fn bitmap_pixels(hbitmap: HBITMAP) -> Option<(Vec<u8>, u32, u32)> {
+    let mut bitmap = BITMAP::default();
+    if GetObjectW(
+        hbitmap,
+        std::mem::size_of::<BITMAP>() as i32,
+        Some(&mut bitmap as *mut BITMAP as *mut std::ffi::c_void),
+    ) == 0
+        || bitmap.bmWidth <= 0
+        || bitmap.bmHeight == 0
+    {
+        return None;
+    }
+    let width = bitmap.bmWidth;
+    let height = bitmap.bmHeight.abs();
+
+    let mut bmi = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            biHeight: -height, // Negative for top-down DIB
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut buffer = vec![0u8; (width * height * 4) as usize];
+
+    let hdc = MemoryDc::new()?;
+    let lines = GetDIBits(
+        hdc.0,
+        hbitmap,
+        0,
+        height as u32,
+        Some(buffer.as_mut_ptr() as *mut std::ffi::c_void),
+        &mut bmi,
+        DIB_RGB_COLORS,
+    );
+    if lines == 0 {
+        return None;
+    }
+
+    if buffer.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        // Opaque formats such as JPEG leave the alpha byte unset.
+        for pixel in buffer.chunks_exact_mut(4) {
+            pixel[3] = 0xFF;
         }
+    } else {
+        unpremultiply(&mut buffer);
+    }
+    for chunk in buffer.chunks_exact_mut(4) {
+        chunk.swap(0, 2);
+    }
+
+    Some((buffer, width as u32, height as u32))
+}
+
+/// Renders the generic shell icon for files with `extension` (e.g. `"pdf"` or `".docx"`).
+pub fn extract_extension_icon(extension: &str, size: u32) -> CoreResult<Option<Vec<u8>>> {
+    let extension = extension.trim().trim_start_matches('.');
+    if extension.is_empty() || extension.contains(['\\', '/']) {
+        return Ok(None);
+    }
+    let size = size.clamp(1, JUMBO_ICON_SIZE as u32);
+
+    // With SHGFI_USEFILEATTRIBUTES the shell never touches the disk, so a
+    // made-up file name is enough to look up the association.
+    let key = IconCacheKey {
+        path: format!("*.{}", extension.to_lowercase()),
+        index: None,
+        size: Some(size),
+        trim: None,
+    };
+    let icon = cached_icon(key, None, || {
+        let wide_path = wide_string(&format!("x.{extension}"));
+        shell_icon(&wide_path, true, Some(size), None)
+    })?;
+    Ok(icon.map(|icon| icon.png))
+}
+
+fn shell_icon(
+    wide_path: &[u16],
+    attributes_only: bool,
+    size: Option<u32>,
+    trim: Option<u32>,
+) -> CoreResult<Option<IconData>> {
+    // Prefer the 256px jumbo icon; SHGFI_LARGEICON tops out at 32px and looks
+    // blurry once scaled up.
+    let icon = jumbo_icon_pixels(wide_path, attributes_only)
+        .or_else(|| large_icon_pixels(wide_path, attributes_only));
+    let Some((pixels, width, height)) = icon else {
+        return Ok(None);
+    };
+    finish_icon(pixels, width, height, size, trim).map(Some)
+}
+
+/// Scales rendered RGBA pixels to `size` x `size` (or leaves them as they are
+/// without a size) and encodes them as PNG, first cropping transparent padding
+/// when `trim` is set.
+fn finish_icon(
+    pixels: Vec<u8>,
+    width: i32,
+    height: i32,
+    size: Option<u32>,
+    trim: Option<u32>,
+) -> CoreResult<IconData> {
+    let (width, height) = (width as u32, height as u32);
+    let (pixels, width, height) = match trim
+        .and_then(|margin| trim_transparent_border(&pixels, width, height, size, margin))
+    {
+        Some(trimmed) => trimmed,
+        None => (pixels, width, height),
+    };
+    let (out_width, out_height) = size.map_or((width, height), |size| (size, size));
+    let resized = if (out_width, out_height) != (width, height) {
+        resize_image(pixels, width, height, out_width, out_height)?
+    } else {
+        pixels
+    };
+
+    // Convert to PNG
+    let png = encode_as_png(&resized, out_width as usize, out_height as usize)?;
+    Ok(IconData {
+        png,
+        width,
+        height,
+        was_upscaled: out_width > width || out_height > height,
+    })
+}
+
+/// Pulls the icon at `index` straight out of an EXE, DLL or ICO, letting the
+/// loader pick the resource image closest to `size`.
+fn resource_icon_pixels(path: &str, index: i32, size: u32) -> Option<(Vec<u8>, i32, i32)> {
+    let wide_path = wide_string(path);
+    let mut file_name = [0u16; MAX_PATH as usize];
+    file_name
+        .get_mut(..wide_path.len())?
+        .copy_from_slice(&wide_path);
+
+    let size = size as i32;
+    let mut icons = [HICON::default()];
+    unsafe {
+        let count = PrivateExtractIconsW(&file_name, index, size, size, Some(&mut icons), None, 0);
+        if count == 0 || count == u32::MAX || icons[0].is_invalid() {
+            return None;
+        }
+
+        let pixels = render_icon(icons[0], size, size);
+        let _ = DestroyIcon(icons[0]);
+        pixels.map(|pixels| (pixels, size, size))
+    }
+}
+
+/// `SHGetFileInfoW` for a real path, or for a name whose extension is all that
+/// matters when `attributes_only` is set.
+unsafe fn shell_file_info(
+    wide_path: &[u16],
+    attributes_only: bool,
+    flags: SHGFI_FLAGS,
+) -> Option<SHFILEINFOW> {
+    let (attributes, flags) = if attributes_only {
+        (FILE_ATTRIBUTE_NORMAL, flags | SHGFI_USEFILEATTRIBUTES)
+    } else {
+        (FILE_FLAGS_AND_ATTRIBUTES(0), flags)
+    };
+
+    // SHGetFileInfoW needs COM for icon overlays and shell extensions.
+    com::ensure_initialized(Apartment::Sta).ok()?;
+    let mut file_info = std::mem::zeroed::<SHFILEINFOW>();
+    let result = SHGetFileInfoW(
+        PCWSTR(wide_path.as_ptr()),
+        attributes,
+        Some(&mut file_info),
+        std::mem::size_of::<SHFILEINFOW>() as u32,
+        flags,
+    );
+    (result != 0).then_some(file_info)
+}
+
+/// Fetches the file's icon from the system jumbo image list, rendered at 256px.
+fn jumbo_icon_pixels(wide_path: &[u16], attributes_only: bool) -> Option<(Vec<u8>, i32, i32)> {
+    unsafe {
+        let file_info = shell_file_info(wide_path, attributes_only, SHGFI_SYSICONINDEX)?;
+        let image_list: IImageList = SHGetImageList(SHIL_JUMBO as i32).ok()?;
+        let hicon = image_list
+            .GetIcon(file_info.iIcon, ILD_TRANSPARENT.0)
+            .ok()
+            .filter(|hicon| !hicon.is_invalid())?;
+
+        let pixels = render_icon(hicon, JUMBO_ICON_SIZE, JUMBO_ICON_SIZE);
+        let _ = DestroyIcon(hicon);
+        let pixels = pixels?;
+
+        // Files without a large icon resource come back as a small icon in the
+        // top-left corner of the jumbo canvas; the regular path does better there.
+        if !has_pixels_outside(&pixels, JUMBO_ICON_SIZE as usize, ICON_SIZE as usize) {
+            return None;
+        }
+        Some((pixels, JUMBO_ICON_SIZE, JUMBO_ICON_SIZE))
+    }
+}
+
+fn large_icon_pixels(wide_path: &[u16], attributes_only: bool) -> Option<(Vec<u8>, i32, i32)> {
+    unsafe {
+        let file_info = shell_file_info(wide_path, attributes_only, SHGFI_ICON | SHGFI_LARGEICON)?;
+        if file_info.hIcon.is_invalid() {
+            return None;
+        }
+
+        let hicon = file_info.hIcon;
+        let icon_width = GetSystemMetrics(SM_CXICON);
+        let icon_height = GetSystemMetrics(SM_CYICON);
+        let pixels = render_icon(hicon, icon_width, icon_height);
+        let _ = DestroyIcon(hicon);
+        pixels.map(|pixels| (pixels, icon_width, icon_height))
+    }
+}
+
+/// A memory DC owned by a single extraction. Each call creates its own rather
+/// than borrowing the shared screen DC, so concurrent extractions never touch
+/// the same GDI state.
+struct MemoryDc(HDC);
+
+impl MemoryDc {
+    unsafe fn new() -> Option<Self> {
+        let hdc = CreateCompatibleDC(None);
+        (!hdc.is_invalid()).then_some(Self(hdc))
+    }
+}
+
+impl Drop for MemoryDc {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DeleteDC(self.0);
+        }
+    }
+}
+
+struct OwnedBitmap(HBITMAP);
+
+impl Drop for OwnedBitmap {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DeleteObject(self.0);
+        }
+    }
+}
+
+/// Restores a DC's previous object when dropped, so the bitmap can be deleted.
+struct SelectedObject {
+    hdc: HDC,
+    previous: HGDIOBJ,
+}
+
+impl SelectedObject {
+    unsafe fn new(hdc: &MemoryDc, bitmap: &OwnedBitmap) -> Self {
+        Self {
+            hdc: hdc.0,
+            previous: SelectObject(hdc.0, bitmap.0),
+        }
+    }
+}
+
+impl Drop for SelectedObject {
+    fn drop(&mut self) {
+        unsafe {
+            SelectObject(self.hdc, self.previous);
+        }
+    }
+}
+
+/// Draws `hicon` into a 32-bit top-down DIB and returns its pixels as RGBA.
+unsafe fn render_icon(hicon: HICON, icon_width: i32, icon_height: i32) -> Option<Vec<u8>> {
+    if icon_width <= 0 || icon_height <= 0 {
+        return None;
+    }
+    let mem_dc = MemoryDc::new()?;
+
+    // Create bitmap
+    let bmi = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: icon_width,
+            biHeight: -icon_height, // Negative for top-down DIB
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        },
+        bmiColors: [std::mem::zeroed(); 1],
+    };
 
-        let _old_bitmap = SelectObject(mem_dc, hbitmap);
+    let mut bits_ptr: *mut std::ffi::c_void = std::ptr::null_mut();
+    let hbitmap = CreateDIBSection(mem_dc.0, &bmi, DIB_RGB_COLORS, &mut bits_ptr, None, 0).ok()?;
+    if hbitmap.is_invalid() {
+        return None;
+    }
+    let bitmap = OwnedBitmap(hbitmap);
+    if bits_ptr.is_null() {
+        return None;
+    }
+
+    let _selected = SelectedObject::new(&mem_dc, &bitmap);
+
+    let stride = icon_width * 4; // 32 bits per pixel (BGRA)
+    let size = (stride * icon_height) as usize;
+    let bits = std::slice::from_raw_parts_mut(bits_ptr as *mut u8, size);
+
+    // Draw onto a fully transparent canvas; DrawIconEx alpha-blends 32-bit icons
+    // into whatever is already there.
+    bits.fill(0);
+    let _ = DrawIconEx(
+        mem_dc.0,
+        0,
+        0,
+        hicon,
+        icon_width,
+        icon_height,
+        0,
+        None,
+        DI_NORMAL,
+    );
+    let mut buffer = bits.to_vec();
 
-        // Draw icon to bitmap
+    if buffer.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        // No alpha channel (legacy 24-bit or masked icon): recover transparency
+        // from the AND mask, which DI_MASK draws black where the icon is opaque.
+        bits.fill(0xFF);
         let _ = DrawIconEx(
-            mem_dc,
+            mem_dc.0,
             0,
             0,
             hicon,
@@ -124,72 +950,117 @@ pub fn extract_icon_data(icon_path: &str) -> CoreResult<Option<Vec<u8>>> {
             icon_height,
             0,
             None,
-            DI_NORMAL,
+            DI_MASK,
         );
+        apply_mask_alpha(&mut buffer, bits);
+    } else {
+        // Blending onto black leaves colors premultiplied by alpha.
+        unpremultiply(&mut buffer);
+    }
 
-        // Read bitmap data
-        let stride = icon_width * 4; // 32 bits per pixel (BGRA)
-        let size = (stride * icon_height) as usize;
-        let mut buffer = vec![0u8; size];
-        std::ptr::copy_nonoverlapping(bits_ptr as *const u8, buffer.as_mut_ptr(), size);
-
-        // Convert BGRA to RGBA
-        for chunk in buffer.chunks_exact_mut(4) {
-            chunk.swap(0, 2); // Swap B and R
-        }
+    // Convert BGRA to RGBA
+    for chunk in buffer.chunks_exact_mut(4) {
+        chunk.swap(0, 2); // Swap B and R
+    }
 
-        // Cleanup
-        SelectObject(mem_dc, _old_bitmap);
-        let _ = DeleteObject(hbitmap);
-        let _ = DeleteDC(mem_dc);
-        ReleaseDC(None, hdc);
-        let _ = DestroyIcon(hicon);
+    Some(buffer)
+}
 
-        // Resize to ICON_SIZE if needed
-        let resized = if icon_width != ICON_SIZE || icon_height != ICON_SIZE {
-            resize_image(
-                &buffer,
-                icon_width as usize,
-                icon_height as usize,
-                ICON_SIZE as usize,
-                ICON_SIZE as usize,
-            )?
+/// Sets alpha from a drawn AND mask: black mask pixels are opaque, white ones transparent.
+fn apply_mask_alpha(bgra: &mut [u8], mask: &[u8]) {
+    for (pixel, mask) in bgra.chunks_exact_mut(4).zip(mask.chunks_exact(4)) {
+        if mask[0] == 0 {
+            pixel[3] = 0xFF;
         } else {
-            buffer
-        };
-
-        // Convert to PNG
-        let png_data = encode_as_png(&resized, ICON_SIZE as usize, ICON_SIZE as usize)?;
-        Ok(Some(png_data))
+            pixel.fill(0);
+        }
     }
 }
 
-fn resize_image(
-    data: &[u8],
-    src_width: usize,
-    src_height: usize,
-    dst_width: usize,
-    dst_height: usize,
-) -> CoreResult<Vec<u8>> {
-    let mut output = vec![0u8; dst_width * dst_height * 4];
-
-    for y in 0..dst_height {
-        for x in 0..dst_width {
-            let src_x = (x * src_width) / dst_width;
-            let src_y = (y * src_height) / dst_height;
-            let src_idx = (src_y * src_width + src_x) * 4;
-            let dst_idx = (y * dst_width + x) * 4;
-
-            if src_idx + 3 < data.len() && dst_idx + 3 < output.len() {
-                output[dst_idx] = data[src_idx];
-                output[dst_idx + 1] = data[src_idx + 1];
-                output[dst_idx + 2] = data[src_idx + 2];
-                output[dst_idx + 3] = data[src_idx + 3];
+/// Converts premultiplied colors back to straight alpha in place.
+fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = u16::from(pixel[3]);
+        if alpha == 0 {
+            pixel[..3].fill(0);
+        } else if alpha < 255 {
+            for channel in &mut pixel[..3] {
+                *channel = ((u16::from(*channel) * 255 + alpha / 2) / alpha).min(255) as u8;
             }
         }
     }
+}
 
-    Ok(output)
+/// Whether any visible pixel of a square RGBA image lies outside its top-left
+/// `inner` x `inner` corner.
+fn has_pixels_outside(rgba: &[u8], size: usize, inner: usize) -> bool {
+    rgba.chunks_exact(4)
+        .enumerate()
+        .any(|(index, pixel)| pixel[3] != 0 && (index % size >= inner || index / size >= inner))
+}
+
+/// Crops a padded icon to a square around its visible pixels, leaving `margin`
+/// pixels around the glyph once the result is scaled to `size`. `None` when
+/// there is no padding to remove, e.g. for fully opaque icons.
+fn trim_transparent_border(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    size: Option<u32>,
+    margin: u32,
+) -> Option<(Vec<u8>, u32, u32)> {
+    let (w, h) = (width as usize, height as usize);
+    let (mut left, mut top, mut right, mut bottom) = (w, h, 0, 0);
+    for (index, pixel) in rgba.chunks_exact(4).enumerate() {
+        if pixel[3] != 0 {
+            let (x, y) = (index % w, index / w);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+    if left >= right {
+        return None;
+    }
+
+    let glyph = (right - left).max(bottom - top);
+    // The margin is given in output pixels; convert it to source pixels for the
+    // output size the crop will be scaled to.
+    let source_margin = match size {
+        Some(size) => {
+            let margin = (margin as usize).min((size as usize - 1) / 2);
+            (margin * glyph).div_ceil(size as usize - 2 * margin)
+        }
+        None => margin as usize,
+    };
+    let side = (glyph + 2 * source_margin).min(w.min(h));
+    if side >= w && side >= h {
+        return None;
+    }
+
+    // Center the square on the glyph, shifted back inside the image if needed.
+    let x = ((left + right).saturating_sub(side) / 2).min(w - side);
+    let y = ((top + bottom).saturating_sub(side) / 2).min(h - side);
+    let mut cropped = Vec::with_capacity(side * side * 4);
+    for row in y..y + side {
+        let start = (row * w + x) * 4;
+        cropped.extend_from_slice(&rgba[start..start + side * 4]);
+    }
+    Some((cropped, side as u32, side as u32))
+}
+
+fn resize_image(
+    data: Vec<u8>,
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+) -> CoreResult<Vec<u8>> {
+    let image = RgbaImage::from_raw(src_width, src_height, data)
+        .ok_or_else(|| CoreError::Other(anyhow::anyhow!("icon buffer size mismatch")))?;
+    // Triangle filtering keeps 256px -> 48px downscales from aliasing.
+    Ok(image::imageops::resize(&image, dst_width, dst_height, FilterType::Triangle).into_raw())
 }
 
 fn encode_as_png(data: &[u8], width: usize, height: usize) -> CoreResult<Vec<u8>> {
@@ -205,3 +1076,206 @@ fn encode_as_png(data: &[u8], width: usize, height: usize) -> CoreResult<Vec<u8>
     Ok(png_data)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    // Hits shell32.dll from many threads at once; run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn extract_icon_batch_survives_concurrent_batches() {
+        clear_icon_cache();
+        let shell32 = r"C:\Windows\System32\shell32.dll";
+        let jobs: Vec<IconJob> = (0..100)
+            .map(|index| IconJob {
+                icon_path: shell32.to_string(),
+                icon_index: Some(index),
+                size: 32,
+                trim: None,
+            })
+            .collect();
+
+        std::thread::scope(|scope| {
+            for chunk in jobs.chunks(jobs.len().div_ceil(8)) {
+                scope.spawn(move || {
+                    for (job, result) in chunk.iter().zip(extract_icon_batch(chunk)) {
+                        let png = result.unwrap().unwrap_or_else(|| {
+                            panic!("no icon at shell32.dll,{}", job.icon_index.unwrap())
+                        });
+                        assert!(png.starts_with(PNG_SIGNATURE));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn render_icon_keeps_alpha_and_straight_colors() {
+        use windows::Win32::{
+            Graphics::Gdi::CreateBitmap,
+            UI::WindowsAndMessaging::{CreateIconIndirect, ICONINFO},
+        };
+
+        const SIZE: usize = 16;
+        // Opaque BGRA everywhere except 4x4 fully transparent corners.
+        let mut color = Vec::with_capacity(SIZE * SIZE * 4);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let corner = !(4..SIZE - 4).contains(&x) && !(4..SIZE - 4).contains(&y);
+                color.extend_from_slice(if corner { &[0; 4] } else { &[40, 80, 200, 255] });
+            }
+        }
+        // Monochrome rows are word-aligned; an all-zero AND mask keeps every pixel.
+        let mask = vec![0u8; SIZE.div_ceil(16) * 2 * SIZE];
+
+        let rgba = unsafe {
+            let color_bitmap = OwnedBitmap(CreateBitmap(
+                SIZE as i32,
+                SIZE as i32,
+                1,
+                32,
+                Some(color.as_ptr().cast()),
+            ));
+            let mask_bitmap = OwnedBitmap(CreateBitmap(
+                SIZE as i32,
+                SIZE as i32,
+                1,
+                1,
+                Some(mask.as_ptr().cast()),
+            ));
+            let info = ICONINFO {
+                fIcon: true.into(),
+                hbmMask: mask_bitmap.0,
+                hbmColor: color_bitmap.0,
+                ..Default::default()
+            };
+            let hicon = CreateIconIndirect(&info).unwrap();
+            let rgba = render_icon(hicon, SIZE as i32, SIZE as i32);
+            let _ = DestroyIcon(hicon);
+            rgba.unwrap()
+        };
+
+        let pixel = |x: usize, y: usize| &rgba[(y * SIZE + x) * 4..][..4];
+        for (x, y) in [(0, 0), (SIZE - 1, 0), (0, SIZE - 1), (SIZE - 1, SIZE - 1)] {
+            assert_eq!(pixel(x, y)[3], 0, "corner ({x}, {y})");
+        }
+        assert_eq!(pixel(SIZE / 2, SIZE / 2), [200, 80, 40, 255]);
+    }
+
+    /// `count` copies of one RGBA pixel.
+    fn pixels(pixel: [u8; 4], count: usize) -> Vec<u8> {
+        pixel.repeat(count)
+    }
+
+    #[test]
+    fn dominant_color_ignores_transparent_pixels() {
+        let mut rgba = pixels([0, 0, 255, 0], 100);
+        rgba.extend(pixels([0, 0, 255, 100], 100));
+        rgba.extend(pixels([220, 40, 40, 255], 1));
+        assert_eq!(dominant_color(&rgba), Some(0xFFDC_2828));
+    }
+
+    #[test]
+    fn dominant_color_ignores_near_white_and_near_black() {
+        let mut rgba = pixels([240, 240, 240, 255], 100);
+        rgba.extend(pixels([10, 10, 10, 255], 100));
+        rgba.extend(pixels([100, 100, 100, 255], 1));
+        assert_eq!(dominant_color(&rgba), Some(0xFF64_6464));
+    }
+
+    #[test]
+    fn dominant_color_prefers_vivid_colors_over_larger_greys() {
+        let mut rgba = pixels([128, 128, 128, 255], 100);
+        rgba.extend(pixels([220, 40, 40, 255], 10));
+        assert_eq!(dominant_color(&rgba), Some(0xFFDC_2828));
+    }
+
+    #[test]
+    fn dominant_color_of_an_all_white_icon_is_none() {
+        assert_eq!(dominant_color(&pixels([255, 255, 255, 255], 64)), None);
+    }
+
+    /// A transparent `width` x `height` RGBA image with an opaque red glyph
+    /// covering `glyph` (left, top, right, bottom).
+    fn padded_icon(width: usize, height: usize, glyph: (usize, usize, usize, usize)) -> Vec<u8> {
+        let (left, top, right, bottom) = glyph;
+        let mut rgba = vec![0; width * height * 4];
+        for y in top..bottom {
+            for x in left..right {
+                rgba[(y * width + x) * 4..][..4].copy_from_slice(&[255, 0, 0, 255]);
+            }
+        }
+        rgba
+    }
+
+    #[test]
+    fn trim_transparent_border_crops_around_the_glyph() {
+        let rgba = padded_icon(32, 32, (8, 10, 24, 22));
+        let (cropped, width, height) = trim_transparent_border(&rgba, 32, 32, None, 2).unwrap();
+        // A 16x12 glyph plus 2px on each side, centered on the glyph.
+        assert_eq!((width, height), (20, 20));
+        assert_eq!(cropped.len(), 20 * 20 * 4);
+        let pixel = |x: usize, y: usize| &cropped[(y * 20 + x) * 4..][..4];
+        assert_eq!(pixel(1, 10)[3], 0);
+        assert_eq!(pixel(2, 10), [255, 0, 0, 255]);
+        assert_eq!(pixel(17, 10), [255, 0, 0, 255]);
+        assert_eq!(pixel(18, 10)[3], 0);
+        assert_eq!(pixel(10, 3)[3], 0);
+        assert_eq!(pixel(10, 4), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn trim_transparent_border_leaves_opaque_icons_alone() {
+        let rgba = padded_icon(16, 16, (0, 0, 16, 16));
+        assert!(trim_transparent_border(&rgba, 16, 16, Some(48), 4).is_none());
+        assert!(trim_transparent_border(&[0; 16 * 16 * 4], 16, 16, None, 0).is_none());
+    }
+
+    #[test]
+    fn trim_transparent_border_clamps_large_margins() {
+        let rgba = padded_icon(32, 32, (12, 12, 20, 20));
+        // The margin can't exceed the image, so nothing is left to trim.
+        assert!(trim_transparent_border(&rgba, 32, 32, None, 100).is_none());
+        // An output margin past half the output size is clamped rather than
+        // dividing by zero.
+        assert!(trim_transparent_border(&rgba, 32, 32, Some(16), 100).is_none());
+        let (_, width, _) = trim_transparent_border(&rgba, 32, 32, Some(16), 4).unwrap();
+        assert_eq!(width, 16);
+    }
+
+    #[test]
+    fn trim_transparent_border_shifts_the_crop_inside_at_edges() {
+        let rgba = padded_icon(32, 32, (0, 0, 8, 8));
+        let (cropped, width, height) = trim_transparent_border(&rgba, 32, 32, None, 4).unwrap();
+        assert_eq!((width, height), (16, 16));
+        // Centering would start the crop at (-4, -4); it is pinned to the corner.
+        assert_eq!(&cropped[..4], [255, 0, 0, 255]);
+        assert_eq!(cropped[(7 * 16 + 7) * 4 + 3], 255);
+        assert_eq!(cropped[(8 * 16 + 8) * 4 + 3], 0);
+    }
+
+    #[test]
+    fn apply_mask_alpha_follows_the_and_mask() {
+        let mut bgra = [10, 20, 30, 0, 40, 50, 60, 0];
+        let mask = [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0];
+        apply_mask_alpha(&mut bgra, &mask);
+        assert_eq!(bgra, [10, 20, 30, 0xFF, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn unpremultiply_restores_straight_alpha() {
+        let mut pixels = [
+            64, 32, 128, 128, // half transparent
+            10, 20, 30, 255, // opaque, unchanged
+            5, 5, 5, 0, // fully transparent, cleared
+            200, 100, 50, 100, // clamped to 255
+        ];
+        unpremultiply(&mut pixels);
+        assert_eq!(
+            pixels,
+            [128, 64, 255, 128, 10, 20, 30, 255, 0, 0, 0, 0, 255, 255, 128, 100]
+        );
+    }
+}
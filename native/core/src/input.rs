@@ -1,23 +1,93 @@
-use std::{mem::size_of, thread, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem::size_of,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
 
 use anyhow::anyhow;
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use windows::Win32::{
-    Foundation::HWND,
+    Foundation::{ERROR_HOTKEY_ALREADY_REGISTERED, HMODULE, HWND, LPARAM, POINT, RECT, WPARAM},
+    System::{
+        SystemInformation::GetTickCount,
+        Threading::{AttachThreadInput, GetCurrentThreadId},
+    },
     UI::{
+        Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
         Input::KeyboardAndMouse::{
-            SendInput, INPUT, INPUT_KEYBOARD, INPUT_0, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP, VIRTUAL_KEY,
+            GetAsyncKeyState, GetLastInputInfo, RegisterHotKey, SendInput, UnregisterHotKey,
+            HOT_KEY_MODIFIERS, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+            KEYEVENTF_KEYUP, KEYEVENTF_UNICODE, LASTINPUTINFO, MOD_NOREPEAT, VIRTUAL_KEY,
         },
         WindowsAndMessaging::{
-            AllowSetForegroundWindow, BringWindowToTop, GetForegroundWindow, IsIconic, SetForegroundWindow, ShowWindow,
-            ASFW_ANY, SW_RESTORE,
+            AllowSetForegroundWindow, BringWindowToTop, DispatchMessageW, FlashWindowEx,
+            GetAncestor, GetClassNameW, GetCursorPos, GetForegroundWindow, GetMessageW,
+            GetWindowRect, GetWindowThreadProcessId, IsIconic, IsWindow, IsZoomed, PeekMessageW,
+            PostMessageW, PostThreadMessageW, SetCursorPos, SetForegroundWindow, SetWindowPos,
+            ShowWindow, WindowFromPoint, ASFW_ANY, EVENT_SYSTEM_FOREGROUND, FLASHWINFO,
+            FLASHW_TIMERNOFG, FLASHW_TRAY, GA_ROOT, HWND_NOTOPMOST, HWND_TOPMOST, MSG,
+            OBJID_WINDOW, PM_NOREMOVE, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, SWP_NOZORDER,
+            SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE, WINEVENT_OUTOFCONTEXT, WM_APP, WM_CLOSE,
+            WM_HOTKEY, WM_QUIT,
         },
     },
 };
 
-use crate::error::{CoreError, CoreResult};
+use crate::{
+    error::{catch_callback_panic, CoreError, CoreResult},
+    monitor::{enumerate_monitors, virtual_screen_rect, window_monitor},
+    utils::{process_image_path, string_from_wide, window_process_id, window_title},
+    Modifiers, Point, WindowInfo, WindowState,
+};
 
 const KEY_CONTROL: u16 = 0x11; // VK_CONTROL
 const KEY_V: u16 = 0x56; // 'V'
+const KEY_C: u16 = 0x43; // 'C'
+const KEY_ALT: u16 = 0x12; // VK_MENU
+const KEY_SHIFT: u16 = 0x10; // VK_SHIFT
+const KEY_LWIN: u16 = 0x5B; // VK_LWIN
+const KEY_RWIN: u16 = 0x5C; // VK_RWIN
+
+pub const DEFAULT_FOCUS_SETTLE_MS: u32 = 12;
+pub const DEFAULT_KEY_DELAY_MS: u32 = 35;
+
+static FOCUS_SETTLE_MS: AtomicU32 = AtomicU32::new(DEFAULT_FOCUS_SETTLE_MS);
+static KEY_DELAY_MS: AtomicU32 = AtomicU32::new(DEFAULT_KEY_DELAY_MS);
+
+/// Tunes how long we wait after focusing a window and before sending a key combo.
+pub fn set_input_timing(focus_settle_ms: u32, key_delay_ms: u32) {
+    FOCUS_SETTLE_MS.store(focus_settle_ms, Ordering::Relaxed);
+    KEY_DELAY_MS.store(key_delay_ms, Ordering::Relaxed);
+}
+
+fn delay_from(setting: &AtomicU32) -> Duration {
+    Duration::from_millis(u64::from(setting.load(Ordering::Relaxed)))
+}
+
+/// Pause between characters in `type_text`; some apps drop input that arrives too fast.
+pub const DEFAULT_TYPE_DELAY: Duration = Duration::from_millis(5);
+
+pub struct WindowDetails {
+    pub handle: String,
+    pub title: Option<String>,
+    pub class_name: Option<String>,
+    pub process_id: Option<u32>,
+    pub process_path: Option<String>,
+    pub is_minimized: bool,
+    pub is_maximized: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
 
 pub fn capture_foreground_handle() -> Option<String> {
     unsafe {
@@ -25,33 +95,829 @@ pub fn capture_foreground_handle() -> Option<String> {
         if hwnd.0 == 0 {
             None
         } else {
-            Some(format!("{:016X}", hwnd.0 as isize as u64))
+            Some(format_window_handle(hwnd))
         }
     }
 }
 
+/// Full executable path of the app that owns the foreground window.
+pub fn foreground_process_path() -> Option<String> {
+    let hwnd = unsafe { GetForegroundWindow() };
+    window_process_id(hwnd).and_then(process_image_path)
+}
+
 pub fn focus_window(handle: &str) -> CoreResult<()> {
+    match parse_window_handle(handle)? {
+        Some(hwnd) => restore_window(hwnd),
+        None => Ok(()),
+    }
+}
+
+/// Minimizes, maximizes, restores or politely closes the window behind `handle`.
+pub fn set_window_state(handle: &str, state: WindowState) -> CoreResult<()> {
+    let Some(hwnd) = parse_window_handle(handle)? else {
+        return Ok(());
+    };
+
+    unsafe {
+        if !IsWindow(hwnd).as_bool() {
+            return Err(CoreError::NotFound(format!(
+                "window '{handle}' no longer exists"
+            )));
+        }
+
+        match state {
+            WindowState::Minimize => {
+                let _ = ShowWindow(hwnd, SW_MINIMIZE);
+            }
+            WindowState::Maximize => {
+                let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+            }
+            WindowState::Restore => {
+                let _ = ShowWindow(hwnd, SW_RESTORE);
+            }
+            // WM_CLOSE rather than terminating, so the app can prompt to save.
+            WindowState::Close => {
+                PostMessageW(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0)).map_err(|err| {
+                    CoreError::from_hresult("PostMessageW WM_CLOSE failed", err.code())
+                })?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Moves a window onto the monitor at `monitor_index`, keeping its size and
+/// position relative to the work area. Maximized windows stay maximized.
+pub fn move_window_to_monitor(handle: &str, monitor_index: u32) -> CoreResult<()> {
+    let Some(hwnd) = parse_window_handle(handle)? else {
+        return Ok(());
+    };
+    if !unsafe { IsWindow(hwnd) }.as_bool() {
+        return Err(CoreError::NotFound(format!(
+            "window '{handle}' no longer exists"
+        )));
+    }
+
+    let monitors = enumerate_monitors()?;
+    let target = monitors.get(monitor_index as usize).ok_or_else(|| {
+        CoreError::NotFound(format!(
+            "monitor index {monitor_index} out of range ({} monitors attached)",
+            monitors.len()
+        ))
+    })?;
+
+    unsafe {
+        // Work from the restored rectangle; a maximized rect only describes the old monitor.
+        let was_maximized = IsZoomed(hwnd).as_bool();
+        if was_maximized || IsIconic(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        let source = window_monitor(hwnd)?.work_area;
+        let target = target.work_area;
+        let mut rect = RECT::default();
+        GetWindowRect(hwnd, &mut rect)
+            .map_err(|err| CoreError::from_hresult("GetWindowRect failed", err.code()))?;
+
+        let scale = |value: i32, from: i32, to: i32| {
+            (i64::from(value) * i64::from(to) / i64::from(from.max(1))) as i32
+        };
+        let (source_width, source_height) =
+            (source.right - source.left, source.bottom - source.top);
+        let (target_width, target_height) =
+            (target.right - target.left, target.bottom - target.top);
+
+        let width =
+            scale(rect.right - rect.left, source_width, target_width).clamp(1, target_width.max(1));
+        let height = scale(rect.bottom - rect.top, source_height, target_height)
+            .clamp(1, target_height.max(1));
+        let x = target.left + scale(rect.left - source.left, source_width, target_width);
+        let y = target.top + scale(rect.top - source.top, source_height, target_height);
+        // Keep the whole window on the target monitor.
+        let x = x.clamp(target.left, (target.right - width).max(target.left));
+        let y = y.clamp(target.top, (target.bottom - height).max(target.top));
+
+        SetWindowPos(
+            hwnd,
+            HWND(0),
+            x,
+            y,
+            width,
+            height,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )
+        .map_err(|err| CoreError::from_hresult("SetWindowPos failed", err.code()))?;
+
+        if was_maximized {
+            let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+        }
+    }
+    Ok(())
+}
+
+/// Flashes the window's taskbar button to ask for attention: `count` flashes,
+/// after which the button stays highlighted until the window is activated.
+pub fn flash_window(handle: &str, count: u32) -> CoreResult<()> {
+    let Some(hwnd) = parse_window_handle(handle)? else {
+        return Ok(());
+    };
+    if !unsafe { IsWindow(hwnd) }.as_bool() {
+        return Err(CoreError::NotFound(format!(
+            "window '{handle}' no longer exists"
+        )));
+    }
+
+    let info = FLASHWINFO {
+        cbSize: size_of::<FLASHWINFO>() as u32,
+        hwnd,
+        dwFlags: FLASHW_TRAY | FLASHW_TIMERNOFG,
+        uCount: count,
+        dwTimeout: 0,
+    };
+    // The return value is the previous highlight state, not an error.
+    let _ = unsafe { FlashWindowEx(&info) };
+    Ok(())
+}
+
+/// Pins the window above all non-topmost windows, or unpins it, without
+/// moving, resizing or activating it.
+pub fn set_window_topmost(handle: &str, topmost: bool) -> CoreResult<()> {
+    let Some(hwnd) = parse_window_handle(handle)? else {
+        return Ok(());
+    };
+    if !unsafe { IsWindow(hwnd) }.as_bool() {
+        return Err(CoreError::NotFound(format!(
+            "window '{handle}' no longer exists"
+        )));
+    }
+
+    let insert_after = if topmost {
+        HWND_TOPMOST
+    } else {
+        HWND_NOTOPMOST
+    };
+    unsafe {
+        SetWindowPos(
+            hwnd,
+            insert_after,
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+        )
+    }
+    .map_err(|err| CoreError::from_hresult("SetWindowPos failed", err.code()))
+}
+
+/// Describes the window behind `handle`, or `None` once it has been destroyed.
+pub fn window_details(handle: &str) -> CoreResult<Option<WindowDetails>> {
+    match parse_window_handle(handle)? {
+        Some(hwnd) => describe_window(hwnd),
+        None => Ok(None),
+    }
+}
+
+/// Describes the top-level window under a virtual-screen point, or `None` when
+/// there is none (e.g. the point is off every monitor).
+pub fn window_from_point(x: i32, y: i32) -> CoreResult<Option<WindowDetails>> {
+    unsafe {
+        let hwnd = WindowFromPoint(POINT { x, y });
+        if hwnd.0 == 0 {
+            return Ok(None);
+        }
+        // WindowFromPoint returns the deepest child, e.g. a browser's render widget.
+        let root = GetAncestor(hwnd, GA_ROOT);
+        describe_window(if root.0 == 0 { hwnd } else { root })
+    }
+}
+
+fn describe_window(hwnd: HWND) -> CoreResult<Option<WindowDetails>> {
+    unsafe {
+        if !IsWindow(hwnd).as_bool() {
+            return Ok(None);
+        }
+
+        let mut class_buffer = [0u16; 256];
+        let class_len = GetClassNameW(hwnd, &mut class_buffer);
+        let class_name = string_from_wide(&class_buffer[..class_len.max(0) as usize]);
+
+        let mut rect = RECT::default();
+        GetWindowRect(hwnd, &mut rect)
+            .map_err(|err| CoreError::from_hresult("GetWindowRect failed", err.code()))?;
+
+        let process_id = window_process_id(hwnd);
+        Ok(Some(WindowDetails {
+            handle: format_window_handle(hwnd),
+            title: window_title(hwnd),
+            class_name,
+            process_id,
+            process_path: process_id.and_then(process_image_path),
+            is_minimized: IsIconic(hwnd).as_bool(),
+            is_maximized: IsZoomed(hwnd).as_bool(),
+            x: rect.left,
+            y: rect.top,
+            width: (rect.right - rect.left).max(0) as u32,
+            height: (rect.bottom - rect.top).max(0) as u32,
+        }))
+    }
+}
+
+impl From<WindowDetails> for WindowInfo {
+    fn from(details: WindowDetails) -> Self {
+        Self {
+            handle: details.handle,
+            title: details.title,
+            class_name: details.class_name,
+            process_id: details.process_id,
+            process_path: details.process_path,
+            is_minimized: details.is_minimized,
+            is_maximized: details.is_maximized,
+            x: details.x,
+            y: details.y,
+            width: details.width,
+            height: details.height,
+        }
+    }
+}
+
+static FOREGROUND_WATCHER: Lazy<Mutex<Option<ForegroundWatcher>>> = Lazy::new(|| Mutex::new(None));
+
+struct ForegroundWatcher {
+    thread_id: u32,
+    handle: thread::JoinHandle<()>,
+}
+
+impl ForegroundWatcher {
+    fn stop(self) {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        self.handle.join().ok();
+    }
+}
+
+thread_local! {
+    static FOREGROUND_CALLBACK: RefCell<Option<ThreadsafeFunction<WindowInfo>>> = const { RefCell::new(None) };
+}
+
+/// Emits the new foreground window's info every time focus moves to another window.
+/// Calling it again replaces the previous callback.
+pub fn watch_foreground_changes(callback: ThreadsafeFunction<WindowInfo>) -> CoreResult<()> {
+    let mut guard = FOREGROUND_WATCHER.lock();
+    if let Some(watcher) = guard.take() {
+        watcher.stop();
+    }
+
+    let (ready, started) = mpsc::channel();
+    let handle = thread::Builder::new()
+        .name("wolong-foreground".to_string())
+        .spawn(move || run_foreground_hook(callback, ready))
+        .map_err(|err| CoreError::Other(anyhow!("spawn foreground thread failed: {err}")))?;
+
+    let thread_id = started
+        .recv()
+        .map_err(|_| CoreError::Other(anyhow!("foreground thread exited during startup")))??;
+    *guard = Some(ForegroundWatcher { thread_id, handle });
+    Ok(())
+}
+
+pub fn unwatch_foreground_changes() {
+    if let Some(watcher) = FOREGROUND_WATCHER.lock().take() {
+        watcher.stop();
+    }
+}
+
+fn run_foreground_hook(
+    callback: ThreadsafeFunction<WindowInfo>,
+    ready: mpsc::Sender<CoreResult<u32>>,
+) {
+    let mut msg = MSG::default();
+    // Out-of-context hooks are delivered through this thread's message queue.
+    let hook = unsafe {
+        let _ = PeekMessageW(&mut msg, HWND(0), 0, 0, PM_NOREMOVE);
+        SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            EVENT_SYSTEM_FOREGROUND,
+            HMODULE(0),
+            Some(foreground_event_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT,
+        )
+    };
+    if hook.is_invalid() {
+        let _ = ready.send(Err(CoreError::from_win32("SetWinEventHook failed")));
+        return;
+    }
+
+    FOREGROUND_CALLBACK.with(|slot| *slot.borrow_mut() = Some(callback));
+    let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+
+    loop {
+        let result = unsafe { GetMessageW(&mut msg, HWND(0), 0, 0) };
+        if result.0 <= 0 {
+            break;
+        }
+        unsafe {
+            DispatchMessageW(&msg);
+        }
+    }
+
+    unsafe {
+        let _ = UnhookWinEvent(hook);
+    }
+    FOREGROUND_CALLBACK.with(|slot| *slot.borrow_mut() = None);
+}
+
+unsafe extern "system" fn foreground_event_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    _id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    if hwnd.0 == 0 || id_object != OBJID_WINDOW.0 {
+        return;
+    }
+    catch_callback_panic("foreground listener", || {
+        let Ok(Some(details)) = describe_window(hwnd) else {
+            return;
+        };
+
+        FOREGROUND_CALLBACK.with(|slot| {
+            if let Some(callback) = slot.borrow().as_ref() {
+                let _ = callback.call(Ok(details.into()), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        });
+    });
+}
+
+fn format_window_handle(hwnd: HWND) -> String {
+    format!("{:016X}", hwnd.0 as u64)
+}
+
+/// Decodes a handle produced by `capture_foreground_handle`; empty or zero means none.
+pub(crate) fn parse_window_handle(handle: &str) -> CoreResult<Option<HWND>> {
     let trimmed = handle.trim();
     if trimmed.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     let normalized = trimmed.strip_prefix("0x").unwrap_or(trimmed);
     let value = u64::from_str_radix(normalized, 16)
-        .map_err(|err| CoreError::Other(anyhow!("invalid window handle '{handle}': {err}")))?;
+        .map_err(|err| CoreError::InvalidArgument(format!("invalid window handle '{handle}': {err}")))?;
+
+    Ok((value != 0).then_some(HWND(value as isize)))
+}
 
-    if value == 0 {
+pub fn simulate_paste() -> CoreResult<()> {
+    send_combo(&[(KEY_CONTROL, false), (KEY_V, false), (KEY_V, true), (KEY_CONTROL, true)])
+}
+
+/// Milliseconds since the last keyboard or mouse input in this session.
+pub fn idle_time_ms() -> CoreResult<u32> {
+    let mut info = LASTINPUTINFO {
+        cbSize: size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    unsafe {
+        if !GetLastInputInfo(&mut info).as_bool() {
+            return Err(CoreError::from_win32("GetLastInputInfo failed"));
+        }
+        // Both are 32-bit tick counts, so wrap rather than underflow after ~49 days.
+        Ok(GetTickCount().wrapping_sub(info.dwTime))
+    }
+}
+
+/// Whether the virtual key is held down right now, regardless of which window
+/// has focus.
+pub fn key_pressed(vk: u32) -> CoreResult<bool> {
+    if !(1..=0xFE).contains(&vk) {
+        return Err(CoreError::InvalidArgument(format!(
+            "virtual key {vk:#x} out of range"
+        )));
+    }
+    Ok(async_key_down(vk as u16))
+}
+
+/// Which modifiers are held down right now; either side counts.
+pub fn modifier_state() -> Modifiers {
+    Modifiers {
+        ctrl: async_key_down(KEY_CONTROL),
+        alt: async_key_down(KEY_ALT),
+        shift: async_key_down(KEY_SHIFT),
+        win: async_key_down(KEY_LWIN) || async_key_down(KEY_RWIN),
+    }
+}
+
+fn async_key_down(vk: u16) -> bool {
+    // The high bit is the current state; the low bit only says "pressed since last call".
+    let state = unsafe { GetAsyncKeyState(i32::from(vk)) };
+    state < 0
+}
+
+/// The pointer position in virtual-screen coordinates.
+pub fn cursor_pos() -> CoreResult<Point> {
+    let mut point = POINT::default();
+    unsafe { GetCursorPos(&mut point) }
+        .map_err(|_| CoreError::from_win32("GetCursorPos failed"))?;
+    Ok(Point {
+        x: point.x,
+        y: point.y,
+    })
+}
+
+/// Moves the pointer, rejecting points outside every monitor's combined bounds.
+pub fn set_cursor_pos(x: i32, y: i32) -> CoreResult<()> {
+    let screen = virtual_screen_rect();
+    if x < screen.left || x >= screen.right || y < screen.top || y >= screen.bottom {
+        return Err(CoreError::InvalidArgument(format!(
+            "({x}, {y}) is outside the virtual screen ({}, {})-({}, {})",
+            screen.left, screen.top, screen.right, screen.bottom
+        )));
+    }
+    unsafe { SetCursorPos(x, y) }.map_err(|_| CoreError::from_win32("SetCursorPos failed"))
+}
+
+pub fn simulate_copy() -> CoreResult<()> {
+    send_combo(&[
+        (KEY_CONTROL, false),
+        (KEY_C, false),
+        (KEY_C, true),
+        (KEY_CONTROL, true),
+    ])
+}
+
+/// Presses the keys of a spec like `"ctrl+shift+escape"` in order, then releases them in reverse.
+pub fn send_hotkey(spec: &str) -> CoreResult<()> {
+    let keys = parse_hotkey_spec(spec)?;
+    let sequence: Vec<(u16, bool)> = keys
+        .iter()
+        .map(|&vk| (vk, false))
+        .chain(keys.iter().rev().map(|&vk| (vk, true)))
+        .collect();
+    send_combo(&sequence)
+}
+
+fn parse_hotkey_spec(spec: &str) -> CoreResult<Vec<u16>> {
+    let keys = spec
+        .split('+')
+        .map(|token| {
+            let token = token.trim().to_ascii_lowercase();
+            key_code(&token).ok_or_else(|| {
+                CoreError::InvalidArgument(format!("unknown key '{token}' in hotkey '{spec}'"))
+            })
+        })
+        .collect::<CoreResult<Vec<u16>>>()?;
+
+    if keys.is_empty() {
+        return Err(CoreError::InvalidArgument("empty hotkey spec".to_string()));
+    }
+    Ok(keys)
+}
+
+/// Maps a lowercase key name to its virtual key code.
+fn key_code(token: &str) -> Option<u16> {
+    let code = match token {
+        "ctrl" | "control" => KEY_CONTROL,
+        "alt" => KEY_ALT,
+        "shift" => KEY_SHIFT,
+        "win" | "meta" | "super" => KEY_LWIN,
+        "esc" | "escape" => 0x1B,
+        "enter" | "return" => 0x0D,
+        "tab" => 0x09,
+        "space" => 0x20,
+        "backspace" => 0x08,
+        "delete" | "del" => 0x2E,
+        "insert" | "ins" => 0x2D,
+        "home" => 0x24,
+        "end" => 0x23,
+        "pageup" => 0x21,
+        "pagedown" => 0x22,
+        "left" => 0x25,
+        "up" => 0x26,
+        "right" => 0x27,
+        "down" => 0x28,
+        _ => {
+            if let Some(number) = token.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+                return (1..=24).contains(&number).then_some(0x70 + number - 1);
+            }
+            let mut chars = token.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(ch), None) if ch.is_ascii_alphanumeric() => {
+                    Some(ch.to_ascii_uppercase() as u16)
+                }
+                _ => None,
+            };
+        }
+    };
+    Some(code)
+}
+
+/// Longest hold accepted; the calling thread is blocked for the whole duration.
+const MAX_HOLD_MS: u32 = 10_000;
+
+/// Presses `vks` in order, keeps them down for `duration_ms`, then releases
+/// them in reverse. Keys that went down are released even if a later press
+/// fails or the thread unwinds.
+pub fn hold_keys(vks: &[u32], duration_ms: u32) -> CoreResult<()> {
+    if vks.is_empty() {
+        return Err(CoreError::InvalidArgument("no keys to hold".to_string()));
+    }
+    if let Some(vk) = vks.iter().find(|vk| !(1..=0xFE).contains(*vk)) {
+        return Err(CoreError::InvalidArgument(format!(
+            "virtual key {vk:#x} out of range"
+        )));
+    }
+    if duration_ms > MAX_HOLD_MS {
+        return Err(CoreError::InvalidArgument(format!(
+            "hold duration {duration_ms}ms exceeds {MAX_HOLD_MS}ms"
+        )));
+    }
+
+    let mut held = HeldKeys(Vec::with_capacity(vks.len()));
+    for &vk in vks {
+        send_inputs(&[key_input(vk as u16, false)])?;
+        held.0.push(vk as u16);
+    }
+    thread::sleep(Duration::from_millis(u64::from(duration_ms)));
+    Ok(())
+}
+
+/// Keys currently pressed by `hold_keys`, released in reverse on drop.
+struct HeldKeys(Vec<u16>);
+
+impl Drop for HeldKeys {
+    fn drop(&mut self) {
+        let inputs: Vec<INPUT> = self.0.iter().rev().map(|&vk| key_input(vk, true)).collect();
+        if let Err(err) = send_inputs(&inputs) {
+            log::warn!("releasing held keys failed: {err}");
+        }
+    }
+}
+
+/// Types `text` as Unicode key events, independent of the active keyboard layout.
+///
+/// Line breaks (`\n` or `\r\n`) are sent as a single `\r`, i.e. Enter.
+pub fn type_text(text: &str, delay: Duration) -> CoreResult<()> {
+    for (index, keystroke) in keystroke_units(text).iter().enumerate() {
+        if index > 0 && !delay.is_zero() {
+            thread::sleep(delay);
+        }
+
+        let inputs: Vec<INPUT> = keystroke
+            .iter()
+            .flat_map(|&unit| [unicode_input(unit, false), unicode_input(unit, true)])
+            .collect();
+        send_inputs(&inputs)?;
+    }
+    Ok(())
+}
+
+/// Groups the UTF-16 units of `text` into keystrokes. Characters above U+FFFF
+/// (emoji and friends) need their high and low surrogates sent back to back,
+/// so `"a😀b"` yields `[a]`, `[high, low]`, `[b]`.
+fn keystroke_units(text: &str) -> Vec<Vec<u16>> {
+    // Apps expect Enter for a line break, and a lone LF is often ignored.
+    let units: Vec<u16> = text
+        .replace("\r\n", "\n")
+        .encode_utf16()
+        .map(|unit| {
+            if unit == u16::from(b'\n') {
+                u16::from(b'\r')
+            } else {
+                unit
+            }
+        })
+        .collect();
+
+    let mut keystrokes = Vec::with_capacity(units.len());
+    let mut index = 0;
+    while index < units.len() {
+        let high_surrogate = (0xD800..0xDC00).contains(&units[index]);
+        let len = if high_surrogate && index + 1 < units.len() {
+            2
+        } else {
+            1
+        };
+        keystrokes.push(units[index..index + len].to_vec());
+        index += len;
+    }
+    keystrokes
+}
+
+fn unicode_input(unit: u16, key_up: bool) -> INPUT {
+    let flags = if key_up {
+        KEYEVENTF_UNICODE | KEYEVENTF_KEYUP
+    } else {
+        KEYEVENTF_UNICODE
+    };
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(0),
+                wScan: unit,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+/// Posted to the hotkey thread when commands are waiting in its channel.
+const WM_HOTKEY_COMMAND: u32 = WM_APP + 1;
+
+static HOTKEYS: Lazy<HotkeyManager> = Lazy::new(HotkeyManager::default);
+
+enum HotkeyCommand {
+    Register {
+        id: u32,
+        modifiers: u32,
+        vk: u32,
+        callback: ThreadsafeFunction<u32>,
+        reply: mpsc::Sender<CoreResult<()>>,
+    },
+    Unregister {
+        id: u32,
+    },
+}
+
+/// `RegisterHotKey` binds to the calling thread's queue, so every registration is
+/// forwarded to one long-lived message-pump thread.
+#[derive(Default)]
+struct HotkeyManager {
+    worker: Mutex<Option<HotkeyWorker>>,
+}
+
+struct HotkeyWorker {
+    thread_id: u32,
+    commands: mpsc::Sender<HotkeyCommand>,
+}
+
+impl HotkeyManager {
+    fn send(&self, command: HotkeyCommand) -> CoreResult<()> {
+        let mut guard = self.worker.lock();
+        let worker = match guard.take() {
+            Some(worker) => worker,
+            None => spawn_hotkey_thread()?,
+        };
+
+        let delivered = worker.commands.send(command).is_ok()
+            && unsafe {
+                PostThreadMessageW(worker.thread_id, WM_HOTKEY_COMMAND, WPARAM(0), LPARAM(0))
+                    .is_ok()
+            };
+        *guard = Some(worker);
+
+        if delivered {
+            Ok(())
+        } else {
+            Err(CoreError::from_win32(
+                "PostThreadMessageW to hotkey thread failed",
+            ))
+        }
+    }
+}
+
+/// Registers a system-wide hotkey; `callback` receives `id` each time it fires.
+/// Re-registering an existing `id` replaces its key combination and callback.
+pub fn register_global_hotkey(
+    id: u32,
+    modifiers: u32,
+    vk: u32,
+    callback: ThreadsafeFunction<u32>,
+) -> CoreResult<()> {
+    let (reply, result) = mpsc::channel();
+    HOTKEYS.send(HotkeyCommand::Register {
+        id,
+        modifiers,
+        vk,
+        callback,
+        reply,
+    })?;
+    result
+        .recv()
+        .map_err(|_| CoreError::Other(anyhow!("hotkey thread exited")))?
+}
+
+pub fn unregister_global_hotkey(id: u32) -> CoreResult<()> {
+    if HOTKEYS.worker.lock().is_none() {
         return Ok(());
     }
+    HOTKEYS.send(HotkeyCommand::Unregister { id })
+}
+
+fn spawn_hotkey_thread() -> CoreResult<HotkeyWorker> {
+    let (commands, receiver) = mpsc::channel();
+    let (ready, thread_id) = mpsc::channel();
 
-    let hwnd = HWND(value as isize);
-    restore_window(hwnd)
+    thread::Builder::new()
+        .name("wolong-hotkeys".to_string())
+        .spawn(move || {
+            // Make sure the thread has a message queue before anyone posts to it.
+            let mut msg = MSG::default();
+            unsafe {
+                let _ = PeekMessageW(&mut msg, HWND(0), 0, 0, PM_NOREMOVE);
+                let _ = ready.send(GetCurrentThreadId());
+            }
+            run_hotkey_loop(receiver);
+        })
+        .map_err(|err| CoreError::Other(anyhow!("spawn hotkey thread failed: {err}")))?;
+
+    let thread_id = thread_id
+        .recv()
+        .map_err(|_| CoreError::Other(anyhow!("hotkey thread exited during startup")))?;
+    Ok(HotkeyWorker {
+        thread_id,
+        commands,
+    })
 }
 
-pub fn simulate_paste() -> CoreResult<()> {
-    send_combo(&[(KEY_CONTROL, false), (KEY_V, false), (KEY_V, true), (KEY_CONTROL, true)])
+fn run_hotkey_loop(commands: mpsc::Receiver<HotkeyCommand>) {
+    let mut callbacks: HashMap<u32, ThreadsafeFunction<u32>> = HashMap::new();
+    let mut msg = MSG::default();
+
+    loop {
+        let result = unsafe { GetMessageW(&mut msg, HWND(0), 0, 0) };
+        if result.0 <= 0 {
+            break;
+        }
+
+        match msg.message {
+            WM_HOTKEY => {
+                let id = msg.wParam.0 as u32;
+                if let Some(callback) = callbacks.get(&id) {
+                    let _ = callback.call(Ok(id), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            }
+            WM_HOTKEY_COMMAND => {
+                for command in commands.try_iter() {
+                    apply_hotkey_command(command, &mut callbacks);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_hotkey_command(
+    command: HotkeyCommand,
+    callbacks: &mut HashMap<u32, ThreadsafeFunction<u32>>,
+) {
+    match command {
+        HotkeyCommand::Register {
+            id,
+            modifiers,
+            vk,
+            callback,
+            reply,
+        } => {
+            if callbacks.remove(&id).is_some() {
+                unsafe {
+                    let _ = UnregisterHotKey(HWND(0), id as i32);
+                }
+            }
+
+            // MOD_NOREPEAT keeps a held combination from firing over and over.
+            let modifiers = HOT_KEY_MODIFIERS(modifiers) | MOD_NOREPEAT;
+            let result = match unsafe { RegisterHotKey(HWND(0), id as i32, modifiers, vk) } {
+                Ok(()) => {
+                    callbacks.insert(id, callback);
+                    Ok(())
+                }
+                Err(err) if err.code() == ERROR_HOTKEY_ALREADY_REGISTERED.to_hresult() => {
+                    Err(CoreError::HotkeyAlreadyRegistered(id))
+                }
+                Err(err) => Err(CoreError::from_hresult("RegisterHotKey failed", err.code())),
+            };
+            let _ = reply.send(result);
+        }
+        HotkeyCommand::Unregister { id } => {
+            if callbacks.remove(&id).is_some() {
+                unsafe {
+                    let _ = UnregisterHotKey(HWND(0), id as i32);
+                }
+            }
+        }
+    }
 }
 
+/// Brings `hwnd` to the foreground.
+///
+/// Windows only lets the foreground process hand focus away, so from the
+/// background `SetForegroundWindow` can report success and change nothing. Our
+/// input queue is attached to the foreground and target threads for the
+/// duration of the call; if that still doesn't stick, tapping ALT counts as
+/// user input from us and lifts the foreground lock.
+///
+/// UIPI still applies: a non-elevated process cannot focus or send input to an
+/// elevated window, and none of these workarounds get around that.
 fn restore_window(hwnd: HWND) -> CoreResult<()> {
     if hwnd.0 == 0 {
         return Ok(());
@@ -64,43 +930,100 @@ fn restore_window(hwnd: HWND) -> CoreResult<()> {
             let _ = ShowWindow(hwnd, SW_RESTORE);
         }
 
-        if let Err(err) = BringWindowToTop(hwnd) {
-            return Err(CoreError::Other(anyhow!("BringWindowToTop failed: {err}")));
+        let current_thread = GetCurrentThreadId();
+        let foreground_thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        let target_thread = GetWindowThreadProcessId(hwnd, None);
+
+        let mut focused = {
+            let _foreground = AttachedInput::attach(current_thread, foreground_thread);
+            let _target = (target_thread != foreground_thread)
+                .then(|| AttachedInput::attach(current_thread, target_thread))
+                .flatten();
+
+            if let Err(err) = BringWindowToTop(hwnd) {
+                return Err(CoreError::from_hresult(
+                    "BringWindowToTop failed",
+                    err.code(),
+                ));
+            }
+            SetForegroundWindow(hwnd).as_bool()
+        };
+
+        if !focused || GetForegroundWindow() != hwnd {
+            send_inputs(&[key_input(KEY_ALT, false), key_input(KEY_ALT, true)])?;
+            focused = SetForegroundWindow(hwnd).as_bool();
         }
 
-        if SetForegroundWindow(hwnd).0 == 0 {
+        if !focused {
             return Err(CoreError::from_win32("SetForegroundWindow failed"));
         }
 
         // Give Windows a moment to settle focus before we send keystrokes
-        thread::sleep(Duration::from_millis(12));
+        thread::sleep(delay_from(&FOCUS_SETTLE_MS));
     }
 
     Ok(())
 }
 
+/// Shares our input state with another thread until dropped.
+struct AttachedInput {
+    ours: u32,
+    theirs: u32,
+}
+
+impl AttachedInput {
+    fn attach(ours: u32, theirs: u32) -> Option<Self> {
+        if theirs == 0 || theirs == ours {
+            return None;
+        }
+        unsafe { AttachThreadInput(ours, theirs, true) }
+            .as_bool()
+            .then_some(Self { ours, theirs })
+    }
+}
+
+impl Drop for AttachedInput {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = AttachThreadInput(self.ours, self.theirs, false);
+        }
+    }
+}
+
 fn send_combo(sequence: &[(u16, bool)]) -> CoreResult<()> {
     // Small delay to allow other windows to settle (matching human timing)
-    thread::sleep(Duration::from_millis(35));
-
-    let mut inputs: Vec<INPUT> = Vec::with_capacity(sequence.len());
-    for &(vk, key_up) in sequence {
-        inputs.push(INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VIRTUAL_KEY(vk),
-                    wScan: 0,
-                    dwFlags: if key_up { KEYEVENTF_KEYUP } else { KEYBD_EVENT_FLAGS(0) },
-                    time: 0,
-                    dwExtraInfo: 0,
+    thread::sleep(delay_from(&KEY_DELAY_MS));
+
+    let inputs: Vec<INPUT> = sequence
+        .iter()
+        .map(|&(vk, key_up)| key_input(vk, key_up))
+        .collect();
+
+    send_inputs(&inputs)
+}
+
+fn key_input(vk: u16, key_up: bool) -> INPUT {
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(vk),
+                wScan: 0,
+                dwFlags: if key_up {
+                    KEYEVENTF_KEYUP
+                } else {
+                    KEYBD_EVENT_FLAGS(0)
                 },
+                time: 0,
+                dwExtraInfo: 0,
             },
-        });
+        },
     }
+}
 
+fn send_inputs(inputs: &[INPUT]) -> CoreResult<()> {
     unsafe {
-        let sent = SendInput(&inputs, size_of::<INPUT>() as i32);
+        let sent = SendInput(inputs, size_of::<INPUT>() as i32);
         if sent == 0 {
             return Err(CoreError::from_win32("SendInput failed"));
         }
@@ -108,7 +1031,27 @@ fn send_combo(sequence: &[(u16, bool)]) -> CoreResult<()> {
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn keystroke_units_keep_surrogate_pairs_together() {
+        let units = keystroke_units("a\u{1f600}b");
+        assert_eq!(units, vec![vec![0x61], vec![0xD83D, 0xDE00], vec![0x62]]);
+    }
 
+    #[test]
+    fn keystroke_units_send_line_breaks_as_enter() {
+        let units = keystroke_units("a\r\nb\nc");
+        assert_eq!(
+            units,
+            vec![vec![0x61], vec![0x0D], vec![0x62], vec![0x0D], vec![0x63]]
+        );
+    }
 
-
+    #[test]
+    fn keystroke_units_of_empty_text_is_empty() {
+        assert!(keystroke_units("").is_empty());
+    }
+}
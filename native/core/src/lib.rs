@@ -1,18 +1,50 @@
 mod app_index;
+mod app_watcher;
 mod clipboard;
-mod input;
+mod com;
+mod dxgi;
 mod error;
 mod icon;
+mod input;
+mod logging;
+mod monitor;
+mod process;
+mod screen_stream;
 mod screenshot;
+mod shell;
+mod theme;
 mod utils;
 
 use app_index::scan_app_records;
-use clipboard::{start_clipboard_watcher, stop_clipboard_watcher};
-use input::{capture_foreground_handle, focus_window as focus_window_handle, simulate_paste};
+use clipboard::{
+    clear_clipboard as clear_clipboard_contents, clipboard_history, clipboard_stats,
+    copy_selection as copy_selected_text, paste_plain_text, pause_clipboard_watcher,
+    read_current_clipboard, reset_clipboard_stats as reset_stats, resume_clipboard_watcher,
+    search_clipboard_history as search_history, start_clipboard_watcher, stop_clipboard_watcher,
+    write_clipboard_content, write_clipboard_image, write_clipboard_rgba, write_clipboard_text,
+};
+use error::{catch_panic, install_panic_hook, CoreError};
+use input::{
+    capture_foreground_handle, cursor_pos, flash_window as flash_window_handle,
+    focus_window as focus_window_handle, foreground_process_path, idle_time_ms,
+    move_window_to_monitor as move_to_monitor, register_global_hotkey as register_hotkey,
+    send_hotkey as send_key_combo, set_input_timing as apply_input_timing,
+    set_window_state as apply_window_state, set_window_topmost as apply_window_topmost,
+    simulate_copy, simulate_paste, type_text as type_unicode_text,
+    unregister_global_hotkey as unregister_hotkey,
+    unwatch_foreground_changes as unwatch_foreground, watch_foreground_changes as watch_foreground,
+    window_details, DEFAULT_FOCUS_SETTLE_MS, DEFAULT_KEY_DELAY_MS, DEFAULT_TYPE_DELAY,
+};
+use monitor::enable_dpi_awareness;
 use napi::bindgen_prelude::*;
 use napi::threadsafe_function::ThreadsafeFunction;
 use napi_derive::napi;
-use screenshot::capture_active_monitor;
+use screenshot::{
+    capture_active_monitor, capture_all_monitors as capture_virtual_screen,
+    capture_monitor_raw as capture_active_monitor_raw, capture_region as capture_screen_region,
+    capture_window as capture_window_image, capture_window_client as capture_window_client_image,
+    recycle_buffer as recycle_capture_buffer, CaptureOptions,
+};
 
 #[napi(object)]
 pub struct AppInfo {
@@ -32,86 +64,859 @@ pub struct ScreenshotPayload {
     pub y: i32,
     pub buffer: Buffer,
     pub mime_type: String,
+    /// Display scale of the captured monitor; `width`/`height` are physical pixels.
+    pub scale_factor: f64,
+    /// Monitor the capture came from, in `move_window_to_monitor` order;
+    /// 4294967295 (`u32::MAX`) for `capture_all_monitors`.
+    pub monitor_index: u32,
+    /// Display device name such as `\\.\DISPLAY1`, or `"virtual"`.
+    pub device_name: String,
+}
+
+/// Unencoded top-down RGBA pixels, 4 bytes per pixel.
+#[napi(object)]
+pub struct RawImage {
+    pub width: u32,
+    pub height: u32,
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub buffer: Buffer,
+    pub scale_factor: f64,
+    pub monitor_index: u32,
+    pub device_name: String,
+}
+
+#[napi(string_enum)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct ScreenshotOptions {
+    pub include_cursor: Option<bool>,
+    /// Defaults to PNG.
+    pub format: Option<ImageFormat>,
+    /// JPEG quality, 1-100 (default 85). WebP is encoded losslessly.
+    pub quality: Option<u8>,
+    /// For `capture_monitor_screenshot`: leave out the taskbar and docked app bars.
+    pub work_area_only: Option<bool>,
+    /// Wait this long before capturing, e.g. to let the user arrange windows.
+    pub delay_ms: Option<u32>,
+}
+
+#[napi(object)]
+pub struct WindowInfo {
+    pub handle: String,
+    pub title: Option<String>,
+    pub class_name: Option<String>,
+    pub process_id: Option<u32>,
+    pub process_path: Option<String>,
+    pub is_minimized: bool,
+    pub is_maximized: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[napi(string_enum)]
+pub enum WindowState {
+    Minimize,
+    Maximize,
+    Restore,
+    Close,
 }
 
 #[napi(object)]
 pub struct ClipboardItem {
     pub sequence: u32,
     pub timestamp: i64,
+    /// Legacy comma-joined form of `formats`; `unknown` when nothing was read.
     pub format: String,
+    /// Payloads present, from `text`, `html`, `rtf`, `files` and `image`.
+    pub formats: Vec<String>,
     pub text: Option<String>,
+    /// Text as it was on the clipboard, set when `normalize_newlines`
+    /// changed it. Restoring the item writes this back.
+    pub raw_text: Option<String>,
     pub html: Option<String>,
+    pub rtf: Option<String>,
+    pub files: Option<Vec<String>>,
     pub image: Option<Buffer>,
+    /// MIME type of `image`, per the watcher's `image_format`.
+    pub image_mime_type: Option<String>,
+    pub image_original_width: Option<u32>,
+    pub image_original_height: Option<u32>,
+    pub source_app: Option<String>,
+    pub source_title: Option<String>,
+    pub source_url: Option<String>,
+    pub truncated: bool,
+    pub kind: String,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct ClipboardWatcherOptions {
+    pub max_image_dimension: Option<u32>,
+    pub max_item_bytes: Option<u32>,
+    pub dedup_window: Option<u32>,
+    pub history_capacity: Option<u32>,
+    /// Retries when another process holds the clipboard open (default 5).
+    pub open_retries: Option<u32>,
+    /// First retry delay, doubled after each retry (default 20).
+    pub open_retry_delay_ms: Option<u32>,
+    /// Upper bound for a single retry delay (default 200).
+    pub open_retry_max_delay_ms: Option<u32>,
+    /// Encoding for captured images (default PNG). JPEG flattens
+    /// transparency onto white.
+    pub image_format: Option<ImageFormat>,
+    /// JPEG quality, 1-100 (default 85).
+    pub image_quality: Option<u8>,
+    /// Convert text line endings to `\n` (default false).
+    pub normalize_newlines: Option<bool>,
+}
+
+#[napi(object)]
+pub struct ClipboardStats {
+    pub text: u32,
+    pub html: u32,
+    pub image: u32,
+    pub files: u32,
+    pub total: u32,
+    /// Times the clipboard stayed locked by another process through every retry.
+    pub open_failures: u32,
 }
 
 #[napi]
-pub async fn scan_apps(start_menu_paths: Vec<String>, registry_paths: Vec<String>) -> napi::Result<Vec<AppInfo>> {
+pub async fn scan_apps(
+    start_menu_paths: Vec<String>,
+    registry_paths: Vec<String>,
+) -> napi::Result<Vec<AppInfo>> {
     let start_menu = start_menu_paths;
     let registry = registry_paths;
     let records = tokio::task::spawn_blocking(move || scan_app_records(&start_menu, &registry))
         .await
-        .map_err(|err| Error::new(Status::GenericFailure, err.to_string()))??;
+        .map_err(CoreError::from)??;
 
-    Ok(records
-        .into_iter()
-        .map(|record| AppInfo {
-            id: record.id,
-            name: record.name,
-            launch_path: record.launch_path,
-            working_directory: record.working_directory,
-            icon_path: record.icon_path,
-            source: record.source,
-        })
-        .collect())
+    Ok(records.into_iter().map(AppInfo::from).collect())
+}
+
+#[napi(string_enum)]
+pub enum AppChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+#[napi(object)]
+pub struct AppChangeEvent {
+    pub kind: AppChangeKind,
+    pub id: String,
+    /// The re-resolved app; absent for removals.
+    pub app: Option<AppInfo>,
+}
+
+/// Calls `callback` as apps under `paths` are added, changed or removed,
+/// compared against a scan taken when subscribing. Calling it again replaces
+/// the previous subscription.
+#[napi]
+pub fn subscribe_app_changes(
+    paths: ScanPaths,
+    callback: ThreadsafeFunction<AppChangeEvent>,
+) -> napi::Result<()> {
+    catch_panic(|| {
+        app_watcher::subscribe_app_changes(paths.start_menu_paths, paths.registry_paths, callback)
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn unsubscribe_app_changes() -> napi::Result<()> {
+    catch_panic(|| {
+        app_watcher::unsubscribe_app_changes();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+/// Apps pinned to the taskbar (`source` = `pinned_taskbar`) or, on Windows 10,
+/// the Start menu (`pinned_start`), e.g. to rank them higher.
+#[napi]
+pub async fn scan_pinned_apps() -> napi::Result<Vec<AppInfo>> {
+    let records = tokio::task::spawn_blocking(app_index::scan_pinned_apps)
+        .await
+        .map_err(CoreError::from)??;
+    Ok(records.into_iter().map(AppInfo::from).collect())
+}
+
+#[napi(object)]
+pub struct RecentItem {
+    pub path: String,
+    pub name: String,
+    /// Unix milliseconds when the item was last opened.
+    pub last_accessed: i64,
+}
+
+/// Up to `limit` recently opened files and folders from the shell's Recent
+/// list, newest first; items whose target is gone are left out.
+#[napi]
+pub async fn scan_recent_documents(limit: u32) -> napi::Result<Vec<RecentItem>> {
+    let documents =
+        tokio::task::spawn_blocking(move || app_index::scan_recent_documents(limit as usize))
+            .await
+            .map_err(CoreError::from)??;
+    Ok(documents.into_iter().map(RecentItem::from).collect())
+}
+
+#[napi::module_init]
+fn init() {
+    install_panic_hook();
+    enable_dpi_awareness();
+}
+
+#[napi(object)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Rectangles are in physical pixels of the virtual screen.
+#[napi(object)]
+pub struct MonitorInfo {
+    /// The index `move_window_to_monitor` and `DxgiCapturer` expect.
+    pub index: u32,
+    pub bounds: Rect,
+    /// `bounds` minus the taskbar and docked app bars.
+    pub work_area: Rect,
+    pub primary: bool,
+    pub scale_factor: f64,
+    /// Display device name such as `\\.\DISPLAY1`.
+    pub device_name: String,
+}
+
+#[napi]
+pub fn get_monitor_layout() -> napi::Result<Vec<MonitorInfo>> {
+    let monitors = catch_panic(monitor::monitor_layout)?;
+    Ok(monitors.into_iter().map(MonitorInfo::from).collect())
+}
+
+/// Sleeps on the runtime rather than in the blocking task, so a delayed
+/// capture does not hold a blocking-pool thread.
+async fn wait_before_capture(delay: std::time::Duration) {
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[napi]
+pub async fn capture_monitor_screenshot(
+    options: Option<ScreenshotOptions>,
+) -> napi::Result<ScreenshotPayload> {
+    let options: CaptureOptions = options.unwrap_or_default().into();
+    wait_before_capture(options.delay).await;
+    let result = tokio::task::spawn_blocking(move || capture_active_monitor(&options))
+        .await
+        .map_err(CoreError::from)??;
+
+    Ok(result.into())
+}
+
+/// Captures the monitor under the cursor without encoding it, for pipelines
+/// that want pixels rather than an image file.
+#[napi]
+pub async fn capture_monitor_raw() -> napi::Result<RawImage> {
+    let capture = tokio::task::spawn_blocking(capture_active_monitor_raw)
+        .await
+        .map_err(CoreError::from)??;
+
+    Ok(capture.into())
+}
+
+/// Captures the monitor under the cursor straight onto the clipboard as a bitmap.
+#[napi]
+pub async fn capture_monitor_to_clipboard() -> napi::Result<()> {
+    tokio::task::spawn_blocking(|| {
+        let capture = capture_active_monitor_raw()?;
+        let written = write_clipboard_rgba(&capture.rgba, capture.width, capture.height);
+        recycle_capture_buffer(capture.rgba);
+        written
+    })
+    .await
+    .map_err(CoreError::from)??;
+
+    Ok(())
+}
+
+/// Streams frames of one monitor, using DXGI Desktop Duplication where
+/// available and GDI otherwise (e.g. over RDP).
+#[napi]
+pub struct DxgiCapturer {
+    inner: dxgi::MonitorCapturer,
+}
+
+#[napi]
+impl DxgiCapturer {
+    /// `monitor_index` follows the monitor order used by `move_window_to_monitor`.
+    #[napi(constructor)]
+    pub fn new(monitor_index: Option<u32>) -> napi::Result<Self> {
+        let monitor_index = monitor_index.unwrap_or(0) as usize;
+        let inner = catch_panic(|| dxgi::MonitorCapturer::new(monitor_index))?;
+        Ok(Self { inner })
+    }
+
+    /// Whether frames come from Desktop Duplication rather than the GDI fallback.
+    #[napi(getter)]
+    pub fn uses_dxgi(&self) -> napi::Result<bool> {
+        catch_panic(|| Ok(self.inner.uses_dxgi())).map_err(Error::from)
+    }
+
+    /// The next changed frame, or `null` if the screen didn't change within
+    /// `timeout_ms` (default 0).
+    #[napi]
+    pub fn next_frame(&mut self, timeout_ms: Option<u32>) -> napi::Result<Option<RawImage>> {
+        let frame = catch_panic(|| self.inner.next_frame(timeout_ms.unwrap_or(0)))?;
+        Ok(frame.map(RawImage::from))
+    }
 }
 
+/// Calls `callback` with each changed frame of a monitor, at most `fps` (capped
+/// at 60) times a second. Frames are dropped while the previous one is still
+/// queued for JS. Starting a stream replaces the running one.
 #[napi]
-pub async fn capture_monitor_screenshot() -> napi::Result<ScreenshotPayload> {
-    let result = tokio::task::spawn_blocking(capture_active_monitor)
+pub fn start_screen_stream(
+    monitor_index: u32,
+    fps: u32,
+    callback: ThreadsafeFunction<screen_stream::StreamFrame>,
+) -> napi::Result<()> {
+    catch_panic(|| screen_stream::start_screen_stream(monitor_index, fps, callback))
+        .map_err(Error::from)
+}
+
+#[napi]
+pub fn stop_screen_stream() -> napi::Result<()> {
+    catch_panic(|| {
+        screen_stream::stop_screen_stream();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+/// Captures every monitor as one image; `x`/`y` are negative when a monitor
+/// sits left of or above the primary one.
+#[napi]
+pub async fn capture_all_monitors(
+    options: Option<ScreenshotOptions>,
+) -> napi::Result<ScreenshotPayload> {
+    let options: CaptureOptions = options.unwrap_or_default().into();
+    wait_before_capture(options.delay).await;
+    let result = tokio::task::spawn_blocking(move || capture_virtual_screen(&options))
         .await
-        .map_err(|err| Error::new(Status::GenericFailure, err.to_string()))??;
+        .map_err(CoreError::from)??;
+
+    Ok(result.into())
+}
+
+/// Captures a window by handle (as returned by `capture_foreground_window`),
+/// even when it is covered by other windows.
+#[napi]
+pub async fn capture_window(
+    handle: String,
+    options: Option<ScreenshotOptions>,
+) -> napi::Result<ScreenshotPayload> {
+    let options: CaptureOptions = options.unwrap_or_default().into();
+    wait_before_capture(options.delay).await;
+    let result = tokio::task::spawn_blocking(move || capture_window_image(&handle, &options))
+        .await
+        .map_err(CoreError::from)??;
+
+    Ok(result.into())
+}
+
+/// Like `capture_window`, but only the client area: no title bar or borders.
+#[napi]
+pub async fn capture_window_client(
+    handle: String,
+    options: Option<ScreenshotOptions>,
+) -> napi::Result<ScreenshotPayload> {
+    let options: CaptureOptions = options.unwrap_or_default().into();
+    wait_before_capture(options.delay).await;
+    let result =
+        tokio::task::spawn_blocking(move || capture_window_client_image(&handle, &options))
+            .await
+            .map_err(CoreError::from)??;
+
+    Ok(result.into())
+}
+
+/// Captures a rectangle of the virtual screen, clamped to its bounds.
+#[napi]
+pub async fn capture_region(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    options: Option<ScreenshotOptions>,
+) -> napi::Result<ScreenshotPayload> {
+    let options: CaptureOptions = options.unwrap_or_default().into();
+    wait_before_capture(options.delay).await;
+    let result =
+        tokio::task::spawn_blocking(move || capture_screen_region(x, y, width, height, &options))
+            .await
+            .map_err(CoreError::from)??;
+
+    Ok(result.into())
+}
 
-    Ok(ScreenshotPayload {
-        width: result.width,
-        height: result.height,
-        x: result.origin_x,
-        y: result.origin_y,
-        buffer: Buffer::from(result.bytes),
-        mime_type: "image/png".to_string(),
+#[napi]
+pub fn subscribe_clipboard(
+    callback: ThreadsafeFunction<ClipboardItem>,
+    options: Option<ClipboardWatcherOptions>,
+) -> napi::Result<()> {
+    let config = options.unwrap_or_default().into();
+    catch_panic(|| start_clipboard_watcher(callback, config)).map_err(Error::from)
+}
+
+#[napi]
+pub fn unsubscribe_clipboard() -> napi::Result<()> {
+    catch_panic(|| {
+        stop_clipboard_watcher();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn pause_clipboard() -> napi::Result<()> {
+    catch_panic(|| {
+        pause_clipboard_watcher();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn resume_clipboard() -> napi::Result<()> {
+    catch_panic(|| {
+        resume_clipboard_watcher();
+        Ok(())
     })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn get_clipboard_stats() -> napi::Result<ClipboardStats> {
+    catch_panic(|| Ok(clipboard_stats())).map_err(Error::from)
+}
+
+#[napi]
+pub fn reset_clipboard_stats() -> napi::Result<()> {
+    catch_panic(|| {
+        reset_stats();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn get_clipboard_history(limit: u32) -> napi::Result<Vec<ClipboardItem>> {
+    let history = catch_panic(|| Ok(clipboard_history(limit as usize)))?;
+    Ok(history.into_iter().map(ClipboardItem::from).collect())
+}
+
+#[napi]
+pub fn search_clipboard_history(query: String, limit: u32) -> napi::Result<Vec<ClipboardItem>> {
+    let matches = catch_panic(|| Ok(search_history(&query, limit as usize)))?;
+    Ok(matches.into_iter().map(ClipboardItem::from).collect())
+}
+
+#[napi]
+pub fn get_current_clipboard() -> napi::Result<Option<ClipboardItem>> {
+    catch_panic(read_current_clipboard)
+        .map(|snapshot| snapshot.map(ClipboardItem::from))
+        .map_err(Error::from)
+}
+
+#[napi]
+pub fn set_clipboard_text(text: String) -> napi::Result<()> {
+    catch_panic(|| write_clipboard_text(&text)).map_err(Error::from)
+}
+
+#[napi]
+pub fn set_clipboard_image(png: Buffer) -> napi::Result<()> {
+    catch_panic(|| write_clipboard_image(&png)).map_err(Error::from)
+}
+
+#[napi]
+pub fn restore_clipboard_item(item: ClipboardItem) -> napi::Result<()> {
+    catch_panic(|| write_clipboard_content(&item.into())).map_err(Error::from)
+}
+
+#[napi]
+pub fn clear_clipboard() -> napi::Result<()> {
+    catch_panic(clear_clipboard_contents).map_err(Error::from)
+}
+
+#[napi]
+pub fn capture_foreground_window() -> napi::Result<Option<String>> {
+    catch_panic(|| Ok(capture_foreground_handle())).map_err(Error::from)
+}
+
+#[napi]
+pub fn get_foreground_process_path() -> napi::Result<Option<String>> {
+    catch_panic(|| Ok(foreground_process_path())).map_err(Error::from)
+}
+
+#[napi(object)]
+pub struct Theme {
+    /// Apps are set to use the dark theme.
+    pub dark: bool,
+    pub high_contrast: bool,
+}
+
+#[napi]
+pub fn get_system_theme() -> napi::Result<Theme> {
+    catch_panic(|| Ok(theme::system_theme())).map_err(Error::from)
+}
+
+/// The window accent color as `0xAARRGGBB`, or `null` if it can't be read.
+#[napi]
+pub fn get_accent_color() -> napi::Result<Option<u32>> {
+    catch_panic(|| Ok(theme::accent_color())).map_err(Error::from)
+}
+
+/// Calls `callback` with the new theme when dark mode, high contrast or the
+/// accent color changes. Calling it again replaces the previous callback.
+#[napi]
+pub fn watch_theme_changes(callback: ThreadsafeFunction<Theme>) -> napi::Result<()> {
+    catch_panic(|| theme::watch_theme_changes(callback)).map_err(Error::from)
+}
+
+#[napi]
+pub fn unwatch_theme_changes() -> napi::Result<()> {
+    catch_panic(|| {
+        theme::unwatch_theme_changes();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+/// Opens the containing folder in Explorer with the file selected.
+#[napi]
+pub fn reveal_in_explorer(path: String) -> napi::Result<()> {
+    catch_panic(|| shell::reveal_in_explorer(&path)).map_err(Error::from)
+}
+
+/// Opens an `http`, `https`, `mailto`, `ms-settings` or `ms-windows-store` URL
+/// with its default handler; other schemes are rejected.
+#[napi]
+pub fn open_url(url: String) -> napi::Result<()> {
+    catch_panic(|| shell::open_url(&url)).map_err(Error::from)
+}
+
+#[napi(object)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// Full image path where permissions allow, otherwise the executable name.
+    pub exe_path: String,
+    pub parent_pid: u32,
 }
 
 #[napi]
-pub fn subscribe_clipboard(callback: ThreadsafeFunction<ClipboardItem>) -> napi::Result<()> {
-    start_clipboard_watcher(callback)
-        .map_err(|err| Error::new(Status::GenericFailure, err.to_string()))
+pub fn list_processes() -> napi::Result<Vec<ProcessInfo>> {
+    let processes = catch_panic(process::list_processes)?;
+    Ok(processes.into_iter().map(ProcessInfo::from).collect())
 }
 
+/// Ends a process. Unless `force` is set, its windows are asked to close first
+/// and it is only terminated if it hasn't exited after two seconds. Fails with
+/// `E_ACCESS_DENIED` for elevated processes when this one isn't.
 #[napi]
-pub fn unsubscribe_clipboard() {
-    stop_clipboard_watcher();
+pub async fn kill_process(pid: u32, force: bool) -> napi::Result<()> {
+    tokio::task::spawn_blocking(move || process::kill_process(pid, force))
+        .await
+        .map_err(CoreError::from)??;
+    Ok(())
+}
+
+#[napi(object)]
+pub struct CommandResult {
+    /// `null` when the process was killed after timing out.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
 }
 
+/// Runs a program without a console window and resolves with its output once
+/// it exits. With `timeout_ms`, the process and everything it started are
+/// killed when the time is up; background processes it leaves behind are ended
+/// once it exits.
 #[napi]
-pub fn capture_foreground_window() -> Option<String> {
-    capture_foreground_handle()
+pub async fn run_command(
+    exe: String,
+    args: Vec<String>,
+    cwd: Option<String>,
+    timeout_ms: Option<u32>,
+) -> napi::Result<CommandResult> {
+    let timeout = timeout_ms.map(|ms| std::time::Duration::from_millis(u64::from(ms)));
+    let result = tokio::task::spawn_blocking(move || {
+        process::run_command(&exe, &args, cwd.as_deref(), timeout)
+    })
+    .await
+    .map_err(CoreError::from)??;
+    Ok(result)
+}
+
+/// Whether this process runs elevated, e.g. to skip a redundant "run as admin".
+#[napi]
+pub fn is_elevated() -> napi::Result<bool> {
+    catch_panic(|| Ok(utils::is_elevated())).map_err(Error::from)
 }
 
 #[napi]
 pub fn focus_window(handle: String) -> napi::Result<()> {
-    focus_window_handle(&handle)
-        .map_err(|err| Error::new(Status::GenericFailure, err.to_string()))
+    catch_panic(|| focus_window_handle(&handle)).map_err(Error::from)
+}
+
+/// Flashes the window's taskbar button `count` times, then keeps it highlighted
+/// until the user switches to the window.
+#[napi]
+pub fn flash_window(handle: String, count: u32) -> napi::Result<()> {
+    catch_panic(|| flash_window_handle(&handle, count)).map_err(Error::from)
+}
+
+/// Keeps the window above all non-topmost windows (or releases it) without
+/// activating it.
+#[napi]
+pub fn set_window_topmost(handle: String, topmost: bool) -> napi::Result<()> {
+    catch_panic(|| apply_window_topmost(&handle, topmost)).map_err(Error::from)
+}
+
+#[napi]
+pub fn get_window_info(handle: String) -> napi::Result<Option<WindowInfo>> {
+    let details = catch_panic(|| window_details(&handle))?;
+
+    Ok(details.map(WindowInfo::from))
+}
+
+/// The top-level window under a screen point, e.g. the cursor position from
+/// `get_cursor_pos`.
+#[napi]
+pub fn window_from_point(x: i32, y: i32) -> napi::Result<Option<WindowInfo>> {
+    let details = catch_panic(|| input::window_from_point(x, y))?;
+
+    Ok(details.map(WindowInfo::from))
+}
+
+#[napi]
+pub fn watch_foreground_changes(callback: ThreadsafeFunction<WindowInfo>) -> napi::Result<()> {
+    catch_panic(|| watch_foreground(callback)).map_err(Error::from)
+}
+
+#[napi]
+pub fn unwatch_foreground_changes() -> napi::Result<()> {
+    catch_panic(|| {
+        unwatch_foreground();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn set_window_state(handle: String, state: WindowState) -> napi::Result<()> {
+    catch_panic(|| apply_window_state(&handle, state)).map_err(Error::from)
+}
+
+/// Omitted values fall back to the defaults (12ms focus settle, 35ms key delay).
+#[napi]
+pub fn set_input_timing(
+    focus_settle_ms: Option<u32>,
+    key_delay_ms: Option<u32>,
+) -> napi::Result<()> {
+    catch_panic(|| {
+        apply_input_timing(
+            focus_settle_ms.unwrap_or(DEFAULT_FOCUS_SETTLE_MS),
+            key_delay_ms.unwrap_or(DEFAULT_KEY_DELAY_MS),
+        );
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn get_idle_time_ms() -> napi::Result<u32> {
+    catch_panic(idle_time_ms).map_err(Error::from)
+}
+
+/// Virtual-key code (e.g. 0x10 for Shift) currently held down, whichever
+/// window has focus.
+#[napi]
+pub fn is_key_pressed(vk: u32) -> napi::Result<bool> {
+    catch_panic(|| input::key_pressed(vk)).map_err(Error::from)
+}
+
+#[napi(object)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub win: bool,
+}
+
+/// Modifier keys currently held down; left and right are not distinguished.
+#[napi]
+pub fn get_modifier_state() -> napi::Result<Modifiers> {
+    catch_panic(|| Ok(input::modifier_state())).map_err(Error::from)
+}
+
+#[napi(object)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The pointer position in physical virtual-screen pixels.
+#[napi]
+pub fn get_cursor_pos() -> napi::Result<Point> {
+    catch_panic(cursor_pos).map_err(Error::from)
+}
+
+/// Moves the pointer; fails with `E_INVALID_ARGUMENT` outside the virtual screen.
+#[napi]
+pub fn set_cursor_pos(x: i32, y: i32) -> napi::Result<()> {
+    catch_panic(|| input::set_cursor_pos(x, y)).map_err(Error::from)
+}
+
+#[napi]
+pub fn move_window_to_monitor(handle: String, monitor_index: u32) -> napi::Result<()> {
+    catch_panic(|| move_to_monitor(&handle, monitor_index)).map_err(Error::from)
 }
 
 #[napi]
 pub fn paste_clipboard() -> napi::Result<()> {
-    simulate_paste()
-        .map_err(|err| Error::new(Status::GenericFailure, err.to_string()))
+    catch_panic(simulate_paste).map_err(Error::from)
+}
+
+#[napi]
+pub fn send_hotkey(spec: String) -> napi::Result<()> {
+    catch_panic(|| send_key_combo(&spec)).map_err(Error::from)
+}
+
+/// Types `text` regardless of keyboard layout; line breaks are sent as Enter.
+/// Resolves once the last character has been sent.
+#[napi]
+pub async fn type_text(text: String, delay_ms: Option<u32>) -> napi::Result<()> {
+    let delay = delay_ms
+        .map(|ms| std::time::Duration::from_millis(u64::from(ms)))
+        .unwrap_or(DEFAULT_TYPE_DELAY);
+    tokio::task::spawn_blocking(move || type_unicode_text(&text, delay))
+        .await
+        .map_err(CoreError::from)??;
+    Ok(())
+}
+
+/// Holds the virtual keys down for `duration_ms` (at most 10s), then releases
+/// them in reverse order. Resolves once the keys are released.
+#[napi]
+pub async fn hold_keys(vks: Vec<u32>, duration_ms: u32) -> napi::Result<()> {
+    tokio::task::spawn_blocking(move || input::hold_keys(&vks, duration_ms))
+        .await
+        .map_err(CoreError::from)??;
+    Ok(())
+}
+
+#[napi]
+pub fn register_global_hotkey(
+    id: u32,
+    modifiers: u32,
+    vk: u32,
+    callback: ThreadsafeFunction<u32>,
+) -> napi::Result<()> {
+    catch_panic(|| register_hotkey(id, modifiers, vk, callback)).map_err(Error::from)
+}
+
+#[napi]
+pub fn unregister_global_hotkey(id: u32) -> napi::Result<()> {
+    catch_panic(|| unregister_hotkey(id)).map_err(Error::from)
+}
+
+#[napi]
+pub fn copy_clipboard() -> napi::Result<()> {
+    catch_panic(simulate_copy).map_err(Error::from)
+}
+
+#[napi]
+pub fn copy_selection(restore: bool) -> napi::Result<Option<String>> {
+    catch_panic(|| copy_selected_text(restore)).map_err(Error::from)
+}
+
+#[napi]
+pub fn paste_as_plain_text(restore: bool) -> napi::Result<()> {
+    catch_panic(|| paste_plain_text(restore)).map_err(Error::from)
+}
+
+#[napi(object)]
+pub struct LogRecord {
+    /// `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    /// Module that logged the record, e.g. `wolong_core::app_index`.
+    pub target: String,
+    pub message: String,
+}
+
+/// Forwards native log records to `callback`, starting at `info` level. The
+/// callback doesn't keep the process alive.
+#[napi]
+pub fn init_logging(env: Env, mut callback: ThreadsafeFunction<LogRecord>) -> napi::Result<()> {
+    callback.unref(&env)?;
+    catch_panic(|| {
+        logging::install(callback);
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+/// One of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+#[napi]
+pub fn set_log_level(level: String) -> napi::Result<()> {
+    catch_panic(|| logging::set_level(&level)).map_err(Error::from)
 }
 
 #[napi]
-pub fn version() -> String {
-    env!("CARGO_PKG_VERSION").to_string()
+pub fn version() -> napi::Result<String> {
+    catch_panic(|| Ok(env!("CARGO_PKG_VERSION").to_string())).map_err(Error::from)
+}
+
+#[napi(object)]
+pub struct BuildInfo {
+    pub version: String,
+    pub target_triple: String,
+    pub rustc_version: String,
+    /// Short commit hash, or `"unknown"` when built outside a git checkout.
+    pub git_sha: String,
+    /// Unix seconds; honours `SOURCE_DATE_EPOCH`.
+    pub build_timestamp: i64,
+}
+
+/// Identifies the exact native build, for bug reports.
+#[napi]
+pub fn build_info() -> napi::Result<BuildInfo> {
+    catch_panic(|| {
+        Ok(BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            target_triple: env!("WOLONG_TARGET").to_string(),
+            rustc_version: env!("WOLONG_RUSTC_VERSION").to_string(),
+            git_sha: env!("WOLONG_GIT_SHA").to_string(),
+            build_timestamp: env!("WOLONG_BUILD_TIMESTAMP").parse().unwrap_or(0),
+        })
+    })
+    .map_err(Error::from)
 }
 
 #[napi(object)]
@@ -120,21 +925,276 @@ pub struct ScanPaths {
     pub registry_paths: Vec<String>,
 }
 
+#[napi(string_enum)]
+pub enum KnownFolder {
+    /// Start Menu programs shared by all users.
+    CommonPrograms,
+    /// The current user's Start Menu programs.
+    Programs,
+    Desktop,
+    PublicDesktop,
+    Startup,
+    CommonStartup,
+    Documents,
+    Downloads,
+    LocalAppData,
+    RoamingAppData,
+    ProgramData,
+    ProgramFiles,
+    /// Shortcuts to recently opened files, as read by `scan_recent_documents`.
+    Recent,
+}
+
+/// Resolves a shell folder, following localized names and folder redirection.
 #[napi]
-pub fn get_default_scan_paths() -> ScanPaths {
+pub fn get_known_folder(folder: KnownFolder) -> napi::Result<Option<String>> {
+    catch_panic(|| Ok(utils::known_folder_path(folder))).map_err(Error::from)
+}
+
+/// The desktop app that opens `protocol` URLs, e.g. `http` for the default
+/// browser or `mailto` for the mail client. `null` when nothing is registered
+/// or the handler is a packaged (Store) app.
+#[napi]
+pub fn get_default_app_for(protocol: String) -> napi::Result<Option<AppInfo>> {
+    let record = catch_panic(|| app_index::default_app_for(&protocol))?;
+    Ok(record.map(AppInfo::from))
+}
+
+#[napi]
+pub fn get_default_scan_paths() -> napi::Result<ScanPaths> {
     use app_index::get_default_scan_paths as get_paths;
-    let (start_menu, registry) = get_paths();
-    ScanPaths {
+    let (start_menu, registry) = catch_panic(|| Ok(get_paths()))?;
+    Ok(ScanPaths {
         start_menu_paths: start_menu,
         registry_paths: registry,
-    }
+    })
+}
+
+/// Re-reads environment variables used to expand `%VAR%` paths, e.g. after
+/// changing `process.env`. `scan_apps` refreshes them itself.
+#[napi]
+pub fn refresh_env_cache() -> napi::Result<()> {
+    catch_panic(|| {
+        utils::refresh_env_cache();
+        Ok(())
+    })
+    .map_err(Error::from)
 }
 
 #[napi]
 pub fn extract_icon(icon_path: String) -> napi::Result<Option<Buffer>> {
-    match icon::extract_icon_data(&icon_path) {
+    match catch_panic(|| icon::extract_icon_data(&icon_path)) {
+        Ok(Some(data)) => Ok(Some(Buffer::from(data))),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[napi(object)]
+pub struct IconData {
+    pub png: Buffer,
+    pub width: u32,
+    pub height: u32,
+    pub was_upscaled: bool,
+}
+
+/// Like `extract_icon`, but reports the source resolution; without `size` the
+/// icon is returned at that resolution.
+#[napi]
+pub fn extract_icon_info(icon_path: String, size: Option<u32>) -> napi::Result<Option<IconData>> {
+    match catch_panic(|| icon::extract_icon_info(&icon_path, size)) {
+        Ok(Some(data)) => Ok(Some(IconData {
+            png: Buffer::from(data.png),
+            width: data.width,
+            height: data.height,
+            was_upscaled: data.was_upscaled,
+        })),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[napi(object)]
+pub struct FallbackIcon {
+    pub png: Buffer,
+    /// `primary`, `fallback` or `generic`: which candidate the icon came from.
+    pub source: String,
+}
+
+/// Tries `icon_path`, then the icon of `fallback_exe`, then the generic
+/// application icon.
+#[napi]
+pub fn extract_icon_with_fallback(
+    icon_path: String,
+    fallback_exe: Option<String>,
+    size: u32,
+) -> napi::Result<Option<FallbackIcon>> {
+    let icon = catch_panic(|| {
+        icon::extract_icon_with_fallback(&icon_path, fallback_exe.as_deref(), size)
+    })?;
+    Ok(icon.map(|(png, source)| FallbackIcon {
+        png: Buffer::from(png),
+        source: source.as_str().to_string(),
+    }))
+}
+
+#[napi]
+pub fn extract_icon_data_url(icon_path: String, size: u32) -> napi::Result<Option<String>> {
+    catch_panic(|| icon::extract_icon_data_url(&icon_path, size)).map_err(Error::from)
+}
+
+/// The icon's dominant color as `0xAARRGGBB`, ignoring transparent and
+/// near-white/near-black pixels; `null` when no color is left.
+#[napi]
+pub fn icon_dominant_color(icon_path: String) -> napi::Result<Option<u32>> {
+    catch_panic(|| icon::icon_dominant_color(&icon_path)).map_err(Error::from)
+}
+
+#[napi]
+pub fn extract_thumbnail(path: String, size: u32) -> napi::Result<Option<Buffer>> {
+    match catch_panic(|| icon::extract_thumbnail(&path, size)) {
+        Ok(Some(data)) => Ok(Some(Buffer::from(data))),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[napi]
+pub fn extract_uwp_logo(package_family_name: String, size: u32) -> napi::Result<Option<Buffer>> {
+    match catch_panic(|| icon::extract_uwp_logo(&package_family_name, size)) {
+        Ok(Some(data)) => Ok(Some(Buffer::from(data))),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[napi(object)]
+pub struct IconRequest {
+    pub icon_path: String,
+    pub icon_index: Option<i32>,
+    pub size: Option<u32>,
+    /// Crop transparent padding around the glyph so it fills more of `size`.
+    /// Icons without padding are left as they are.
+    pub auto_trim: Option<bool>,
+    /// Pixels of the output kept around a trimmed glyph (default 0).
+    pub trim_margin: Option<u32>,
+}
+
+#[napi(object)]
+pub struct IconResult {
+    pub ok: bool,
+    pub png: Option<Buffer>,
+    pub error: Option<String>,
+}
+
+/// Results are in the same order as `requests`.
+#[napi]
+pub async fn extract_icons(requests: Vec<IconRequest>) -> napi::Result<Vec<IconResult>> {
+    let jobs: Vec<icon::IconJob> = requests
+        .into_iter()
+        .map(|request| icon::IconJob {
+            icon_path: request.icon_path,
+            icon_index: request.icon_index,
+            size: request.size.unwrap_or(icon::ICON_SIZE as u32),
+            trim: request
+                .auto_trim
+                .unwrap_or(false)
+                .then(|| request.trim_margin.unwrap_or(0)),
+        })
+        .collect();
+
+    let results = tokio::task::spawn_blocking(move || icon::extract_icon_batch(&jobs))
+        .await
+        .map_err(CoreError::from)?;
+
+    Ok(results
+        .into_iter()
+        .map(|result| match result {
+            Ok(png) => IconResult {
+                ok: true,
+                png: png.map(Buffer::from),
+                error: None,
+            },
+            Err(err) => IconResult {
+                ok: false,
+                png: None,
+                error: Some(format!("{}: {err}", err.code())),
+            },
+        })
+        .collect())
+}
+
+/// Scans like `scan_apps`, then extracts every app's icon at `icon_size` into
+/// the icon cache with at most `concurrency` extractions in flight, so later
+/// `extract_icon_*` calls at that size are cache hits. Resolves once all icons
+/// are cached; icons that fail to extract are skipped.
+#[napi]
+pub async fn warm_index(
+    paths: ScanPaths,
+    icon_size: u32,
+    concurrency: u32,
+) -> napi::Result<Vec<AppInfo>> {
+    let ScanPaths {
+        start_menu_paths,
+        registry_paths,
+    } = paths;
+    let records =
+        tokio::task::spawn_blocking(move || scan_app_records(&start_menu_paths, &registry_paths))
+            .await
+            .map_err(CoreError::from)??;
+
+    let mut icon_paths: Vec<String> = records
+        .iter()
+        .filter_map(|record| record.icon_path.clone())
+        .collect();
+    icon_paths.sort_unstable();
+    icon_paths.dedup();
+
+    let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrency.max(1) as usize));
+    let mut tasks = Vec::with_capacity(icon_paths.len());
+    for icon_path in icon_paths {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| CoreError::Other(anyhow::anyhow!("icon semaphore closed: {err}")))?;
+        tasks.push(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            if let Err(err) = icon::extract_icon_sized(&icon_path, None, icon_size) {
+                log::debug!("warming icon {icon_path:?} failed: {err}");
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.map_err(CoreError::from)?;
+    }
+
+    Ok(records.into_iter().map(AppInfo::from).collect())
+}
+
+#[napi]
+pub fn clear_icon_cache() -> napi::Result<()> {
+    catch_panic(|| {
+        icon::clear_icon_cache();
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn set_icon_cache_capacity(capacity: u32) -> napi::Result<()> {
+    catch_panic(|| {
+        icon::set_icon_cache_capacity(capacity as usize);
+        Ok(())
+    })
+    .map_err(Error::from)
+}
+
+#[napi]
+pub fn extract_extension_icon(extension: String, size: u32) -> napi::Result<Option<Buffer>> {
+    match catch_panic(|| icon::extract_extension_icon(&extension, size)) {
         Ok(Some(data)) => Ok(Some(Buffer::from(data))),
         Ok(None) => Ok(None),
-        Err(e) => Err(Error::new(Status::GenericFailure, e.to_string())),
+        Err(e) => Err(e.into()),
     }
 }
@@ -0,0 +1,57 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+
+use crate::{
+    error::{CoreError, CoreResult},
+    LogRecord,
+};
+
+/// Forwards `log` records to the JS callback registered with `install`.
+struct JsLogger;
+
+static LOGGER: JsLogger = JsLogger;
+static SINK: Lazy<RwLock<Option<ThreadsafeFunction<LogRecord>>>> = Lazy::new(|| RwLock::new(None));
+
+impl Log for JsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && SINK.read().is_some()
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() > log::max_level() {
+            return;
+        }
+        if let Some(callback) = SINK.read().as_ref() {
+            let entry = LogRecord {
+                level: record.level().as_str().to_ascii_lowercase(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            };
+            let _ = callback.call(Ok(entry), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Routes native log output to `callback`, replacing any earlier sink. Logging
+/// starts at `info` unless `set_level` already chose a level.
+pub fn install(callback: ThreadsafeFunction<LogRecord>) {
+    *SINK.write() = Some(callback);
+    // Only the first call can register; later ones just swap the sink.
+    if log::set_logger(&LOGGER).is_ok() && log::max_level() == LevelFilter::Off {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// Accepts `off`, `error`, `warn`, `info`, `debug` or `trace`.
+pub fn set_level(level: &str) -> CoreResult<()> {
+    let filter = level
+        .trim()
+        .parse::<LevelFilter>()
+        .map_err(|_| CoreError::InvalidArgument(format!("unknown log level '{level}'")))?;
+    log::set_max_level(filter);
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use windows::Win32::{
+    Foundation::{BOOL, HWND, LPARAM, RECT},
+    Graphics::Gdi::{
+        EnumDisplayMonitors, GetMonitorInfoW, MonitorFromWindow, HDC, HMONITOR, MONITORINFO,
+        MONITORINFOEXW, MONITOR_FROM_FLAGS,
+    },
+    UI::{
+        HiDpi::{
+            GetDpiForMonitor, SetProcessDpiAwarenessContext, SetThreadDpiAwarenessContext,
+            DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, MDT_EFFECTIVE_DPI,
+        },
+        WindowsAndMessaging::{
+            GetSystemMetrics, MONITORINFOF_PRIMARY, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN,
+            SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+        },
+    },
+};
+
+use crate::{
+    error::{CoreError, CoreResult},
+    utils::string_from_wide,
+    MonitorInfo, Rect,
+};
+
+pub(crate) const MONITOR_DEFAULTTONEAREST: MONITOR_FROM_FLAGS = MONITOR_FROM_FLAGS(2);
+
+/// Bounds of the desktop spanning all monitors; the origin is negative when a
+/// monitor sits left of or above the primary one.
+pub(crate) fn virtual_screen_rect() -> RECT {
+    unsafe {
+        let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        RECT {
+            left,
+            top,
+            right: left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            bottom: top + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        }
+    }
+}
+
+/// One attached display. Rectangles are in virtual-screen coordinates, in
+/// physical pixels when the calling thread is per-monitor DPI aware.
+pub struct MonitorArea {
+    /// Position in `enumerate_monitors` order.
+    pub index: u32,
+    pub bounds: RECT,
+    /// Monitor bounds minus the taskbar and docked app bars.
+    pub work_area: RECT,
+    pub primary: bool,
+    pub scale_factor: f64,
+    /// Display device name such as `\\.\DISPLAY1`.
+    pub device_name: String,
+}
+
+impl From<RECT> for Rect {
+    fn from(rect: RECT) -> Self {
+        Rect {
+            x: rect.left,
+            y: rect.top,
+            width: (rect.right - rect.left).max(0) as u32,
+            height: (rect.bottom - rect.top).max(0) as u32,
+        }
+    }
+}
+
+impl From<MonitorArea> for MonitorInfo {
+    fn from(area: MonitorArea) -> Self {
+        MonitorInfo {
+            index: area.index,
+            bounds: area.bounds.into(),
+            work_area: area.work_area.into(),
+            primary: area.primary,
+            scale_factor: area.scale_factor,
+            device_name: area.device_name,
+        }
+    }
+}
+
+/// Opts the process into per-monitor (v2) DPI awareness so coordinates and
+/// captures are in physical pixels. Fails harmlessly when the host has already
+/// chosen an awareness mode.
+pub fn enable_dpi_awareness() {
+    unsafe {
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+/// Makes the current thread per-monitor DPI aware until dropped, for hosts
+/// whose process-wide mode couldn't be changed.
+pub(crate) struct DpiAwarenessScope(DPI_AWARENESS_CONTEXT);
+
+impl DpiAwarenessScope {
+    pub(crate) fn per_monitor() -> Self {
+        Self(unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) })
+    }
+}
+
+impl Drop for DpiAwarenessScope {
+    fn drop(&mut self) {
+        if self.0 .0 != 0 {
+            unsafe {
+                SetThreadDpiAwarenessContext(self.0);
+            }
+        }
+    }
+}
+
+pub(crate) fn monitor_scale_factor(monitor: HMONITOR) -> f64 {
+    let (mut dpi_x, mut dpi_y) = (0u32, 0u32);
+    match unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) } {
+        Ok(()) if dpi_x > 0 => f64::from(dpi_x) / 96.0,
+        _ => 1.0,
+    }
+}
+
+/// Lists the attached monitors in the order Windows enumerates them.
+pub fn enumerate_monitors() -> CoreResult<Vec<MonitorArea>> {
+    monitor_handles()?
+        .into_iter()
+        .enumerate()
+        .map(|(index, monitor)| monitor_area(monitor, index as u32))
+        .collect()
+}
+
+/// Monitor handles in the order `enumerate_monitors` reports them.
+pub(crate) fn monitor_handles() -> CoreResult<Vec<HMONITOR>> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        let data = LPARAM(&mut handles as *mut Vec<HMONITOR> as isize);
+        if !EnumDisplayMonitors(HDC(0), None, Some(collect_monitor), data).as_bool() {
+            return Err(CoreError::from_win32("EnumDisplayMonitors failed"));
+        }
+    }
+    Ok(handles)
+}
+
+unsafe extern "system" fn collect_monitor(
+    monitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    data: LPARAM,
+) -> BOOL {
+    let handles = &mut *(data.0 as *mut Vec<HMONITOR>);
+    handles.push(monitor);
+    true.into()
+}
+
+/// The monitor that contains most of `hwnd`.
+pub fn window_monitor(hwnd: HWND) -> CoreResult<MonitorArea> {
+    let monitor = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) };
+    if monitor.0 == 0 {
+        return Err(CoreError::from_win32("MonitorFromWindow failed"));
+    }
+    let index = monitor_handles()?
+        .iter()
+        .position(|handle| *handle == monitor)
+        .map_or(u32::MAX, |index| index as u32);
+    monitor_area(monitor, index)
+}
+
+/// Full bounds of `monitor` in virtual-screen coordinates.
+pub(crate) fn monitor_bounds(monitor: HMONITOR) -> CoreResult<RECT> {
+    let mut info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe {
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            return Err(CoreError::from_win32("GetMonitorInfoW failed"));
+        }
+    }
+    Ok(info.rcMonitor)
+}
+
+fn monitor_area(monitor: HMONITOR, index: u32) -> CoreResult<MonitorArea> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+    unsafe {
+        if !GetMonitorInfoW(monitor, &mut info as *mut _ as *mut MONITORINFO).as_bool() {
+            return Err(CoreError::from_win32("GetMonitorInfoW failed"));
+        }
+    }
+
+    Ok(MonitorArea {
+        index,
+        bounds: info.monitorInfo.rcMonitor,
+        work_area: info.monitorInfo.rcWork,
+        primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+        scale_factor: monitor_scale_factor(monitor),
+        device_name: string_from_wide(&info.szDevice).unwrap_or_default(),
+    })
+}
+
+/// Every attached monitor with its bounds, work area and scale, in physical pixels.
+pub fn monitor_layout() -> CoreResult<Vec<MonitorArea>> {
+    let _dpi = DpiAwarenessScope::per_monitor();
+    enumerate_monitors()
+}
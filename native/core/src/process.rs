@@ -0,0 +1,322 @@
+use std::{
+    ffi::c_void,
+    io::Read,
+    mem,
+    os::windows::{io::AsRawHandle, process::CommandExt},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{
+            CloseHandle, BOOL, ERROR_INVALID_PARAMETER, E_ACCESSDENIED, HANDLE, HWND, LPARAM,
+            WAIT_OBJECT_0, WPARAM,
+        },
+        System::{
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+                TH32CS_SNAPPROCESS,
+            },
+            JobObjects::{
+                AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+                SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            },
+            Threading::{
+                GetCurrentProcessId, OpenProcess, TerminateProcess, WaitForSingleObject,
+                PROCESS_ACCESS_RIGHTS, PROCESS_SYNCHRONIZE, PROCESS_TERMINATE,
+            },
+        },
+        UI::WindowsAndMessaging::{
+            EnumWindows, GetWindowThreadProcessId, IsWindowVisible, PostMessageW, WM_CLOSE,
+        },
+    },
+};
+
+use crate::{
+    error::{CoreError, CoreResult},
+    utils::{is_elevated, process_image_path, string_from_wide},
+    CommandResult, ProcessInfo,
+};
+
+/// Keeps console tools from flashing a console window.
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long a process gets to exit after `WM_CLOSE` before it is terminated.
+const GRACEFUL_EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub name: String,
+    /// Full image path where we may query it, otherwise the executable name.
+    pub exe_path: String,
+    pub parent_pid: u32,
+}
+
+impl From<ProcessEntry> for ProcessInfo {
+    fn from(entry: ProcessEntry) -> Self {
+        ProcessInfo {
+            pid: entry.pid,
+            name: entry.name,
+            exe_path: entry.exe_path,
+            parent_pid: entry.parent_pid,
+        }
+    }
+}
+
+/// Snapshot of the processes running in any session.
+pub fn list_processes() -> CoreResult<Vec<ProcessEntry>> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }
+        .map_err(|err| CoreError::from_hresult("CreateToolhelp32Snapshot failed", err.code()))?;
+
+    let mut entry = PROCESSENTRY32W {
+        dwSize: size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+    let mut processes = Vec::new();
+    let mut next = unsafe { Process32FirstW(snapshot, &mut entry) };
+    while next.is_ok() {
+        // The System Idle Process has no name or image.
+        if entry.th32ProcessID != 0 {
+            let name = string_from_wide(&entry.szExeFile).unwrap_or_default();
+            processes.push(ProcessEntry {
+                pid: entry.th32ProcessID,
+                exe_path: process_image_path(entry.th32ProcessID).unwrap_or_else(|| name.clone()),
+                name,
+                parent_pid: entry.th32ParentProcessID,
+            });
+        }
+        next = unsafe { Process32NextW(snapshot, &mut entry) };
+    }
+
+    unsafe {
+        let _ = CloseHandle(snapshot);
+    }
+    Ok(processes)
+}
+
+/// Ends a process. Without `force`, its visible top-level windows are sent
+/// `WM_CLOSE` first (so it can save state) and it is only terminated if it is
+/// still running after `GRACEFUL_EXIT_TIMEOUT`.
+pub fn kill_process(pid: u32, force: bool) -> CoreResult<()> {
+    if pid == 0 || pid == unsafe { GetCurrentProcessId() } {
+        return Err(CoreError::InvalidArgument(format!(
+            "refusing to end process {pid}"
+        )));
+    }
+
+    let process = open_process(pid, PROCESS_TERMINATE | PROCESS_SYNCHRONIZE)?;
+    let result = end_process(process, pid, force);
+    unsafe {
+        let _ = CloseHandle(process);
+    }
+    result
+}
+
+fn end_process(process: HANDLE, pid: u32, force: bool) -> CoreResult<()> {
+    if !force && close_windows_of(pid) {
+        let timeout = GRACEFUL_EXIT_TIMEOUT.as_millis() as u32;
+        if unsafe { WaitForSingleObject(process, timeout) } == WAIT_OBJECT_0 {
+            return Ok(());
+        }
+    }
+    unsafe { TerminateProcess(process, 1) }
+        .map_err(|err| CoreError::from_hresult("TerminateProcess failed", err.code()))
+}
+
+fn open_process(pid: u32, access: PROCESS_ACCESS_RIGHTS) -> CoreResult<HANDLE> {
+    unsafe { OpenProcess(access, false, pid) }.map_err(|err| {
+        if err.code() == E_ACCESSDENIED {
+            let hint = if is_elevated() {
+                "it is a protected or system process"
+            } else {
+                "it runs elevated or as another user; run as administrator to end it"
+            };
+            CoreError::AccessDenied(format!("cannot end process {pid}: {hint}"))
+        } else if err.code() == ERROR_INVALID_PARAMETER.to_hresult() {
+            CoreError::NotFound(format!("process {pid} not found"))
+        } else {
+            CoreError::from_hresult("OpenProcess failed", err.code())
+        }
+    })
+}
+
+/// Posts `WM_CLOSE` to the process's visible top-level windows; false if it
+/// has none.
+fn close_windows_of(pid: u32) -> bool {
+    struct Search {
+        pid: u32,
+        closed: bool,
+    }
+
+    unsafe extern "system" fn visit(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam.0 as *mut Search);
+        let mut owner = 0u32;
+        GetWindowThreadProcessId(hwnd, Some(&mut owner));
+        if owner == search.pid
+            && IsWindowVisible(hwnd).as_bool()
+            && PostMessageW(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0)).is_ok()
+        {
+            search.closed = true;
+        }
+        BOOL(1)
+    }
+
+    let mut search = Search { pid, closed: false };
+    unsafe {
+        let _ = EnumWindows(Some(visit), LPARAM(&mut search as *mut Search as isize));
+    }
+    search.closed
+}
+
+/// Runs `exe` to completion and collects its output, killing it if it outlives
+/// `timeout`. Output is decoded as UTF-8, replacing invalid sequences.
+///
+/// Processes it starts are ended along with it, so one left running can't hold
+/// the output pipes open and keep this from returning.
+pub fn run_command(
+    exe: &str,
+    args: &[String],
+    cwd: Option<&str>,
+    timeout: Option<Duration>,
+) -> CoreResult<CommandResult> {
+    let mut command = Command::new(exe);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .creation_flags(CREATE_NO_WINDOW);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command.spawn().map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => CoreError::NotFound(format!("'{exe}' not found")),
+        _ => CoreError::Io(err),
+    })?;
+
+    // Anything the child starts before it is assigned escapes the job; that
+    // window is the few instructions between spawn and here.
+    let job = Job::kill_on_close()
+        .and_then(|job| job.assign(&child).map(|()| job))
+        .map_err(|err| log::debug!("run_command without a job object: {err}"))
+        .ok();
+
+    // Drain both pipes concurrently so a chatty child can't block on a full pipe.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let (status, timed_out) = wait_with_timeout(&mut child, timeout, job.as_ref())?;
+    // Closing the job ends leftover descendants, which closes their pipe handles.
+    drop(job);
+    Ok(CommandResult {
+        exit_code: status,
+        stdout: String::from_utf8_lossy(&stdout.join().unwrap_or_default()).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr.join().unwrap_or_default()).into_owned(),
+        timed_out,
+    })
+}
+
+/// A job object that ends every process in it when the handle closes.
+struct Job(HANDLE);
+
+impl Job {
+    fn kill_on_close() -> CoreResult<Self> {
+        let handle = unsafe { CreateJobObjectW(None, PCWSTR::null()) }
+            .map_err(|err| CoreError::from_hresult("CreateJobObjectW failed", err.code()))?;
+        let job = Job(handle);
+
+        let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const c_void,
+                mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        }
+        .map_err(|err| CoreError::from_hresult("SetInformationJobObject failed", err.code()))?;
+        Ok(job)
+    }
+
+    fn assign(&self, child: &Child) -> CoreResult<()> {
+        let process = HANDLE(child.as_raw_handle() as isize);
+        unsafe { AssignProcessToJobObject(self.0, process) }
+            .map_err(|err| CoreError::from_hresult("AssignProcessToJobObject failed", err.code()))
+    }
+
+    fn terminate(&self) {
+        unsafe {
+            let _ = TerminateJobObject(self.0, 1);
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// The exit code (`None` if the child was killed) and whether it timed out.
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    job: Option<&Job>,
+) -> CoreResult<(Option<i32>, bool)> {
+    let Some(timeout) = timeout else {
+        return Ok((child.wait()?.code(), false));
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status.code(), false));
+        }
+        if Instant::now() >= deadline {
+            if let Some(job) = job {
+                job.terminate();
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok((None, true));
+        }
+        thread::sleep(COMMAND_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Spawns real processes; run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn run_command_timeout_ends_grandchildren_holding_the_pipes() {
+        // `start /B` leaves a ping running that inherits cmd's stdout.
+        let args = ["/C", "start /B ping -n 30 127.0.0.1 & ping -n 30 127.0.0.1"].map(String::from);
+        let started = Instant::now();
+        let result = run_command("cmd", &args, None, Some(Duration::from_millis(500))).unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.exit_code, None);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use napi::{
+    bindgen_prelude::ToNapiValue,
+    sys,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::{
+    dxgi::MonitorCapturer,
+    error::{CoreError, CoreResult},
+    screenshot::{recycle_buffer, release_buffers},
+    RawImage,
+};
+
+/// Frame rates above this only burn CPU copying frames JS cannot keep up with.
+const MAX_STREAM_FPS: u32 = 60;
+
+static SCREEN_STREAM: Lazy<Mutex<Option<ScreenStream>>> = Lazy::new(|| Mutex::new(None));
+
+struct ScreenStream {
+    shutdown: Arc<AtomicBool>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl ScreenStream {
+    fn stop(self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.handle.join().ok();
+        // The stream is the pool's main user; don't keep its frames around.
+        release_buffers();
+    }
+}
+
+/// A frame queued for the JS callback.
+///
+/// Threadsafe function queues are unbounded, so a slow consumer would otherwise
+/// pile up full-screen buffers. Converting the frame happens on the JS thread
+/// right before the callback runs, which is where the stream learns it may
+/// queue the next one.
+pub struct StreamFrame {
+    image: RawImage,
+    pending: Arc<AtomicBool>,
+}
+
+impl ToNapiValue for StreamFrame {
+    unsafe fn to_napi_value(env: sys::napi_env, frame: Self) -> napi::Result<sys::napi_value> {
+        frame.pending.store(false, Ordering::Release);
+        RawImage::to_napi_value(env, frame.image)
+    }
+}
+
+/// Streams changed frames of one monitor to `callback` at up to `fps` frames
+/// per second, replacing any running stream. Frames captured while the previous
+/// one is still waiting for JS are dropped.
+pub fn start_screen_stream(
+    monitor_index: u32,
+    fps: u32,
+    callback: ThreadsafeFunction<StreamFrame>,
+) -> CoreResult<()> {
+    if fps == 0 {
+        return Err(CoreError::InvalidArgument(
+            "fps must be at least 1".to_string(),
+        ));
+    }
+    let interval = Duration::from_secs(1) / fps.min(MAX_STREAM_FPS);
+
+    let mut guard = SCREEN_STREAM.lock();
+    if let Some(stream) = guard.take() {
+        stream.stop();
+    }
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_flag = Arc::clone(&shutdown);
+    let (ready, started) = mpsc::channel();
+    let handle = thread::Builder::new()
+        .name("wolong-screen-stream".to_string())
+        .spawn(move || {
+            // The D3D objects behind the capturer must stay on the thread that created them.
+            let capturer = match MonitorCapturer::new(monitor_index as usize) {
+                Ok(capturer) => capturer,
+                Err(err) => {
+                    let _ = ready.send(Err(err));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+            run_screen_stream(capturer, interval, callback, &shutdown_flag);
+        })
+        .map_err(|err| CoreError::Other(anyhow::anyhow!("spawn screen stream failed: {err}")))?;
+    if let Err(err) = started
+        .recv()
+        .map_err(|_| CoreError::Other(anyhow::anyhow!("screen stream exited during startup")))
+        .and_then(|started| started)
+    {
+        handle.join().ok();
+        return Err(err);
+    }
+
+    *guard = Some(ScreenStream { shutdown, handle });
+    Ok(())
+}
+
+pub fn stop_screen_stream() {
+    if let Some(stream) = SCREEN_STREAM.lock().take() {
+        stream.stop();
+    }
+}
+
+fn run_screen_stream(
+    mut capturer: MonitorCapturer,
+    interval: Duration,
+    callback: ThreadsafeFunction<StreamFrame>,
+    shutdown: &AtomicBool,
+) {
+    let pending = Arc::new(AtomicBool::new(false));
+    let timeout_ms = interval.as_millis() as u32;
+
+    while !shutdown.load(Ordering::SeqCst) {
+        let started = Instant::now();
+        // Desktop duplication blocks until the screen changes (or the frame
+        // interval passes); the GDI fallback returns straight away.
+        match capturer.next_frame(timeout_ms) {
+            Ok(Some(frame)) if pending.load(Ordering::Acquire) => recycle_buffer(frame.rgba),
+            Ok(Some(frame)) => {
+                pending.store(true, Ordering::Release);
+                let frame = StreamFrame {
+                    image: frame.into(),
+                    pending: Arc::clone(&pending),
+                };
+                let status = callback.call(Ok(frame), ThreadsafeFunctionCallMode::NonBlocking);
+                if status != napi::Status::Ok {
+                    pending.store(false, Ordering::Release);
+                }
+            }
+            Ok(None) => {}
+            Err(err) => log::warn!("screen stream frame failed: {err}"),
+        }
+
+        // Also caps the rate when duplication hands frames over early.
+        if let Some(remaining) = interval.checked_sub(started.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
@@ -1,21 +1,76 @@
-use std::mem::size_of;
+use std::{mem::size_of, time::Duration};
 
 use anyhow::Context;
-use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    ColorType, ImageEncoder,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use windows::Win32::{
-    Foundation::{HWND, POINT},
+    Foundation::{HWND, POINT, RECT},
     Graphics::Gdi::{
-        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC,
-        GetDIBits, GetMonitorInfoW, MonitorFromPoint, ReleaseDC, SelectObject, BITMAPINFO,
-        BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, MONITORINFO, MONITORINFOEXW, MONITOR_FROM_FLAGS,
-        RGBQUAD, SRCCOPY,
+        BitBlt, ClientToScreen, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject,
+        GetDC, GetDIBits, GetMonitorInfoW, MonitorFromPoint, MonitorFromRect, ReleaseDC,
+        SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HDC, HMONITOR,
+        MONITORINFO, MONITORINFOEXW, RGBQUAD, SRCCOPY,
+    },
+    Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS, PW_CLIENTONLY},
+    UI::WindowsAndMessaging::{
+        DrawIconEx, GetClientRect, GetCursorInfo, GetCursorPos, GetIconInfo, GetWindowRect,
+        IsWindow, CURSORINFO, CURSOR_SHOWING, DI_NORMAL, HICON, ICONINFO, PW_RENDERFULLCONTENT,
     },
-    UI::WindowsAndMessaging::GetCursorPos,
 };
 
-const MONITOR_DEFAULTTONEAREST: MONITOR_FROM_FLAGS = MONITOR_FROM_FLAGS(2);
-
 use crate::error::{CoreError, CoreResult};
+use crate::input::parse_window_handle;
+use crate::monitor::{
+    monitor_handles, monitor_scale_factor, virtual_screen_rect, DpiAwarenessScope,
+    MONITOR_DEFAULTTONEAREST,
+};
+use crate::utils::string_from_wide;
+use crate::{ImageFormat, RawImage, ScreenshotOptions, ScreenshotPayload};
+
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+/// Total capacity the pool may keep: two 4K frames (about 33 MB each).
+const MAX_POOLED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Pixel buffers of finished captures, handed back out by `take_buffer` so
+/// back-to-back captures (e.g. a screen stream) reuse their allocations.
+static BUFFER_POOL: Lazy<Mutex<Vec<Vec<u8>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// A zeroed buffer of `len` bytes, reusing the smallest pooled allocation that
+/// is big enough so larger ones stay available for larger captures.
+pub(crate) fn take_buffer(len: usize) -> Vec<u8> {
+    let pooled = {
+        let mut pool = BUFFER_POOL.lock();
+        pool.iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.capacity() >= len)
+            .min_by_key(|(_, buffer)| buffer.capacity())
+            .map(|(index, _)| index)
+            .map(|index| pool.swap_remove(index))
+    };
+    let mut buffer = pooled.unwrap_or_default();
+    buffer.clear();
+    buffer.resize(len, 0);
+    buffer
+}
+
+/// Returns a buffer from `take_buffer` once its pixels are no longer needed.
+/// Buffers that would push the pool past `MAX_POOLED_BYTES` are freed instead.
+pub(crate) fn recycle_buffer(buffer: Vec<u8>) {
+    let mut pool = BUFFER_POOL.lock();
+    let pooled: usize = pool.iter().map(Vec::capacity).sum();
+    if pooled + buffer.capacity() <= MAX_POOLED_BYTES {
+        pool.push(buffer);
+    }
+}
+
+/// Frees every pooled buffer, e.g. once a screen stream stops.
+pub(crate) fn release_buffers() {
+    BUFFER_POOL.lock().clear();
+}
 
 pub struct ScreenshotResult {
     pub width: u32,
@@ -23,9 +78,163 @@ pub struct ScreenshotResult {
     pub origin_x: i32,
     pub origin_y: i32,
     pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+    pub scale_factor: f64,
+    pub monitor: MonitorIdentity,
+}
+
+/// Which display a capture came from.
+#[derive(Clone)]
+pub struct MonitorIdentity {
+    /// Position in `enumerate_monitors` order; `u32::MAX` for the whole virtual screen.
+    pub index: u32,
+    /// GDI device name such as `\\.\DISPLAY1`, or `"virtual"`.
+    pub device_name: String,
+}
+
+impl MonitorIdentity {
+    fn virtual_screen() -> Self {
+        Self {
+            index: u32::MAX,
+            device_name: "virtual".to_string(),
+        }
+    }
+}
+
+pub(crate) fn monitor_identity(monitor: HMONITOR) -> MonitorIdentity {
+    let index = monitor_handles()
+        .ok()
+        .and_then(|handles| handles.iter().position(|handle| *handle == monitor))
+        .map_or(u32::MAX, |index| index as u32);
+
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+    let device_name = unsafe {
+        if GetMonitorInfoW(monitor, &mut info as *mut _ as *mut MONITORINFO).as_bool() {
+            string_from_wide(&info.szDevice).unwrap_or_default()
+        } else {
+            String::new()
+        }
+    };
+
+    MonitorIdentity { index, device_name }
 }
 
-pub fn capture_active_monitor() -> CoreResult<ScreenshotResult> {
+/// Captured pixels before encoding, as top-down RGBA.
+pub struct RawCapture {
+    pub width: u32,
+    pub height: u32,
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub rgba: Vec<u8>,
+    /// Display scale of the monitor the capture is (mostly) on, e.g. 1.5 at 144 DPI.
+    pub scale_factor: f64,
+    pub monitor: MonitorIdentity,
+}
+
+impl RawCapture {
+    fn encode(self, options: &CaptureOptions) -> CoreResult<ScreenshotResult> {
+        let encoded = encode_image(&self.rgba, self.width, self.height, options);
+        recycle_buffer(self.rgba);
+        let (bytes, mime_type) = encoded?;
+        Ok(ScreenshotResult {
+            width: self.width,
+            height: self.height,
+            origin_x: self.origin_x,
+            origin_y: self.origin_y,
+            bytes,
+            mime_type,
+            scale_factor: self.scale_factor,
+            monitor: self.monitor,
+        })
+    }
+}
+
+impl From<RawCapture> for RawImage {
+    fn from(capture: RawCapture) -> Self {
+        Self {
+            width: capture.width,
+            height: capture.height,
+            origin_x: capture.origin_x,
+            origin_y: capture.origin_y,
+            buffer: capture.rgba.into(),
+            scale_factor: capture.scale_factor,
+            monitor_index: capture.monitor.index,
+            device_name: capture.monitor.device_name,
+        }
+    }
+}
+
+impl From<ScreenshotResult> for ScreenshotPayload {
+    fn from(result: ScreenshotResult) -> Self {
+        Self {
+            width: result.width,
+            height: result.height,
+            x: result.origin_x,
+            y: result.origin_y,
+            buffer: result.bytes.into(),
+            mime_type: result.mime_type.to_string(),
+            scale_factor: result.scale_factor,
+            monitor_index: result.monitor.index,
+            device_name: result.monitor.device_name,
+        }
+    }
+}
+
+pub struct CaptureOptions {
+    /// Composite the mouse cursor into the capture.
+    pub include_cursor: bool,
+    pub format: ImageFormat,
+    /// JPEG quality, 1-100. WebP output is always lossless.
+    pub quality: u8,
+    /// Capture the monitor's work area (without the taskbar) instead of all of it.
+    pub work_area_only: bool,
+    /// How long the async entry points wait before capturing.
+    pub delay: Duration,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            include_cursor: false,
+            format: ImageFormat::Png,
+            quality: DEFAULT_JPEG_QUALITY,
+            work_area_only: false,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+impl From<ScreenshotOptions> for CaptureOptions {
+    fn from(options: ScreenshotOptions) -> Self {
+        Self {
+            include_cursor: options.include_cursor.unwrap_or(false),
+            format: options.format.unwrap_or(ImageFormat::Png),
+            quality: options
+                .quality
+                .unwrap_or(DEFAULT_JPEG_QUALITY)
+                .clamp(1, 100),
+            work_area_only: options.work_area_only.unwrap_or(false),
+            delay: Duration::from_millis(options.delay_ms.map_or(0, u64::from)),
+        }
+    }
+}
+
+pub fn capture_active_monitor(options: &CaptureOptions) -> CoreResult<ScreenshotResult> {
+    let _dpi = DpiAwarenessScope::per_monitor();
+    let rect = active_monitor_rect(options.work_area_only)?;
+    unsafe { capture_screen_area(&rect, options)?.encode(options) }
+}
+
+/// Like `capture_active_monitor`, but hands back the RGBA pixels unencoded.
+pub fn capture_monitor_raw() -> CoreResult<RawCapture> {
+    let _dpi = DpiAwarenessScope::per_monitor();
+    let rect = active_monitor_rect(false)?;
+    unsafe { capture_screen_area(&rect, &CaptureOptions::default()) }
+}
+
+/// Bounds of the monitor under the cursor, or just its work area.
+fn active_monitor_rect(work_area_only: bool) -> CoreResult<RECT> {
     unsafe {
         let mut cursor = POINT::default();
         GetCursorPos(&mut cursor).map_err(|_| CoreError::from_win32("GetCursorPos failed"))?;
@@ -41,9 +250,13 @@ pub fn capture_active_monitor() -> CoreResult<ScreenshotResult> {
             return Err(CoreError::from_win32("GetMonitorInfoW failed"));
         }
 
-        let rect = info.monitorInfo.rcMonitor;
-        let width = (rect.right - rect.left) as i32;
-        let height = (rect.bottom - rect.top) as i32;
+        let rect = if work_area_only {
+            info.monitorInfo.rcWork
+        } else {
+            info.monitorInfo.rcMonitor
+        };
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
 
         if width <= 0 || height <= 0 {
             return Err(CoreError::Other(anyhow::anyhow!(
@@ -51,100 +264,590 @@ pub fn capture_active_monitor() -> CoreResult<ScreenshotResult> {
             )));
         }
 
-        let screen_dc = GetDC(HWND(0));
-        if screen_dc.0 == 0 {
-            return Err(CoreError::from_win32("GetDC failed"));
-        }
+        Ok(rect)
+    }
+}
 
-        let memory_dc = CreateCompatibleDC(screen_dc);
-        if memory_dc.0 == 0 {
-            let _ = ReleaseDC(HWND(0), screen_dc);
-            return Err(CoreError::from_win32("CreateCompatibleDC failed"));
-        }
+/// Captures the virtual-screen rectangle at (`x`, `y`), clamped to the
+/// virtual screen.
+pub fn capture_region(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    options: &CaptureOptions,
+) -> CoreResult<ScreenshotResult> {
+    if width == 0 || height == 0 {
+        return Err(CoreError::InvalidArgument(
+            "capture region must have a non-zero width and height".to_string(),
+        ));
+    }
 
-        let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
-        if bitmap.0 == 0 {
-            let _ = DeleteDC(memory_dc);
-            let _ = ReleaseDC(HWND(0), screen_dc);
-            return Err(CoreError::from_win32("CreateCompatibleBitmap failed"));
-        }
+    let _dpi = DpiAwarenessScope::per_monitor();
+    let region = clamp_region(x, y, width, height, &virtual_screen_rect()).ok_or_else(|| {
+        CoreError::InvalidArgument("capture region lies outside the virtual screen".to_string())
+    })?;
+
+    unsafe { capture_screen_area(&region, options)?.encode(options) }
+}
+
+/// Intersects the `width` x `height` rectangle at (`x`, `y`) with `bounds`.
+///
+/// Virtual-screen coordinates go negative for monitors left of or above the
+/// primary one, so nothing here assumes a zero origin; the far edges are
+/// computed in i64 so huge sizes can't overflow.
+fn clamp_region(x: i32, y: i32, width: u32, height: u32, bounds: &RECT) -> Option<RECT> {
+    let left = x.max(bounds.left);
+    let top = y.max(bounds.top);
+    let right = (i64::from(x) + i64::from(width)).min(i64::from(bounds.right)) as i32;
+    let bottom = (i64::from(y) + i64::from(height)).min(i64::from(bounds.bottom)) as i32;
+    (right > left && bottom > top).then_some(RECT {
+        left,
+        top,
+        right,
+        bottom,
+    })
+}
+
+/// Where a capture of `rect` lands on the virtual screen, and its size in pixels.
+#[derive(Debug, PartialEq)]
+struct CaptureGeometry {
+    origin_x: i32,
+    origin_y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl CaptureGeometry {
+    /// `None` for an empty or inverted rect. Edges left of or above the primary
+    /// monitor are negative, which the subtraction handles as is.
+    fn of(rect: &RECT) -> Option<Self> {
+        let width = rect.right.checked_sub(rect.left)?;
+        let height = rect.bottom.checked_sub(rect.top)?;
+        (width > 0 && height > 0).then_some(Self {
+            origin_x: rect.left,
+            origin_y: rect.top,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+}
+
+/// Copies `rect` of the virtual screen.
+unsafe fn capture_screen_area(rect: &RECT, options: &CaptureOptions) -> CoreResult<RawCapture> {
+    let geometry = CaptureGeometry::of(rect)
+        .ok_or_else(|| CoreError::InvalidArgument("capture area is empty".to_string()))?;
+    capture_screen_rect(
+        geometry.origin_x,
+        geometry.origin_y,
+        geometry.width as i32,
+        geometry.height as i32,
+        options,
+    )
+}
+
+/// Captures the whole virtual desktop across every monitor in one image.
+pub fn capture_all_monitors(options: &CaptureOptions) -> CoreResult<ScreenshotResult> {
+    let _dpi = DpiAwarenessScope::per_monitor();
+    let screen = virtual_screen_rect();
+    let width = screen.right - screen.left;
+    let height = screen.bottom - screen.top;
+    if width <= 0 || height <= 0 {
+        return Err(CoreError::Other(anyhow::anyhow!(
+            "virtual screen dimensions invalid"
+        )));
+    }
+
+    let mut capture =
+        unsafe { capture_screen_rect(screen.left, screen.top, width, height, options)? };
+    capture.monitor = MonitorIdentity::virtual_screen();
+    capture.encode(options)
+}
+
+/// Captures a top-level window, even when it is occluded or partly off-screen.
+pub fn capture_window(handle: &str, options: &CaptureOptions) -> CoreResult<ScreenshotResult> {
+    let _dpi = DpiAwarenessScope::per_monitor();
+    let hwnd = existing_window(handle)?;
+
+    let mut rect = RECT::default();
+    unsafe {
+        GetWindowRect(hwnd, &mut rect)
+            .map_err(|_| CoreError::from_win32("GetWindowRect failed"))?;
+    }
+    let width = rect.right - rect.left;
+    let height = rect.bottom - rect.top;
+    if width <= 0 || height <= 0 {
+        return Err(CoreError::InvalidArgument(
+            "window has no visible area".to_string(),
+        ));
+    }
+
+    unsafe {
+        capture_rect(
+            rect.left,
+            rect.top,
+            width,
+            height,
+            options,
+            |memory_dc, screen_dc| {
+                // PW_RENDERFULLCONTENT asks DWM for the composed content, which also
+                // works for DirectX/Chromium windows. Some windows refuse; copying
+                // the visible screen area is better than nothing then.
+                if PrintWindow(hwnd, memory_dc, PRINT_WINDOW_FLAGS(PW_RENDERFULLCONTENT)).as_bool()
+                {
+                    return Ok(());
+                }
+                BitBlt(
+                    memory_dc, 0, 0, width, height, screen_dc, rect.left, rect.top, SRCCOPY,
+                )
+                .map_err(|_| CoreError::from_win32("PrintWindow and BitBlt failed"))
+            },
+        )?
+        .encode(options)
+    }
+}
+
+/// Captures just a window's client area, without title bar, borders or shadow.
+pub fn capture_window_client(
+    handle: &str,
+    options: &CaptureOptions,
+) -> CoreResult<ScreenshotResult> {
+    let _dpi = DpiAwarenessScope::per_monitor();
+    let hwnd = existing_window(handle)?;
 
-        let old = SelectObject(memory_dc, bitmap);
-        if old.0 == 0 {
-            let _ = DeleteObject(bitmap);
-            let _ = DeleteDC(memory_dc);
-            let _ = ReleaseDC(HWND(0), screen_dc);
-            return Err(CoreError::from_win32("SelectObject failed"));
+    let mut client = RECT::default();
+    let mut origin = POINT::default();
+    unsafe {
+        GetClientRect(hwnd, &mut client)
+            .map_err(|_| CoreError::from_win32("GetClientRect failed"))?;
+        if !ClientToScreen(hwnd, &mut origin).as_bool() {
+            return Err(CoreError::from_win32("ClientToScreen failed"));
         }
+    }
+    let width = client.right - client.left;
+    let height = client.bottom - client.top;
+    if width <= 0 || height <= 0 {
+        // Minimized windows report an empty client area.
+        return Err(CoreError::InvalidArgument(
+            "window client area is empty".to_string(),
+        ));
+    }
 
-        if BitBlt(
-            memory_dc, 0, 0, width, height, screen_dc, rect.left, rect.top, SRCCOPY,
-        )
-        .is_err()
-        {
-            SelectObject(memory_dc, old);
-            let _ = DeleteObject(bitmap);
-            let _ = DeleteDC(memory_dc);
-            let _ = ReleaseDC(HWND(0), screen_dc);
-            return Err(CoreError::from_win32("BitBlt failed"));
-        }
-
-        let mut bitmap_info = BITMAPINFO {
-            bmiHeader: BITMAPINFOHEADER {
-                biSize: size_of::<BITMAPINFOHEADER>() as u32,
-                biWidth: width,
-                biHeight: -height,
-                biPlanes: 1,
-                biBitCount: 32,
-                biCompression: BI_RGB.0,
-                biSizeImage: 0,
-                biXPelsPerMeter: 0,
-                biYPelsPerMeter: 0,
-                biClrUsed: 0,
-                biClrImportant: 0,
+    unsafe {
+        capture_rect(
+            origin.x,
+            origin.y,
+            width,
+            height,
+            options,
+            |memory_dc, screen_dc| {
+                let flags = PW_CLIENTONLY.0 | PW_RENDERFULLCONTENT;
+                if PrintWindow(hwnd, memory_dc, PRINT_WINDOW_FLAGS(flags)).as_bool() {
+                    return Ok(());
+                }
+                BitBlt(
+                    memory_dc, 0, 0, width, height, screen_dc, origin.x, origin.y, SRCCOPY,
+                )
+                .map_err(|_| CoreError::from_win32("PrintWindow and BitBlt failed"))
             },
-            bmiColors: [RGBQUAD::default(); 1],
-        };
+        )?
+        .encode(options)
+    }
+}
 
-        let mut buffer = vec![0u8; (width * height * 4) as usize];
-        let result = GetDIBits(
-            memory_dc,
-            bitmap,
-            0,
-            height as u32,
-            Some(buffer.as_mut_ptr().cast()),
-            &mut bitmap_info,
-            DIB_RGB_COLORS,
-        );
+fn existing_window(handle: &str) -> CoreResult<HWND> {
+    parse_window_handle(handle)?
+        .filter(|hwnd| unsafe { IsWindow(*hwnd) }.as_bool())
+        .ok_or_else(|| CoreError::NotFound(format!("window '{handle}' not found")))
+}
+
+/// Copies a rectangle of the screen DC.
+pub(crate) unsafe fn capture_screen_rect(
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    options: &CaptureOptions,
+) -> CoreResult<RawCapture> {
+    capture_rect(left, top, width, height, options, |memory_dc, screen_dc| {
+        BitBlt(
+            memory_dc, 0, 0, width, height, screen_dc, left, top, SRCCOPY,
+        )
+        .map_err(|_| CoreError::from_win32("BitBlt failed"))
+    })
+}
+
+/// Sets up a `width` x `height` bitmap for `draw` to fill (given the memory
+/// and screen DCs), then reads it back as RGBA positioned at (`left`, `top`).
+unsafe fn capture_rect(
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    options: &CaptureOptions,
+    draw: impl FnOnce(HDC, HDC) -> CoreResult<()>,
+) -> CoreResult<RawCapture> {
+    let screen_dc = GetDC(HWND(0));
+    if screen_dc.0 == 0 {
+        return Err(CoreError::from_win32("GetDC failed"));
+    }
+
+    let memory_dc = CreateCompatibleDC(screen_dc);
+    if memory_dc.0 == 0 {
+        let _ = ReleaseDC(HWND(0), screen_dc);
+        return Err(CoreError::from_win32("CreateCompatibleDC failed"));
+    }
+
+    let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
+    if bitmap.0 == 0 {
+        let _ = DeleteDC(memory_dc);
+        let _ = ReleaseDC(HWND(0), screen_dc);
+        return Err(CoreError::from_win32("CreateCompatibleBitmap failed"));
+    }
+
+    let old = SelectObject(memory_dc, bitmap);
+    if old.0 == 0 {
+        let _ = DeleteObject(bitmap);
+        let _ = DeleteDC(memory_dc);
+        let _ = ReleaseDC(HWND(0), screen_dc);
+        return Err(CoreError::from_win32("SelectObject failed"));
+    }
 
+    if let Err(err) = draw(memory_dc, screen_dc) {
         SelectObject(memory_dc, old);
         let _ = DeleteObject(bitmap);
         let _ = DeleteDC(memory_dc);
         let _ = ReleaseDC(HWND(0), screen_dc);
+        return Err(err);
+    }
+    if options.include_cursor {
+        draw_cursor(memory_dc, left, top);
+    }
+
+    let mut bitmap_info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            biHeight: -height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        },
+        bmiColors: [RGBQUAD::default(); 1],
+    };
+
+    let mut buffer = take_buffer((width * height * 4) as usize);
+    let result = GetDIBits(
+        memory_dc,
+        bitmap,
+        0,
+        height as u32,
+        Some(buffer.as_mut_ptr().cast()),
+        &mut bitmap_info,
+        DIB_RGB_COLORS,
+    );
+
+    SelectObject(memory_dc, old);
+    let _ = DeleteObject(bitmap);
+    let _ = DeleteDC(memory_dc);
+    let _ = ReleaseDC(HWND(0), screen_dc);
+
+    if result == 0 {
+        let err = CoreError::from_win32("GetDIBits failed");
+        recycle_buffer(buffer);
+        return Err(err);
+    }
+
+    // Convert BGRA to RGBA. GDI leaves the alpha byte undefined, but screen
+    // content is always opaque.
+    for chunk in buffer.chunks_exact_mut(4) {
+        chunk.swap(0, 2);
+        chunk[3] = 0xFF;
+    }
 
-        if result == 0 {
-            return Err(CoreError::from_win32("GetDIBits failed"));
+    let bounds = RECT {
+        left,
+        top,
+        right: left + width,
+        bottom: top + height,
+    };
+    let monitor = MonitorFromRect(&bounds, MONITOR_DEFAULTTONEAREST);
+    Ok(RawCapture {
+        width: width as u32,
+        height: height as u32,
+        origin_x: left,
+        origin_y: top,
+        rgba: buffer,
+        scale_factor: monitor_scale_factor(monitor),
+        monitor: monitor_identity(monitor),
+    })
+}
+
+/// Encodes RGBA pixels in the requested format, returning the bytes and MIME type.
+fn encode_image(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    options: &CaptureOptions,
+) -> CoreResult<(Vec<u8>, &'static str)> {
+    encode_rgba(rgba, width, height, options.format, options.quality)
+}
+
+/// Encodes RGBA pixels as PNG, JPEG (at `quality`, 1-100) or lossless WebP,
+/// returning the bytes and MIME type.
+pub(crate) fn encode_rgba(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+    quality: u8,
+) -> CoreResult<(Vec<u8>, &'static str)> {
+    // Screen content usually compresses to well under a quarter of its raw size.
+    let mut bytes = Vec::with_capacity(rgba.len() / 4);
+    let mime_type = match format {
+        ImageFormat::Png => {
+            PngEncoder::new(&mut bytes)
+                .write_image(rgba, width, height, ColorType::Rgba8)
+                .context("encode PNG failed")?;
+            "image/png"
+        }
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel; the encoder would simply drop it,
+            // turning transparent areas into whatever color they hide.
+            let flattened = flatten_onto_white(rgba);
+            JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100))
+                .write_image(
+                    flattened.as_deref().unwrap_or(rgba),
+                    width,
+                    height,
+                    ColorType::Rgba8,
+                )
+                .context("encode JPEG failed")?;
+            "image/jpeg"
         }
+        ImageFormat::WebP => {
+            WebPEncoder::new_lossless(&mut bytes)
+                .write_image(rgba, width, height, ColorType::Rgba8)
+                .context("encode WebP failed")?;
+            "image/webp"
+        }
+    };
+    Ok((bytes, mime_type))
+}
 
-        // Convert BGRA to RGBA
-        for chunk in buffer.chunks_exact_mut(4) {
-            chunk.swap(0, 2);
+/// Composites translucent pixels onto white; `None` when every pixel is
+/// already opaque, as with screen captures.
+fn flatten_onto_white(rgba: &[u8]) -> Option<Vec<u8>> {
+    if rgba.chunks_exact(4).all(|pixel| pixel[3] == 0xFF) {
+        return None;
+    }
+    let mut flattened = rgba.to_vec();
+    for pixel in flattened.chunks_exact_mut(4) {
+        let alpha = u32::from(pixel[3]);
+        for channel in &mut pixel[..3] {
+            *channel = ((u32::from(*channel) * alpha + 0xFF * (0xFF - alpha) + 127) / 0xFF) as u8;
         }
+        pixel[3] = 0xFF;
+    }
+    Some(flattened)
+}
 
-        let mut png_bytes = Vec::new();
-        {
-            let encoder = PngEncoder::new(&mut png_bytes);
-            encoder
-                .write_image(&buffer, width as u32, height as u32, ColorType::Rgba8)
-                .context("encode PNG failed")?;
+/// Draws the current cursor into a capture whose top-left corner is at
+/// (`origin_x`, `origin_y`) on screen. A hidden cursor draws nothing.
+unsafe fn draw_cursor(memory_dc: HDC, origin_x: i32, origin_y: i32) {
+    let mut cursor = CURSORINFO {
+        cbSize: size_of::<CURSORINFO>() as u32,
+        ..Default::default()
+    };
+    if GetCursorInfo(&mut cursor).is_err()
+        || cursor.flags != CURSOR_SHOWING
+        || cursor.hCursor.is_invalid()
+    {
+        return;
+    }
+    let hicon = HICON(cursor.hCursor.0);
+
+    // ptScreenPos is where the hotspot is, not the image's top-left corner.
+    let mut icon_info = ICONINFO::default();
+    if GetIconInfo(hicon, &mut icon_info).is_err() {
+        return;
+    }
+    let _ = DeleteObject(icon_info.hbmMask);
+    if !icon_info.hbmColor.is_invalid() {
+        let _ = DeleteObject(icon_info.hbmColor);
+    }
+
+    let _ = DrawIconEx(
+        memory_dc,
+        cursor.ptScreenPos.x - icon_info.xHotspot as i32 - origin_x,
+        cursor.ptScreenPos.y - icon_info.yHotspot as i32 - origin_y,
+        hicon,
+        0,
+        0,
+        0,
+        None,
+        DI_NORMAL,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A monitor left of the primary one plus the primary 1920x1080 monitor.
+    const VIRTUAL_SCREEN: RECT = RECT {
+        left: -1920,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+
+    fn edges(rect: RECT) -> (i32, i32, i32, i32) {
+        (rect.left, rect.top, rect.right, rect.bottom)
+    }
+
+    #[test]
+    fn take_buffer_picks_the_smallest_fitting_buffer() {
+        release_buffers();
+        for capacity in [4096, 1024, 2048] {
+            recycle_buffer(Vec::with_capacity(capacity));
         }
 
-        Ok(ScreenshotResult {
-            width: width as u32,
-            height: height as u32,
-            origin_x: rect.left,
-            origin_y: rect.top,
-            bytes: png_bytes,
-        })
+        let buffer = take_buffer(1500);
+        assert_eq!(buffer.len(), 1500);
+        assert_eq!(buffer.capacity(), 2048);
+        assert_eq!(take_buffer(1024).capacity(), 1024);
+        assert_eq!(take_buffer(1).capacity(), 4096);
+        release_buffers();
+    }
+
+    // Timing only; run with `cargo test --release -- --ignored --nocapture pooled_buffers`.
+    // Each frame is filled like GetDIBits would, so fresh allocations pay their
+    // page faults too.
+    #[test]
+    #[ignore]
+    fn pooled_buffers_beat_fresh_allocations() {
+        use std::time::Instant;
+
+        const FRAME: usize = 3840 * 2160 * 4;
+        const ROUNDS: u32 = 200;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let mut buffer = vec![0u8; FRAME];
+            buffer.fill(0xFF);
+            std::hint::black_box(buffer);
+        }
+        let fresh = start.elapsed();
+
+        release_buffers();
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let mut buffer = take_buffer(FRAME);
+            buffer.fill(0xFF);
+            recycle_buffer(std::hint::black_box(buffer));
+        }
+        let pooled = start.elapsed();
+        release_buffers();
+
+        println!(
+            "4K frame x{ROUNDS}: fresh {:?}/frame, pooled {:?}/frame",
+            fresh / ROUNDS,
+            pooled / ROUNDS
+        );
+    }
+
+    #[test]
+    fn clamp_region_keeps_negative_origins() {
+        let rect = clamp_region(-1000, 100, 500, 200, &VIRTUAL_SCREEN).unwrap();
+        assert_eq!(edges(rect), (-1000, 100, -500, 300));
+    }
+
+    #[test]
+    fn clamp_region_rejects_offscreen_regions() {
+        assert!(clamp_region(-3000, 0, 500, 500, &VIRTUAL_SCREEN).is_none());
+        assert!(clamp_region(0, 1080, 100, 100, &VIRTUAL_SCREEN).is_none());
+        assert!(clamp_region(0, 0, 0, 100, &VIRTUAL_SCREEN).is_none());
+    }
+
+    #[test]
+    fn clamp_region_trims_partial_overlap() {
+        let rect = clamp_region(-2000, -50, 300, 200, &VIRTUAL_SCREEN).unwrap();
+        assert_eq!(edges(rect), (-1920, 0, -1700, 150));
+
+        let rect = clamp_region(1800, 1000, 400, 400, &VIRTUAL_SCREEN).unwrap();
+        assert_eq!(edges(rect), (1800, 1000, 1920, 1080));
+    }
+
+    #[test]
+    fn capture_geometry_handles_negative_origins() {
+        let rect = RECT {
+            left: -1920,
+            top: -200,
+            right: 0,
+            bottom: 880,
+        };
+        let geometry = CaptureGeometry::of(&rect).unwrap();
+        assert_eq!(
+            geometry,
+            CaptureGeometry {
+                origin_x: -1920,
+                origin_y: -200,
+                width: 1920,
+                height: 1080,
+            }
+        );
+    }
+
+    #[test]
+    fn capture_geometry_of_a_clamped_region() {
+        let bounds = RECT {
+            left: -1920,
+            top: -200,
+            right: 1920,
+            bottom: 1080,
+        };
+        let region = clamp_region(-2500, -300, 1000, 500, &bounds).unwrap();
+        let geometry = CaptureGeometry::of(&region).unwrap();
+        assert_eq!(
+            (
+                geometry.origin_x,
+                geometry.origin_y,
+                geometry.width,
+                geometry.height
+            ),
+            (-1920, -200, 420, 400)
+        );
+    }
+
+    #[test]
+    fn capture_geometry_rejects_empty_rects() {
+        let empty = RECT {
+            left: -100,
+            top: -100,
+            right: -100,
+            bottom: 0,
+        };
+        assert!(CaptureGeometry::of(&empty).is_none());
+        let inverted = RECT {
+            left: 0,
+            top: 0,
+            right: -10,
+            bottom: 10,
+        };
+        assert!(CaptureGeometry::of(&inverted).is_none());
+    }
+
+    #[test]
+    fn clamp_region_does_not_overflow_on_huge_sizes() {
+        let rect = clamp_region(i32::MAX - 1, 0, u32::MAX, 10, &VIRTUAL_SCREEN);
+        assert!(rect.is_none());
+        let rect = clamp_region(0, 0, u32::MAX, u32::MAX, &VIRTUAL_SCREEN).unwrap();
+        assert_eq!(edges(rect), (0, 0, 1920, 1080));
     }
 }
@@ -0,0 +1,94 @@
+use std::{os::windows::process::CommandExt, path::Path, process::Command, ptr};
+
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::HWND,
+        UI::{
+            Shell::{ILFree, SHOpenFolderAndSelectItems, SHParseDisplayName, ShellExecuteW},
+            WindowsAndMessaging::SW_SHOWNORMAL,
+        },
+    },
+};
+
+use crate::{
+    com::{self, Apartment},
+    error::{CoreError, CoreResult},
+    utils::wide_string,
+};
+
+/// Opens the containing folder in Explorer with `path` selected. Accepts the
+/// forward-slash paths produced by the app index.
+pub fn reveal_in_explorer(path: &str) -> CoreResult<()> {
+    let native = path.trim().replace('/', "\\");
+    if native.is_empty() {
+        return Err(CoreError::InvalidArgument("path is empty".to_string()));
+    }
+    if !Path::new(&native).exists() {
+        return Err(CoreError::NotFound(format!("'{native}' does not exist")));
+    }
+
+    com::ensure_initialized(Apartment::Sta)?;
+    if let Err(err) = select_in_folder(&native) {
+        log::debug!("SHOpenFolderAndSelectItems failed, using explorer.exe: {err}");
+        // explorer.exe parses its own command line, so the path is quoted by hand.
+        Command::new("explorer.exe")
+            .raw_arg(format!("/select,\"{native}\""))
+            .spawn()?;
+    }
+    Ok(())
+}
+
+fn select_in_folder(path: &str) -> CoreResult<()> {
+    let wide = wide_string(path);
+    let mut pidl = ptr::null_mut();
+    unsafe {
+        SHParseDisplayName(PCWSTR(wide.as_ptr()), None, &mut pidl, 0, None)
+            .map_err(|err| CoreError::from_hresult("SHParseDisplayName failed", err.code()))?;
+        // With no child items, the folder holding `pidl` is opened and it is selected.
+        let result = SHOpenFolderAndSelectItems(pidl, None, 0);
+        ILFree(Some(pidl));
+        result
+            .map_err(|err| CoreError::from_hresult("SHOpenFolderAndSelectItems failed", err.code()))
+    }
+}
+
+/// Schemes `open_url` will hand to the shell. Anything else could start an
+/// arbitrary registered protocol handler.
+const ALLOWED_URL_SCHEMES: &[&str] =
+    &["http", "https", "mailto", "ms-settings", "ms-windows-store"];
+
+/// Opens `url` with its default handler, e.g. the default browser.
+pub fn open_url(url: &str) -> CoreResult<()> {
+    let url = url.trim();
+    let scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase())
+        .ok_or_else(|| CoreError::InvalidArgument(format!("'{url}' is not a URL")))?;
+    if !ALLOWED_URL_SCHEMES.contains(&scheme.as_str()) {
+        return Err(CoreError::InvalidArgument(format!(
+            "URL scheme '{scheme}' is not allowed"
+        )));
+    }
+
+    com::ensure_initialized(Apartment::Sta)?;
+    let wide = wide_string(url);
+    let result = unsafe {
+        ShellExecuteW(
+            HWND(0),
+            w!("open"),
+            PCWSTR(wide.as_ptr()),
+            PCWSTR::null(),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are error codes rather than instance handles.
+    if result.0 <= 32 {
+        return Err(CoreError::Windows(format!(
+            "ShellExecuteW failed (code {})",
+            result.0
+        )));
+    }
+    Ok(())
+}
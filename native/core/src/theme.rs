@@ -0,0 +1,230 @@
+use std::{cell::RefCell, ffi::c_void, sync::mpsc, thread};
+
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::{BOOL, HWND, LPARAM, LRESULT, WPARAM},
+        Graphics::Dwm::DwmGetColorizationColor,
+        System::{LibraryLoader::GetModuleHandleW, Threading::GetCurrentThreadId},
+        UI::{
+            Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW},
+            WindowsAndMessaging::{
+                CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+                PostThreadMessageW, RegisterClassW, SystemParametersInfoW, MSG,
+                SPI_GETHIGHCONTRAST, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, WINDOW_EX_STYLE,
+                WM_DWMCOLORIZATIONCOLORCHANGED, WM_QUIT, WM_SETTINGCHANGE, WM_SYSCOLORCHANGE,
+                WNDCLASSW, WS_OVERLAPPED,
+            },
+        },
+    },
+};
+use winreg::{enums::HKEY_CURRENT_USER, RegKey};
+
+use crate::{
+    error::{catch_callback_panic, CoreError, CoreResult},
+    Theme,
+};
+
+const PERSONALIZE_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+const DWM_KEY: &str = r"Software\Microsoft\Windows\DWM";
+
+pub fn system_theme() -> Theme {
+    // Missing on builds older than 1809, which only had a light app theme.
+    let apps_use_light_theme = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(PERSONALIZE_KEY)
+        .and_then(|key| key.get_value::<u32, _>("AppsUseLightTheme"))
+        .unwrap_or(1);
+
+    Theme {
+        dark: apps_use_light_theme == 0,
+        high_contrast: high_contrast_enabled(),
+    }
+}
+
+fn high_contrast_enabled() -> bool {
+    let mut info = HIGHCONTRASTW {
+        cbSize: size_of::<HIGHCONTRASTW>() as u32,
+        ..Default::default()
+    };
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            info.cbSize,
+            Some(&mut info as *mut HIGHCONTRASTW as *mut c_void),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    }
+    .is_ok()
+        && info.dwFlags.contains(HCF_HIGHCONTRASTON)
+}
+
+/// The window-frame accent as `0xAARRGGBB`.
+pub fn accent_color() -> Option<u32> {
+    let mut color = 0u32;
+    let mut opaque = BOOL::default();
+    if unsafe { DwmGetColorizationColor(&mut color, &mut opaque) }.is_ok() {
+        return Some(color);
+    }
+    // DWM composition can be unavailable (e.g. some RDP sessions); the stored
+    // value uses the same layout.
+    RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(DWM_KEY)
+        .and_then(|key| key.get_value::<u32, _>("ColorizationColor"))
+        .ok()
+}
+
+static THEME_WATCHER: Lazy<Mutex<Option<ThemeWatcher>>> = Lazy::new(|| Mutex::new(None));
+
+struct ThemeWatcher {
+    thread_id: u32,
+    handle: thread::JoinHandle<()>,
+}
+
+impl ThemeWatcher {
+    fn stop(self) {
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        self.handle.join().ok();
+    }
+}
+
+struct ThemeListener {
+    callback: ThreadsafeFunction<Theme>,
+    last: (bool, bool, Option<u32>),
+}
+
+thread_local! {
+    static THEME_LISTENER: RefCell<Option<ThemeListener>> = const { RefCell::new(None) };
+}
+
+const LISTENER_CLASS_NAME: PCWSTR = w!("WolongThemeListener");
+
+static LISTENER_CLASS: Lazy<u16> = Lazy::new(|| unsafe {
+    let instance = GetModuleHandleW(None).unwrap_or_default();
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(theme_listener_proc),
+        hInstance: instance.into(),
+        lpszClassName: LISTENER_CLASS_NAME,
+        ..Default::default()
+    };
+    RegisterClassW(&class)
+});
+
+/// Emits the theme whenever dark mode, high contrast or the accent color
+/// changes. Calling it again replaces the previous callback.
+pub fn watch_theme_changes(callback: ThreadsafeFunction<Theme>) -> CoreResult<()> {
+    let mut guard = THEME_WATCHER.lock();
+    if let Some(watcher) = guard.take() {
+        watcher.stop();
+    }
+
+    let (ready, started) = mpsc::channel();
+    let handle = thread::Builder::new()
+        .name("wolong-theme".to_string())
+        .spawn(move || run_theme_listener(callback, ready))
+        .map_err(|err| CoreError::Other(anyhow::anyhow!("spawn theme thread failed: {err}")))?;
+
+    let thread_id = started
+        .recv()
+        .map_err(|_| CoreError::Other(anyhow::anyhow!("theme thread exited during startup")))??;
+    *guard = Some(ThemeWatcher { thread_id, handle });
+    Ok(())
+}
+
+pub fn unwatch_theme_changes() {
+    if let Some(watcher) = THEME_WATCHER.lock().take() {
+        watcher.stop();
+    }
+}
+
+fn run_theme_listener(callback: ThreadsafeFunction<Theme>, ready: mpsc::Sender<CoreResult<u32>>) {
+    if *LISTENER_CLASS == 0 {
+        let _ = ready.send(Err(CoreError::Windows(
+            "RegisterClassW theme listener failed".to_string(),
+        )));
+        return;
+    }
+
+    // Setting changes are broadcast to top-level windows only, so this can't be
+    // a message-only window; it is simply never shown.
+    let hwnd = unsafe {
+        CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            LISTENER_CLASS_NAME,
+            PCWSTR::null(),
+            WS_OVERLAPPED,
+            0,
+            0,
+            0,
+            0,
+            HWND(0),
+            None,
+            GetModuleHandleW(None).unwrap_or_default(),
+            None,
+        )
+    };
+    if hwnd.0 == 0 {
+        let _ = ready.send(Err(CoreError::from_win32(
+            "CreateWindowExW theme listener failed",
+        )));
+        return;
+    }
+
+    let theme = system_theme();
+    THEME_LISTENER.with(|slot| {
+        *slot.borrow_mut() = Some(ThemeListener {
+            callback,
+            last: (theme.dark, theme.high_contrast, accent_color()),
+        })
+    });
+    let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+
+    let mut msg = MSG::default();
+    loop {
+        let result = unsafe { GetMessageW(&mut msg, HWND(0), 0, 0) };
+        if result.0 <= 0 {
+            break;
+        }
+        unsafe {
+            DispatchMessageW(&msg);
+        }
+    }
+
+    unsafe {
+        let _ = DestroyWindow(hwnd);
+    }
+    THEME_LISTENER.with(|slot| *slot.borrow_mut() = None);
+}
+
+unsafe extern "system" fn theme_listener_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if matches!(
+        msg,
+        WM_SETTINGCHANGE | WM_SYSCOLORCHANGE | WM_DWMCOLORIZATIONCOLORCHANGED
+    ) {
+        // These arrive in bursts for unrelated settings; only report real changes.
+        catch_callback_panic("theme listener", || {
+            THEME_LISTENER.with(|slot| {
+                if let Some(listener) = slot.borrow_mut().as_mut() {
+                    let theme = system_theme();
+                    let current = (theme.dark, theme.high_contrast, accent_color());
+                    if current != listener.last {
+                        listener.last = current;
+                        let _ = listener
+                            .callback
+                            .call(Ok(theme), ThreadsafeFunctionCallMode::NonBlocking);
+                    }
+                }
+            });
+        });
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
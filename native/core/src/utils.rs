@@ -1,11 +1,44 @@
 use std::{collections::HashMap, ffi::OsStr, os::windows::prelude::OsStrExt, path::Path};
 
 use base64::{engine::general_purpose, Engine as _};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
 use sha2::{Digest, Sha256};
+use windows::{
+    core::PWSTR,
+    Win32::{
+        Foundation::{CloseHandle, HANDLE, HWND},
+        Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+        System::Com::CoTaskMemFree,
+        System::Threading::{
+            GetCurrentProcess, OpenProcess, OpenProcessToken, QueryFullProcessImageNameW,
+            PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+        },
+        UI::{
+            Shell::{
+                FOLDERID_CommonPrograms, FOLDERID_CommonStartup, FOLDERID_Desktop,
+                FOLDERID_Documents, FOLDERID_Downloads, FOLDERID_LocalAppData,
+                FOLDERID_ProgramData, FOLDERID_ProgramFiles, FOLDERID_Programs,
+                FOLDERID_PublicDesktop, FOLDERID_Recent, FOLDERID_RoamingAppData, FOLDERID_Startup,
+                SHGetKnownFolderPath, KF_FLAG_DEFAULT,
+            },
+            WindowsAndMessaging::{GetWindowTextW, GetWindowThreadProcessId},
+        },
+    },
+};
+
+use crate::KnownFolder;
+
+/// Separates parts so `["a/b", "c"]` and `["a", "b/c"]` hash differently; it
+/// can't appear in paths or display names.
+const ID_PART_SEPARATOR: u8 = 0x1f;
 
 pub fn hash_id(parts: &[&str]) -> String {
     let mut hasher = Sha256::new();
-    for part in parts {
+    for (index, part) in parts.iter().enumerate() {
+        if index > 0 {
+            hasher.update([ID_PART_SEPARATOR]);
+        }
         hasher.update(part.as_bytes());
     }
     general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize())
@@ -23,8 +56,32 @@ pub fn wide_string(value: &str) -> Vec<u16> {
     wide
 }
 
+/// Uses forward slashes throughout, except that a `\\server\` prefix is kept
+/// as is. Verbatim `\\?\` and `\\?\UNC\` paths and `\\.\` device paths keep
+/// backslashes everywhere: Windows does no separator translation after those
+/// prefixes, so a forward slash there would not open.
 pub fn normalize_path(path: &Path) -> String {
-    path.to_string_lossy().replace('\\', "/")
+    let raw = path.to_string_lossy();
+    let (prefix, rest) = split_path_prefix(&raw);
+    if prefix.starts_with(r"\\?\") || prefix.starts_with(r"\\.\") {
+        return format!("{prefix}{}", rest.replace('/', "\\"));
+    }
+    format!("{prefix}{}", rest.replace('\\', "/"))
+}
+
+fn split_path_prefix(path: &str) -> (&str, &str) {
+    for prefix in [r"\\?\UNC\", r"\\?\", r"\\.\"] {
+        if path
+            .get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+        {
+            return path.split_at(prefix.len());
+        }
+    }
+    if path.starts_with(r"\\") {
+        return path.split_at(2);
+    }
+    ("", path)
 }
 
 pub fn string_from_wide(buffer: &[u16]) -> Option<String> {
@@ -37,10 +94,20 @@ pub fn string_from_wide(buffer: &[u16]) -> Option<String> {
         .map(|s| s.trim().to_string())
 }
 
+/// Snapshot of the process environment for `expand_env_vars`, which runs for
+/// every path candidate during a scan.
+static ENV_CACHE: Lazy<RwLock<HashMap<String, String>>> =
+    Lazy::new(|| RwLock::new(std::env::vars().collect()));
+
+/// Re-reads the process environment after it has been changed.
+pub fn refresh_env_cache() {
+    *ENV_CACHE.write() = std::env::vars().collect();
+}
+
 pub fn expand_env_vars(value: &str) -> String {
     let mut result = String::with_capacity(value.len());
     let mut chars = value.chars().peekable();
-    let env_map: HashMap<String, String> = std::env::vars().collect();
+    let env_map = ENV_CACHE.read();
 
     while let Some(ch) = chars.next() {
         if ch == '%' {
@@ -70,3 +137,269 @@ pub fn expand_env_vars(value: &str) -> String {
 
     result
 }
+
+/// Splits a command line such as `"C:\Program Files\App\app.exe" --flag` into the
+/// executable and its arguments. An unquoted executable may contain spaces if it
+/// ends in `.exe`, as registry values often do.
+pub fn parse_command_line(raw: &str) -> (String, Vec<String>) {
+    let raw = raw.trim();
+    let (exe, rest) = match raw.strip_prefix('"') {
+        Some(quoted) => match quoted.find('"') {
+            Some(end) => (&quoted[..end], &quoted[end + 1..]),
+            None => (quoted, ""),
+        },
+        None => {
+            let end = unquoted_exe_end(raw)
+                .or_else(|| raw.find(char::is_whitespace))
+                .unwrap_or(raw.len());
+            raw.split_at(end)
+        }
+    };
+    (exe.trim().to_string(), split_arguments(rest))
+}
+
+fn unquoted_exe_end(raw: &str) -> Option<usize> {
+    let lower = raw.to_ascii_lowercase();
+    lower
+        .match_indices(".exe")
+        .map(|(index, _)| index + ".exe".len())
+        .find(|&end| {
+            lower[end..]
+                .chars()
+                .next()
+                .is_none_or(|next| next.is_whitespace() || next == ',')
+        })
+}
+
+/// Whitespace-separated, with `"` grouping and `\"` for a literal quote.
+fn split_arguments(raw: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+    let mut chars = raw.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&'"') => {
+                chars.next();
+                current.push('"');
+                has_arg = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            ch if ch.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            ch => {
+                current.push(ch);
+                has_arg = true;
+            }
+        }
+    }
+    if has_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Resolves the full executable path of a process, if we're allowed to query it.
+pub fn process_image_path(pid: u32) -> Option<String> {
+    if pid == 0 {
+        return None;
+    }
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut buffer = [0u16; 1024];
+        let mut size = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut size,
+        );
+        let _ = CloseHandle(process);
+        result.ok()?;
+        string_from_wide(&buffer[..size as usize])
+    }
+}
+
+/// Returns the owning process id of a window, or `None` if the window is gone.
+pub fn window_process_id(hwnd: HWND) -> Option<u32> {
+    if hwnd.0 == 0 {
+        return None;
+    }
+    let mut pid = 0u32;
+    unsafe {
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    }
+    (pid != 0).then_some(pid)
+}
+
+pub fn window_title(hwnd: HWND) -> Option<String> {
+    if hwnd.0 == 0 {
+        return None;
+    }
+    let mut buffer = [0u16; 512];
+    let len = unsafe { GetWindowTextW(hwnd, &mut buffer) };
+    if len <= 0 {
+        return None;
+    }
+    string_from_wide(&buffer[..len as usize])
+}
+
+/// Whether this process runs with an elevated (administrator) token. Elevated
+/// windows don't accept drag-and-drop from a non-elevated Explorer (UIPI).
+pub fn is_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut size = 0u32;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut _),
+            size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        let _ = CloseHandle(token);
+        result.is_ok() && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Resolves a shell folder the way Explorer does, honouring localized names and
+/// folder redirection.
+pub fn known_folder_path(folder: KnownFolder) -> Option<String> {
+    let id = match folder {
+        KnownFolder::CommonPrograms => &FOLDERID_CommonPrograms,
+        KnownFolder::Programs => &FOLDERID_Programs,
+        KnownFolder::Desktop => &FOLDERID_Desktop,
+        KnownFolder::PublicDesktop => &FOLDERID_PublicDesktop,
+        KnownFolder::Startup => &FOLDERID_Startup,
+        KnownFolder::CommonStartup => &FOLDERID_CommonStartup,
+        KnownFolder::Documents => &FOLDERID_Documents,
+        KnownFolder::Downloads => &FOLDERID_Downloads,
+        KnownFolder::LocalAppData => &FOLDERID_LocalAppData,
+        KnownFolder::RoamingAppData => &FOLDERID_RoamingAppData,
+        KnownFolder::ProgramData => &FOLDERID_ProgramData,
+        KnownFolder::ProgramFiles => &FOLDERID_ProgramFiles,
+        KnownFolder::Recent => &FOLDERID_Recent,
+    };
+
+    unsafe {
+        let path = SHGetKnownFolderPath(id, KF_FLAG_DEFAULT, None).ok()?;
+        let value = path.to_string().ok();
+        CoTaskMemFree(Some(path.0 as *const _));
+        value.filter(|value| !value.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_id_separates_parts() {
+        assert_ne!(
+            hash_id(&["start_menu", "a/b"]),
+            hash_id(&["start_menu/a", "b"])
+        );
+        assert_ne!(hash_id(&["ab", "c"]), hash_id(&["a", "bc"]));
+        assert_eq!(
+            hash_id(&["start_menu", "a/b"]),
+            hash_id(&["start_menu", "a/b"])
+        );
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_command_line_quoted_exe_with_arguments() {
+        assert_eq!(
+            parse_command_line(r#""C:\Program Files\App\app.exe" --flag "two words""#),
+            (
+                r"C:\Program Files\App\app.exe".to_string(),
+                args(&["--flag", "two words"])
+            )
+        );
+    }
+
+    #[test]
+    fn parse_command_line_unquoted_exe_with_spaces() {
+        assert_eq!(
+            parse_command_line(r"C:\Program Files\App\app.exe --x"),
+            (r"C:\Program Files\App\app.exe".to_string(), args(&["--x"]))
+        );
+    }
+
+    #[test]
+    fn parse_command_line_stops_at_icon_index() {
+        let (exe, _) = parse_command_line(r"C:\Program Files\App\app.exe,0");
+        assert_eq!(exe, r"C:\Program Files\App\app.exe");
+        let (exe, _) = parse_command_line(r#""C:\Program Files\App\app.exe",-101"#);
+        assert_eq!(exe, r"C:\Program Files\App\app.exe");
+    }
+
+    #[test]
+    fn parse_command_line_unterminated_quote_takes_the_rest() {
+        assert_eq!(
+            parse_command_line(r#""C:\Program Files\App\app.exe"#),
+            (r"C:\Program Files\App\app.exe".to_string(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn parse_command_line_escaped_quotes_in_arguments() {
+        assert_eq!(
+            parse_command_line(r#"app.exe --title "say \"hi\" now" \"bare\""#),
+            (
+                "app.exe".to_string(),
+                args(&["--title", r#"say "hi" now"#, r#""bare""#])
+            )
+        );
+    }
+
+    #[test]
+    fn normalize_path_keeps_long_paths_verbatim() {
+        let long = format!(r"\\?\C:\{}\file.txt", ["segment"; 40].join(r"\"));
+        assert!(long.len() > 300);
+        assert_eq!(normalize_path(Path::new(&long)), long);
+    }
+
+    #[test]
+    fn normalize_path_keeps_verbatim_unc_paths() {
+        let path = r"\\?\UNC\server\share\dir\app.exe";
+        assert_eq!(normalize_path(Path::new(path)), path);
+    }
+
+    #[test]
+    fn normalize_path_keeps_unc_server_prefix() {
+        assert_eq!(
+            normalize_path(Path::new(r"\\server\share\dir\app.exe")),
+            r"\\server/share/dir/app.exe"
+        );
+    }
+
+    #[test]
+    fn normalize_path_converts_drive_relative_paths() {
+        assert_eq!(
+            normalize_path(Path::new(r"C:tools\app.exe")),
+            "C:tools/app.exe"
+        );
+        assert_eq!(
+            normalize_path(Path::new(r"C:\tools\app.exe")),
+            "C:/tools/app.exe"
+        );
+    }
+}